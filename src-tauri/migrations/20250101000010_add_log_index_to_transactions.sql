@@ -0,0 +1,90 @@
+-- Allow several rows per on-chain transaction (ERC-20 transfer logs, Substrate events)
+-- Rows sharing a hash belong to the same parent transaction; log_index tells them apart.
+-- log_index is -1 for the transaction itself, otherwise the log or event index.
+
+-- SQLite cannot change a UNIQUE constraint in place, so rebuild the table.
+-- xcm_transfers references transactions(id): park its rows while the parent is swapped.
+CREATE TEMP TABLE xcm_transfers_backup AS SELECT * FROM xcm_transfers;
+DELETE FROM xcm_transfers;
+
+DROP VIEW IF EXISTS transactions_with_conversions;
+
+CREATE TABLE transactions_new (
+    id TEXT PRIMARY KEY,
+    profile_id TEXT NOT NULL,
+    chain TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    log_index INTEGER NOT NULL DEFAULT -1,
+    block_number INTEGER NOT NULL,
+    timestamp DATETIME NOT NULL,
+    from_address TEXT NOT NULL,
+    to_address TEXT,
+    value TEXT NOT NULL,
+    token_symbol TEXT NOT NULL,
+    token_decimals INTEGER NOT NULL,
+    transaction_type TEXT NOT NULL,
+    status TEXT NOT NULL,
+    fee TEXT,
+    metadata TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    amount_primary TEXT,
+    primary_currency TEXT DEFAULT 'USD',
+    exchange_rate TEXT,
+    exchange_rate_source TEXT,
+    exchange_rate_timestamp DATETIME,
+    original_amount TEXT,
+    original_currency TEXT,
+    converted_amount TEXT,
+    conversion_rate TEXT,
+    rate_timestamp DATETIME,
+    rate_source TEXT,
+    UNIQUE(chain, hash, log_index),
+    FOREIGN KEY (profile_id) REFERENCES profiles (id)
+);
+
+INSERT INTO transactions_new (
+    id, profile_id, chain, hash, log_index, block_number, timestamp,
+    from_address, to_address, value, token_symbol, token_decimals,
+    transaction_type, status, fee, metadata, created_at, updated_at,
+    amount_primary, primary_currency, exchange_rate, exchange_rate_source,
+    exchange_rate_timestamp, original_amount, original_currency,
+    converted_amount, conversion_rate, rate_timestamp, rate_source
+)
+SELECT
+    id, profile_id, chain, hash, -1, block_number, timestamp,
+    from_address, to_address, value, token_symbol, token_decimals,
+    transaction_type, status, fee, metadata, created_at, updated_at,
+    amount_primary, primary_currency, exchange_rate, exchange_rate_source,
+    exchange_rate_timestamp, original_amount, original_currency,
+    converted_amount, conversion_rate, rate_timestamp, rate_source
+FROM transactions;
+
+DROP TABLE transactions;
+ALTER TABLE transactions_new RENAME TO transactions;
+
+INSERT INTO xcm_transfers SELECT * FROM xcm_transfers_backup;
+DROP TABLE xcm_transfers_backup;
+
+CREATE INDEX IF NOT EXISTS idx_transactions_token_symbol ON transactions(token_symbol);
+CREATE INDEX IF NOT EXISTS idx_transactions_primary_currency ON transactions(primary_currency);
+CREATE INDEX IF NOT EXISTS idx_transactions_timestamp ON transactions(timestamp);
+CREATE INDEX IF NOT EXISTS idx_transactions_currency ON transactions(original_currency);
+CREATE INDEX IF NOT EXISTS idx_transactions_rate_timestamp ON transactions(rate_timestamp);
+
+-- Look up all rows of a parent transaction
+CREATE INDEX IF NOT EXISTS idx_transactions_hash ON transactions(chain, hash);
+
+CREATE VIEW IF NOT EXISTS transactions_with_conversions AS
+SELECT
+    t.*,
+    c.name AS currency_name,
+    c.type AS currency_type,
+    c.symbol AS currency_symbol,
+    CASE
+        WHEN t.exchange_rate IS NOT NULL AND t.exchange_rate != ''
+        THEN CAST(t.value AS REAL) * CAST(t.exchange_rate AS REAL)
+        ELSE CAST(t.value AS REAL)
+    END AS calculated_primary_amount
+FROM transactions t
+LEFT JOIN currencies c ON t.token_symbol = c.code;
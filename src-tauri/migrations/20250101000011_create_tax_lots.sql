@@ -0,0 +1,65 @@
+-- Cost basis method used to match disposals against acquisition lots
+ALTER TABLE account_settings ADD COLUMN cost_basis_method TEXT NOT NULL DEFAULT 'fifo'
+    CHECK(cost_basis_method IN ('fifo', 'lifo', 'hifo', 'average'));
+-- fifo: First in, first out
+-- lifo: Last in, first out
+-- hifo: Highest unit cost first
+-- average: Pooled average cost
+
+-- Acquisition lots built from incoming transactions
+-- Quantities are whole tokens and fiat amounts are in fiat_currency,
+-- both stored as strings to preserve precision
+-- Each lot or disposal is keyed by the transaction it was built from
+CREATE TABLE IF NOT EXISTS tax_lots (
+    transaction_id TEXT PRIMARY KEY,
+    profile_id TEXT NOT NULL,
+    asset TEXT NOT NULL,
+    chain TEXT NOT NULL,
+    acquired_at DATETIME NOT NULL,
+    quantity TEXT NOT NULL,
+    remaining_quantity TEXT NOT NULL,
+    cost_basis TEXT NOT NULL,
+    fiat_currency TEXT NOT NULL,
+    priced BOOLEAN NOT NULL DEFAULT 1,  -- 0 when no fiat value was stored for the transaction
+    method TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (profile_id) REFERENCES profiles(id)
+);
+
+-- Disposals with their realised gain or loss
+CREATE TABLE IF NOT EXISTS tax_disposals (
+    transaction_id TEXT PRIMARY KEY,
+    profile_id TEXT NOT NULL,
+    asset TEXT NOT NULL,
+    chain TEXT NOT NULL,
+    disposed_at DATETIME NOT NULL,
+    quantity TEXT NOT NULL,
+    proceeds TEXT NOT NULL,
+    cost_basis TEXT NOT NULL,
+    gain TEXT NOT NULL,
+    -- Quantity disposed beyond the known lots, carried at zero cost basis
+    unmatched_quantity TEXT NOT NULL DEFAULT '0',
+    fiat_currency TEXT NOT NULL,
+    priced BOOLEAN NOT NULL DEFAULT 1,
+    method TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (profile_id) REFERENCES profiles(id)
+);
+
+-- Which lots each disposal consumed, and how much of them
+CREATE TABLE IF NOT EXISTS tax_lot_matches (
+    disposal_id TEXT NOT NULL,
+    lot_id TEXT NOT NULL,
+    quantity TEXT NOT NULL,
+    cost_basis TEXT NOT NULL,
+    acquired_at DATETIME NOT NULL,
+    PRIMARY KEY (disposal_id, lot_id),
+    FOREIGN KEY (disposal_id) REFERENCES tax_disposals(transaction_id) ON DELETE CASCADE,
+    FOREIGN KEY (lot_id) REFERENCES tax_lots(transaction_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_tax_lots_profile
+ON tax_lots(profile_id, asset);
+
+CREATE INDEX IF NOT EXISTS idx_tax_disposals_profile
+ON tax_disposals(profile_id, disposed_at);
@@ -0,0 +1,47 @@
+-- Chart of accounts per profile, codes follow the frontend chart of accounts templates
+CREATE TABLE IF NOT EXISTS ledger_accounts (
+    profile_id TEXT NOT NULL,
+    code TEXT NOT NULL,
+    name TEXT NOT NULL,
+    account_type TEXT NOT NULL CHECK(account_type IN ('asset', 'liability', 'equity', 'income', 'expense')),
+    is_active BOOLEAN NOT NULL DEFAULT 1,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (profile_id, code),
+    FOREIGN KEY (profile_id) REFERENCES profiles(id)
+);
+
+-- Journal entries, either generated from a transaction or entered manually
+CREATE TABLE IF NOT EXISTS journal_entries (
+    id TEXT PRIMARY KEY,
+    profile_id TEXT NOT NULL,
+    transaction_id TEXT,  -- NULL for manual entries
+    entry_date DATETIME NOT NULL,
+    description TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (profile_id) REFERENCES profiles(id)
+);
+
+-- Debit and credit lines of a journal entry, amounts in the entry currency
+CREATE TABLE IF NOT EXISTS journal_postings (
+    entry_id TEXT NOT NULL,
+    line INTEGER NOT NULL,
+    profile_id TEXT NOT NULL,
+    account_code TEXT NOT NULL,
+    debit TEXT NOT NULL DEFAULT '0',
+    credit TEXT NOT NULL DEFAULT '0',
+    asset TEXT,  -- Token the line refers to, if any
+    PRIMARY KEY (entry_id, line),
+    FOREIGN KEY (entry_id) REFERENCES journal_entries(id) ON DELETE CASCADE,
+    FOREIGN KEY (profile_id, account_code) REFERENCES ledger_accounts(profile_id, code)
+);
+
+CREATE INDEX IF NOT EXISTS idx_journal_entries_profile_date
+ON journal_entries(profile_id, entry_date);
+
+CREATE INDEX IF NOT EXISTS idx_journal_entries_transaction
+ON journal_entries(transaction_id);
+
+CREATE INDEX IF NOT EXISTS idx_journal_postings_account
+ON journal_postings(profile_id, account_code);
@@ -0,0 +1,21 @@
+-- Encrypted secrets such as price feed API keys, see core::secrets
+CREATE TABLE IF NOT EXISTS secrets (
+    profile_id TEXT NOT NULL,
+    name TEXT NOT NULL,  -- e.g. 'coingecko_api_key'
+    ciphertext BLOB NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (profile_id, name),
+    FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE
+);
+
+-- Single row holding an encrypted known value, used to check the passphrase on unlock
+CREATE TABLE IF NOT EXISTS secret_store (
+    id INTEGER PRIMARY KEY CHECK(id = 1),
+    verifier BLOB NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+-- account_settings.coingecko_api_key and fixer_api_key are no longer written.
+-- Values already there are moved into secrets the first time the store is unlocked.
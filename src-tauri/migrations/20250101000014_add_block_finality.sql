@@ -0,0 +1,18 @@
+-- Hashes of indexed blocks, used to spot chain reorganizations.
+-- Holds each sync chunk's last block and every block with indexed activity;
+-- final blocks are pruned down to the highest one.
+CREATE TABLE IF NOT EXISTS indexed_blocks (
+    chain TEXT NOT NULL,
+    number INTEGER NOT NULL,
+    hash TEXT NOT NULL,
+    parent_hash TEXT NOT NULL,
+    is_final BOOLEAN NOT NULL DEFAULT 0,  -- At or below the chain's finalized head
+    indexed_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chain, number)
+);
+
+CREATE INDEX IF NOT EXISTS idx_indexed_blocks_pending
+ON indexed_blocks(chain, is_final, number);
+
+-- Highest synced block that is also final; data above it may still be rolled back
+ALTER TABLE sync_status ADD COLUMN finalized_block INTEGER NOT NULL DEFAULT 0;
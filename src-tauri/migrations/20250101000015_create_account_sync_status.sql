@@ -0,0 +1,28 @@
+-- Sync checkpoints per account. sync_status was keyed by (profile_id, chain),
+-- so a second address on the same chain started from the first one's checkpoint.
+CREATE TABLE IF NOT EXISTS account_sync_status (
+    account_id TEXT PRIMARY KEY,
+    start_block INTEGER NOT NULL DEFAULT 0,  -- First block to fetch, e.g. the account's first activity
+    last_synced_block INTEGER,  -- NULL until the first chunk is saved
+    finalized_block INTEGER NOT NULL DEFAULT 0,
+    last_sync_time DATETIME,
+    last_error TEXT,  -- Cleared by the next successful sync
+    last_error_at DATETIME,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_accounts_profile_chain_address
+ON accounts(profile_id, chain, address);
+
+-- A shared checkpoint is only known to be right when the profile has a
+-- single account on the chain, the others sync again from their start block
+INSERT INTO account_sync_status (account_id, last_synced_block, finalized_block, last_sync_time)
+SELECT a.id, s.last_synced_block, s.finalized_block, s.last_sync_time
+FROM sync_status s
+JOIN accounts a ON a.profile_id = s.profile_id AND a.chain = s.chain
+WHERE (
+    SELECT COUNT(*) FROM accounts b
+    WHERE b.profile_id = s.profile_id AND b.chain = s.chain
+) = 1;
+
+DROP TABLE sync_status;
@@ -0,0 +1,14 @@
+-- Free-form labels on transactions, e.g. "gift" or "airdrop"
+CREATE TABLE IF NOT EXISTS transaction_tags (
+    transaction_id TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (transaction_id, tag),
+    FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_transaction_tags_tag
+ON transaction_tags(tag);
+
+-- Keyset pagination over a profile's history
+CREATE INDEX IF NOT EXISTS idx_transactions_profile_timestamp
+ON transactions(profile_id, timestamp, id);
@@ -0,0 +1,73 @@
+-- Fold the tables of 0009 (currencies_mvp, exchange_rates_mvp and
+-- account_currency_settings) into currencies, exchange_rates and
+-- account_settings, which the services read and write.
+
+-- Substrate attributes move to the one currency registry
+ALTER TABLE currencies ADD COLUMN is_substrate BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE currencies ADD COLUMN chain_id TEXT;  -- e.g. 'polkadot', 'kusama'
+
+INSERT OR IGNORE INTO currencies (
+    id, code, name, type, decimals, is_supported, coingecko_id, fixer_id,
+    symbol, is_substrate, chain_id, created_at, updated_at
+)
+SELECT
+    id, code, name, type, decimals, is_active, coingecko_id, fixer_id,
+    symbol, COALESCE(is_substrate, 0), chain_id, created_at, updated_at
+FROM currencies_mvp
+WHERE code NOT IN (SELECT code FROM currencies);
+
+UPDATE currencies SET
+    is_substrate = COALESCE((SELECT m.is_substrate FROM currencies_mvp m WHERE m.code = currencies.code), 0),
+    chain_id = (SELECT m.chain_id FROM currencies_mvp m WHERE m.code = currencies.code)
+WHERE code IN (SELECT code FROM currencies_mvp);
+
+CREATE INDEX IF NOT EXISTS idx_currencies_chain ON currencies(chain_id);
+
+-- 'calculated' rates are what exchange_rates calls 'compound'. The '-init'
+-- rows are 0009's example rates, not rates anyone entered.
+INSERT OR IGNORE INTO exchange_rates (
+    id, from_currency, to_currency, rate, timestamp, source, ttl_seconds,
+    metadata, created_at, updated_at
+)
+SELECT
+    id, from_currency, to_currency, rate, timestamp,
+    CASE source WHEN 'calculated' THEN 'compound' ELSE source END,
+    ttl_seconds, metadata, created_at, created_at
+FROM exchange_rates_mvp
+WHERE id NOT LIKE '%-init';
+
+-- Profiles with settings in both tables keep their account_settings row
+INSERT OR IGNORE INTO account_settings (
+    id, profile_id, primary_currency, conversion_method, decimal_places,
+    use_thousands_separator, created_at, updated_at
+)
+SELECT
+    id, profile_id, primary_currency, conversion_method, decimal_places,
+    use_thousands_separator, created_at, updated_at
+FROM account_currency_settings
+WHERE profile_id NOT IN (SELECT profile_id FROM account_settings);
+
+-- Plain text API keys go where the secret store picks them up on unlock,
+-- unless the store already holds a key for that feed
+UPDATE account_settings SET
+    coingecko_api_key = COALESCE(coingecko_api_key, (
+        SELECT s.coingecko_api_key FROM account_currency_settings s
+        WHERE s.profile_id = account_settings.profile_id
+        AND NOT EXISTS (
+            SELECT 1 FROM secrets k
+            WHERE k.profile_id = s.profile_id AND k.name = 'coingecko_api_key'
+        )
+    )),
+    fixer_api_key = COALESCE(fixer_api_key, (
+        SELECT s.fixer_api_key FROM account_currency_settings s
+        WHERE s.profile_id = account_settings.profile_id
+        AND NOT EXISTS (
+            SELECT 1 FROM secrets k
+            WHERE k.profile_id = s.profile_id AND k.name = 'fixer_api_key'
+        )
+    ))
+WHERE profile_id IN (SELECT profile_id FROM account_currency_settings);
+
+DROP TABLE account_currency_settings;
+DROP TABLE exchange_rates_mvp;
+DROP TABLE currencies_mvp;
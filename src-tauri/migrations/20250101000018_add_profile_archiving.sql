@@ -0,0 +1,2 @@
+-- Archived profiles are hidden from the profile list but keep their data
+ALTER TABLE profiles ADD COLUMN archived_at DATETIME;
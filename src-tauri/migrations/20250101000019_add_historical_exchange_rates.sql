@@ -0,0 +1,5 @@
+-- Historical rates are kept for good, cleanup only expires spot rates
+ALTER TABLE exchange_rates ADD COLUMN is_historical BOOLEAN NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_exchange_rates_historical
+ON exchange_rates(from_currency, to_currency, is_historical);
@@ -0,0 +1,21 @@
+-- Manual rate book: rates a profile's board approved for a period, used by
+-- the 'fixed' conversion method
+CREATE TABLE IF NOT EXISTS manual_rates (
+    id TEXT PRIMARY KEY,
+    profile_id TEXT NOT NULL,
+    from_currency TEXT NOT NULL,
+    to_currency TEXT NOT NULL,
+    rate TEXT NOT NULL,  -- Stored as TEXT to preserve decimal precision
+    valid_from DATE NOT NULL,
+    valid_to DATE,  -- Inclusive, NULL while the rate is open-ended
+    note TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (profile_id) REFERENCES profiles(id) ON DELETE CASCADE,
+    FOREIGN KEY (from_currency) REFERENCES currencies(code),
+    FOREIGN KEY (to_currency) REFERENCES currencies(code),
+    CHECK (valid_to IS NULL OR valid_to >= valid_from)
+);
+
+CREATE INDEX IF NOT EXISTS idx_manual_rates_pair
+ON manual_rates(profile_id, from_currency, to_currency, valid_from);
@@ -0,0 +1,4 @@
+-- Method amount_primary was converted with, so a change of method marks the
+-- conversion stale just like a change of primary currency
+ALTER TABLE transactions ADD COLUMN conversion_method TEXT
+    CHECK(conversion_method IN ('spot', 'historical', 'fixed'));
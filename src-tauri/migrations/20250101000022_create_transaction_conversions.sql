@@ -0,0 +1,31 @@
+-- Value of each transaction in every currency its profile reports in. The
+-- amount_primary columns on transactions mirror the primary currency row.
+CREATE TABLE IF NOT EXISTS transaction_conversions (
+    transaction_id TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    amount TEXT NOT NULL,  -- Stored as TEXT to preserve decimal precision
+    exchange_rate TEXT NOT NULL,  -- Per whole token
+    exchange_rate_source TEXT,  -- NULL when the transaction is in this currency
+    exchange_rate_timestamp DATETIME,
+    conversion_method TEXT NOT NULL CHECK(conversion_method IN ('spot', 'historical', 'fixed')),
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (transaction_id, currency),
+    FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE,
+    FOREIGN KEY (currency) REFERENCES currencies(code)
+);
+
+CREATE INDEX IF NOT EXISTS idx_transaction_conversions_currency
+ON transaction_conversions(currency);
+
+-- Keep the conversions done so far
+INSERT OR IGNORE INTO transaction_conversions (
+    transaction_id, currency, amount, exchange_rate, exchange_rate_source,
+    exchange_rate_timestamp, conversion_method
+)
+SELECT id, primary_currency, amount_primary, exchange_rate, exchange_rate_source,
+    exchange_rate_timestamp, conversion_method
+FROM transactions
+WHERE amount_primary IS NOT NULL AND exchange_rate IS NOT NULL
+AND conversion_method IS NOT NULL
+AND primary_currency IN (SELECT code FROM currencies);
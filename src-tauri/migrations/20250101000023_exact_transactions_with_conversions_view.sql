@@ -0,0 +1,14 @@
+-- The view multiplied raw values by the per-token rate as REAL, which lost
+-- precision and ignored the token's decimals. The converted amount is now
+-- the exact one the currency service computed.
+DROP VIEW IF EXISTS transactions_with_conversions;
+
+CREATE VIEW transactions_with_conversions AS
+SELECT
+    t.*,
+    c.name AS currency_name,
+    c.type AS currency_type,
+    c.symbol AS currency_symbol,
+    t.amount_primary AS calculated_primary_amount
+FROM transactions t
+LEFT JOIN currencies c ON t.token_symbol = c.code;
@@ -0,0 +1,15 @@
+-- Permanent daily prices per asset and fiat currency. Unlike exchange_rates
+-- nothing here expires, cleanup_stale_rates never touches this table.
+CREATE TABLE IF NOT EXISTS price_history (
+    asset TEXT NOT NULL,
+    fiat TEXT NOT NULL,
+    date DATE NOT NULL,
+    open TEXT,  -- First price of the day, NULL when the feed has one per day
+    close TEXT NOT NULL,  -- Last price of the day, TEXT to preserve precision
+    source TEXT NOT NULL CHECK(source IN ('coingecko', 'fixer', 'manual', 'compound')),
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (asset, fiat, date),
+    FOREIGN KEY (asset) REFERENCES currencies(code),
+    FOREIGN KEY (fiat) REFERENCES currencies(code)
+);
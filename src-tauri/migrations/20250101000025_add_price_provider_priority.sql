@@ -0,0 +1,3 @@
+-- Order the price providers are asked in on a cache miss, comma-separated
+-- (e.g. 'manual,coingecko'). NULL keeps the default order.
+ALTER TABLE account_settings ADD COLUMN price_provider_priority TEXT;
@@ -0,0 +1,3 @@
+-- 0006 and 0009 seeded example rates stamped with the migration time. They
+-- are not quotes of any provider, so a conversion must never pick them up.
+DELETE FROM exchange_rates WHERE id LIKE '%-initial' OR id LIKE '%-init';
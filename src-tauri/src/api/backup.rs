@@ -1,9 +1,18 @@
+use crate::db::{backup, Database};
 use anyhow::Result;
+use std::path::PathBuf;
 use tauri::Manager;
 
+/// Write a backup archive and return its path. Without `destination` it goes
+/// into the `backups` folder of the app data directory.
 #[tauri::command]
-pub async fn create_backup(app_handle: tauri::AppHandle) -> Result<String, String> {
-    let _data_dir = app_handle
+pub async fn create_backup(
+    app_handle: tauri::AppHandle,
+    db: tauri::State<'_, Database>,
+    destination: Option<String>,
+    passphrase: Option<String>,
+) -> Result<String, String> {
+    let data_dir = app_handle
         .path()
         .app_data_dir()
         .map_err(|e| e.to_string())?;
@@ -13,17 +22,50 @@ pub async fn create_backup(app_handle: tauri::AppHandle) -> Result<String, Strin
         chrono::Utc::now().format("%Y%m%d_%H%M%S")
     );
 
-    // Create zip archive of the data directory
-    // Implementation would zip the SQLite database and settings
+    let path = match destination {
+        Some(destination) => PathBuf::from(destination),
+        None => {
+            let backups_dir = data_dir.join("backups");
+            std::fs::create_dir_all(&backups_dir).map_err(|e| e.to_string())?;
+            backups_dir.join(backup_name)
+        }
+    };
 
-    Ok(backup_name)
+    backup::create_backup(&db.pool, &data_dir, &path, passphrase.as_deref())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(path.to_string_lossy().to_string())
 }
 
+/// Restore a backup archive, then restart the app on the restored database
 #[tauri::command]
 pub async fn restore_backup(
-    _app_handle: tauri::AppHandle,
-    _backup_path: String,
+    app_handle: tauri::AppHandle,
+    db: tauri::State<'_, Database>,
+    backup_path: String,
+    passphrase: Option<String>,
 ) -> Result<(), String> {
-    // Implementation would extract the backup and restore database
-    Ok(())
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?;
+
+    let restored = backup::restore_backup(
+        &db.pool,
+        &data_dir,
+        &PathBuf::from(backup_path),
+        passphrase.as_deref(),
+    )
+    .await;
+    if let Err(e) = restored {
+        // Failed before the pool was closed, the app keeps running as it was
+        if !db.pool.is_closed() {
+            return Err(e.to_string());
+        }
+        eprintln!("Restore failed after closing the database: {:#}", e);
+    }
+
+    // The pool is closed by the restore, reopen whichever database is in place
+    app_handle.restart();
 }
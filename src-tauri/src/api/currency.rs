@@ -0,0 +1,72 @@
+use crate::core::currency::{AccountSettings, TransactionConversion};
+use crate::core::currency_service::{ConversionReport, CurrencyService, CONVERSION_EVENT};
+use std::sync::Arc;
+use tauri::Emitter;
+
+#[tauri::command]
+pub async fn get_account_settings(
+    currency_service: tauri::State<'_, Arc<CurrencyService>>,
+    profile_id: String,
+) -> Result<Option<AccountSettings>, String> {
+    currency_service
+        .get_account_settings(&profile_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Save a profile's currency settings. New primary or reporting currencies or
+/// a new conversion method reconvert the transactions in the background, the
+/// report arrives as a `currency://conversions` event.
+#[tauri::command]
+pub async fn update_account_settings(
+    app_handle: tauri::AppHandle,
+    currency_service: tauri::State<'_, Arc<CurrencyService>>,
+    settings: AccountSettings,
+) -> Result<(), String> {
+    let stale = currency_service
+        .update_account_settings(&settings)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if stale {
+        let service = currency_service.inner().clone();
+        tauri::async_runtime::spawn(async move {
+            match service.convert_transactions(&settings.profile_id).await {
+                Ok(report) => {
+                    let _ = app_handle.emit(CONVERSION_EVENT, report);
+                }
+                Err(e) => eprintln!(
+                    "Failed to reconvert transactions of {}: {:#}",
+                    settings.profile_id, e
+                ),
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Convert the transactions that lack a conversion to one of the reporting
+/// currencies, or have one done with another method
+#[tauri::command]
+pub async fn convert_transactions(
+    currency_service: tauri::State<'_, Arc<CurrencyService>>,
+    profile_id: String,
+) -> Result<ConversionReport, String> {
+    currency_service
+        .convert_transactions(&profile_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Value of a transaction in each reporting currency
+#[tauri::command]
+pub async fn get_transaction_conversions(
+    currency_service: tauri::State<'_, Arc<CurrencyService>>,
+    transaction_id: String,
+) -> Result<Vec<TransactionConversion>, String> {
+    currency_service
+        .get_transaction_conversions(&transaction_id)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -1,5 +1,8 @@
+use crate::core::currency_service::CurrencyService;
+use crate::db::transactions::TransactionFilter;
 use crate::db::Database;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Days, NaiveDate, Utc};
 use csv::Writer;
 use serde_json;
 
@@ -10,51 +13,118 @@ pub async fn export_transactions_csv(
     profile_id: String,
     start_date: Option<String>,
     end_date: Option<String>,
+    currency: Option<String>,
 ) -> Result<(), String> {
+    let filter = TransactionFilter {
+        profile_id,
+        from: start_date
+            .as_deref()
+            .map(|d| parse_date(d, false))
+            .transpose()
+            .map_err(|e| e.to_string())?,
+        to: end_date
+            .as_deref()
+            .map(|d| parse_date(d, true))
+            .transpose()
+            .map_err(|e| e.to_string())?,
+        ..Default::default()
+    };
     let transactions = db
-        .get_transactions(&profile_id, start_date, end_date)
+        .get_transactions(&filter)
         .await
         .map_err(|e| e.to_string())?;
 
+    // Converted amounts in one of the profile's reporting currencies
+    let conversions = match &currency {
+        Some(currency) => Some(
+            CurrencyService::new(db.pool.clone())
+                .get_conversions_to(&filter.profile_id, currency)
+                .await
+                .map_err(|e| e.to_string())?,
+        ),
+        None => None,
+    };
+
     let mut writer = Writer::from_path(path).map_err(|e| e.to_string())?;
 
     // Write headers
-    writer
-        .write_record([
-            "Date", "Chain", "Hash", "From", "To", "Value", "Token", "Type", "Fee", "Status",
-        ])
-        .map_err(|e| e.to_string())?;
+    let mut headers = [
+        "Date", "Chain", "Hash", "From", "To", "Value", "Amount", "Token", "Type", "Fee", "Status",
+    ]
+    .map(String::from)
+    .to_vec();
+    if let Some(currency) = &currency {
+        headers.push(format!("Amount ({})", currency));
+        headers.push(format!("Rate ({})", currency));
+        headers.push("Rate Source".to_string());
+    }
+    writer.write_record(&headers).map_err(|e| e.to_string())?;
 
     // Write transactions
     for tx in transactions {
-        writer
-            .write_record(&[
-                tx.timestamp.to_string(),
-                tx.chain,
-                tx.hash,
-                tx.from_address,
-                tx.to_address.unwrap_or_default(),
-                tx.value.to_string(),
-                tx.token_symbol,
-                tx.transaction_type,
-                tx.fee.map(|f| f.to_string()).unwrap_or_default(),
-                tx.status,
-            ])
-            .map_err(|e| e.to_string())?;
+        let id = tx.id.to_string();
+        // Whole tokens, exact for any on-chain value
+        let amount = tx.amount().map_err(|e| e.to_string())?;
+        let mut record = vec![
+            tx.timestamp.to_string(),
+            tx.chain,
+            tx.hash,
+            tx.from_address,
+            tx.to_address.unwrap_or_default(),
+            tx.value.to_string(),
+            amount.to_string(),
+            tx.token_symbol,
+            tx.transaction_type,
+            tx.fee.map(|f| f.to_string()).unwrap_or_default(),
+            tx.status,
+        ];
+        if let Some(conversions) = &conversions {
+            let conversion = conversions.get(&id);
+            record.push(conversion.map(|c| c.amount.clone()).unwrap_or_default());
+            record.push(
+                conversion
+                    .map(|c| c.exchange_rate.clone())
+                    .unwrap_or_default(),
+            );
+            record.push(
+                conversion
+                    .and_then(|c| c.exchange_rate_source.clone())
+                    .unwrap_or_default(),
+            );
+        }
+        writer.write_record(&record).map_err(|e| e.to_string())?;
     }
 
     writer.flush().map_err(|e| e.to_string())?;
     Ok(())
 }
 
+/// RFC 3339 timestamp or `YYYY-MM-DD`. A plain end date includes the whole day.
+fn parse_date(date: &str, end: bool) -> Result<DateTime<Utc>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(date) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .with_context(|| format!("Invalid date: {}", date))?;
+    let day = if end {
+        day.checked_add_days(Days::new(1))
+            .context("Date out of range")?
+    } else {
+        day
+    };
+    Ok(day.and_hms_opt(0, 0, 0).unwrap().and_utc())
+}
+
 #[tauri::command]
 pub async fn export_tax_report(
     db: tauri::State<'_, Database>,
     profile_id: String,
     year: i32,
+    currency: Option<String>,
 ) -> Result<serde_json::Value, String> {
     // Generate tax report data
-    let report = generate_tax_report(&db, &profile_id, year)
+    let report = generate_tax_report(&db, &profile_id, year, currency.as_deref())
         .await
         .map_err(|e| e.to_string())?;
 
@@ -62,16 +132,12 @@ pub async fn export_tax_report(
 }
 
 async fn generate_tax_report(
-    _db: &Database,
-    _profile_id: &str,
+    db: &Database,
+    profile_id: &str,
     year: i32,
+    currency: Option<&str>,
 ) -> Result<serde_json::Value> {
-    // Implementation for tax report generation
-    // This would calculate capital gains/losses, income, etc.
-    Ok(serde_json::json!({
-        "year": year,
-        "capital_gains": {},
-        "income": {},
-        "fees": {}
-    }))
+    let report = crate::tax::generate_tax_report(&db.pool, profile_id, year, currency).await?;
+
+    Ok(serde_json::to_value(report)?)
 }
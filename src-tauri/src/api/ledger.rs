@@ -0,0 +1,72 @@
+use crate::db::Database;
+use crate::ledger::{AccountBalance, BalanceSheet, IncomeStatement, Ledger, LedgerAccount};
+use chrono::{DateTime, Utc};
+
+/// The profile's chart of accounts, with the default accounts created on first use
+#[tauri::command]
+pub async fn get_ledger_accounts(
+    db: tauri::State<'_, Database>,
+    profile_id: String,
+) -> Result<Vec<LedgerAccount>, String> {
+    let ledger = Ledger::new(db.pool.clone());
+    ledger
+        .ensure_default_accounts(&profile_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    ledger
+        .get_accounts(&profile_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Rebuild the journal entries of the profile's transactions, returns how many.
+/// Manual entries are kept.
+#[tauri::command]
+pub async fn generate_journal_entries(
+    db: tauri::State<'_, Database>,
+    profile_id: String,
+) -> Result<usize, String> {
+    Ledger::new(db.pool.clone())
+        .generate_entries(&profile_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Debit and credit totals per account, over all entries when no period is given
+#[tauri::command]
+pub async fn get_trial_balance(
+    db: tauri::State<'_, Database>,
+    profile_id: String,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<AccountBalance>, String> {
+    Ledger::new(db.pool.clone())
+        .trial_balance(&profile_id, from, to)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_balance_sheet(
+    db: tauri::State<'_, Database>,
+    profile_id: String,
+    as_of: DateTime<Utc>,
+) -> Result<BalanceSheet, String> {
+    Ledger::new(db.pool.clone())
+        .balance_sheet(&profile_id, as_of)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_income_statement(
+    db: tauri::State<'_, Database>,
+    profile_id: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<IncomeStatement, String> {
+    Ledger::new(db.pool.clone())
+        .income_statement(&profile_id, from, to)
+        .await
+        .map_err(|e| e.to_string())
+}
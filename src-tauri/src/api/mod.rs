@@ -1,2 +1,11 @@
 pub mod backup;
+pub mod currency;
 pub mod export;
+pub mod ledger;
+pub mod price_feeds;
+pub mod profiles;
+pub mod rates;
+pub mod secrets;
+pub mod startup;
+pub mod sync;
+pub mod transactions;
@@ -1,4 +1,7 @@
+use super::{coin_pair, invert, parse_rate, PriceProvider, RateFuture};
+use crate::core::currency::{Currency, ExchangeRateSource};
 use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -24,6 +27,12 @@ struct MarketData {
     current_price: HashMap<String, f64>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct CoinGeckoMarketChartResponse {
+    /// `[unix milliseconds, price]` pairs
+    prices: Vec<(f64, f64)>,
+}
+
 impl CoinGeckoClient {
     /// Create a new CoinGecko client
     pub fn new(api_key: Option<String>) -> Self {
@@ -36,6 +45,12 @@ impl CoinGeckoClient {
         Self { api_key, base_url }
     }
 
+    /// Use another endpoint, e.g. a proxy
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
     /// Get current price for a cryptocurrency
     ///
     /// # Arguments
@@ -43,7 +58,7 @@ impl CoinGeckoClient {
     /// * `vs_currency` - Target currency (e.g., "usd", "eur")
     ///
     /// # Example
-    /// ```
+    /// ```ignore
     /// let client = CoinGeckoClient::new(None);
     /// let price = client.get_price("polkadot", "usd").await?;
     /// ```
@@ -92,7 +107,7 @@ impl CoinGeckoClient {
             ))?;
 
         // Return as string to preserve precision
-        Ok(format!("{:.18}", price))
+        Ok(price.to_string())
     }
 
     /// Get prices for multiple cryptocurrencies at once
@@ -144,7 +159,7 @@ impl CoinGeckoClient {
         for coin_id in coin_ids {
             if let Some(prices) = data.prices.get(*coin_id) {
                 if let Some(price) = prices.get(vs_currency.to_lowercase().as_str()) {
-                    result.insert(coin_id.to_string(), format!("{:.18}", price));
+                    result.insert(coin_id.to_string(), price.to_string());
                 }
             }
         }
@@ -205,7 +220,72 @@ impl CoinGeckoClient {
                 coin_id, vs_currency, date
             ))?;
 
-        Ok(format!("{:.18}", price))
+        Ok(price.to_string())
+    }
+
+    /// Get all prices between two times in one request. CoinGecko returns
+    /// hourly prices for ranges up to 90 days and daily prices beyond.
+    ///
+    /// # Arguments
+    /// * `coin_id` - CoinGecko coin ID
+    /// * `vs_currency` - Target currency
+    /// * `from` / `to` - Range of the prices
+    ///
+    /// # Example
+    /// ```ignore
+    /// let prices = client.get_price_range("polkadot", "usd", start, end).await?;
+    /// ```
+    pub async fn get_price_range(
+        &self,
+        coin_id: &str,
+        vs_currency: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, String)>> {
+        let url = format!(
+            "{}/coins/{}/market_chart/range?vs_currency={}&from={}&to={}",
+            self.base_url,
+            coin_id,
+            vs_currency.to_lowercase(),
+            from.timestamp(),
+            to.timestamp()
+        );
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        if let Some(key) = &self.api_key {
+            headers.insert(
+                "x-cg-pro-api-key",
+                reqwest::header::HeaderValue::from_str(key)?,
+            );
+        }
+
+        let client = reqwest::Client::new();
+        let response = client
+            .get(&url)
+            .headers(headers)
+            .send()
+            .await
+            .context("Failed to fetch price range from CoinGecko")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!("CoinGecko API error ({}): {}", status, error_text);
+        }
+
+        let data: CoinGeckoMarketChartResponse = response
+            .json()
+            .await
+            .context("Failed to parse CoinGecko market chart response")?;
+
+        data.prices
+            .into_iter()
+            .map(|(millis, price)| {
+                let at = DateTime::from_timestamp_millis(millis as i64)
+                    .context("Invalid timestamp in CoinGecko market chart")?;
+                Ok((at, price.to_string()))
+            })
+            .collect()
     }
 
     /// Get supported vs currencies
@@ -237,6 +317,38 @@ impl CoinGeckoClient {
     }
 }
 
+impl PriceProvider for CoinGeckoClient {
+    fn source(&self) -> ExchangeRateSource {
+        ExchangeRateSource::CoinGecko
+    }
+
+    fn spot_rate<'a>(&'a self, from: &'a Currency, to: &'a Currency) -> RateFuture<'a> {
+        Box::pin(async move {
+            let Some((coin_id, vs, inverse)) = coin_pair(from, to) else {
+                return Ok(None);
+            };
+            let price = parse_rate(&self.get_price(coin_id, &vs.code).await?)?;
+            Ok(Some(if inverse { invert(price)? } else { price }))
+        })
+    }
+
+    fn historical_rate<'a>(
+        &'a self,
+        from: &'a Currency,
+        to: &'a Currency,
+        date: NaiveDate,
+    ) -> RateFuture<'a> {
+        Box::pin(async move {
+            let Some((coin_id, vs, inverse)) = coin_pair(from, to) else {
+                return Ok(None);
+            };
+            let date = date.format("%d-%m-%Y").to_string();
+            let price = parse_rate(&self.get_historical_price(coin_id, &date, &vs.code).await?)?;
+            Ok(Some(if inverse { invert(price)? } else { price }))
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
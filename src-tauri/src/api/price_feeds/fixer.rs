@@ -1,4 +1,7 @@
+use super::{parse_rate, PriceProvider, RateFuture};
+use crate::core::currency::{Currency, CurrencyType, ExchangeRateSource};
 use anyhow::{Context, Result};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -39,6 +42,12 @@ impl FixerClient {
         }
     }
 
+    /// Use another endpoint, e.g. a proxy
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
     /// Get current exchange rate
     ///
     /// # Arguments
@@ -46,7 +55,7 @@ impl FixerClient {
     /// * `to_currency` - Target currency (e.g., "EUR")
     ///
     /// # Example
-    /// ```
+    /// ```ignore
     /// let client = FixerClient::new("your_api_key".to_string());
     /// let rate = client.get_rate("USD", "EUR").await?;
     /// ```
@@ -90,7 +99,7 @@ impl FixerClient {
             ))?;
 
         // Return as string to preserve precision
-        Ok(format!("{:.18}", rate))
+        Ok(rate.to_string())
     }
 
     /// Get multiple exchange rates at once
@@ -141,7 +150,7 @@ impl FixerClient {
 
         let mut result = HashMap::new();
         for (currency, rate) in data.rates {
-            result.insert(currency, format!("{:.18}", rate));
+            result.insert(currency, rate.to_string());
         }
 
         Ok(result)
@@ -155,7 +164,7 @@ impl FixerClient {
     /// * `date` - Date in format "YYYY-MM-DD"
     ///
     /// # Example
-    /// ```
+    /// ```ignore
     /// let rate = client.get_historical_rate("USD", "EUR", "2025-01-01").await?;
     /// ```
     pub async fn get_historical_rate(
@@ -203,7 +212,7 @@ impl FixerClient {
                 from_currency, to_currency, date
             ))?;
 
-        Ok(format!("{:.18}", rate))
+        Ok(rate.to_string())
     }
 
     /// Get all available currencies
@@ -261,6 +270,44 @@ impl FixerClient {
     }
 }
 
+/// Fixer only quotes fiat currencies
+fn is_fiat_pair(from: &Currency, to: &Currency) -> bool {
+    from.currency_type == CurrencyType::Fiat && to.currency_type == CurrencyType::Fiat
+}
+
+impl PriceProvider for FixerClient {
+    fn source(&self) -> ExchangeRateSource {
+        ExchangeRateSource::Fixer
+    }
+
+    fn spot_rate<'a>(&'a self, from: &'a Currency, to: &'a Currency) -> RateFuture<'a> {
+        Box::pin(async move {
+            if !is_fiat_pair(from, to) {
+                return Ok(None);
+            }
+            let rate = self.get_rate(&from.code, &to.code).await?;
+            parse_rate(&rate).map(Some)
+        })
+    }
+
+    fn historical_rate<'a>(
+        &'a self,
+        from: &'a Currency,
+        to: &'a Currency,
+        date: NaiveDate,
+    ) -> RateFuture<'a> {
+        Box::pin(async move {
+            if !is_fiat_pair(from, to) {
+                return Ok(None);
+            }
+            let rate = self
+                .get_historical_rate(&from.code, &to.code, &date.to_string())
+                .await?;
+            parse_rate(&rate).map(Some)
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
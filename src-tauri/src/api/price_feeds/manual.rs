@@ -0,0 +1,95 @@
+use super::{parse_rate, PriceProvider, RateFuture};
+use crate::core::currency::{Currency, ExchangeRateSource, ManualRate};
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, Utc};
+use rust_decimal::Decimal;
+use sqlx::{Pool, Sqlite};
+
+/// Rates from a profile's manual rate book, for working offline. A rate
+/// answers for the dates it is valid, and a pair entered one way also
+/// answers the other.
+#[derive(Debug, Clone)]
+pub struct ManualProvider {
+    pool: Pool<Sqlite>,
+    profile_id: String,
+}
+
+impl ManualProvider {
+    pub fn new(pool: Pool<Sqlite>, profile_id: &str) -> Self {
+        Self {
+            pool,
+            profile_id: profile_id.to_string(),
+        }
+    }
+
+    async fn rate(
+        &self,
+        from: &Currency,
+        to: &Currency,
+        date: NaiveDate,
+    ) -> Result<Option<Decimal>> {
+        let rate =
+            find_manual_rate(&self.pool, &self.profile_id, &from.code, &to.code, date).await?;
+        Ok(rate.map(|(_, rate)| rate))
+    }
+}
+
+/// Rate book entry valid for `from`/`to` on `date`, and its rate in that
+/// direction. An entry for the inverse pair answers with the inverse rate,
+/// an entry for the pair itself wins over one.
+pub async fn find_manual_rate(
+    pool: &Pool<Sqlite>,
+    profile_id: &str,
+    from: &str,
+    to: &str,
+    date: NaiveDate,
+) -> Result<Option<(ManualRate, Decimal)>> {
+    let entry = sqlx::query_as::<_, ManualRate>(
+        r#"
+        SELECT * FROM manual_rates
+        WHERE profile_id = ?1
+        AND ((from_currency = ?2 AND to_currency = ?3) OR (from_currency = ?3 AND to_currency = ?2))
+        AND valid_from <= ?4 AND (valid_to IS NULL OR valid_to >= ?4)
+        ORDER BY from_currency = ?2 DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(profile_id)
+    .bind(from)
+    .bind(to)
+    .bind(date)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch manual rate")?;
+    let Some(entry) = entry else {
+        return Ok(None);
+    };
+
+    let rate = parse_rate(&entry.rate)?;
+    if entry.from_currency == from {
+        Ok(Some((entry, rate)))
+    } else if rate.is_zero() {
+        Ok(None)
+    } else {
+        Ok(Some((entry, Decimal::ONE / rate)))
+    }
+}
+
+impl PriceProvider for ManualProvider {
+    fn source(&self) -> ExchangeRateSource {
+        ExchangeRateSource::Manual
+    }
+
+    fn spot_rate<'a>(&'a self, from: &'a Currency, to: &'a Currency) -> RateFuture<'a> {
+        Box::pin(async move { self.rate(from, to, Utc::now().date_naive()).await })
+    }
+
+    fn historical_rate<'a>(
+        &'a self,
+        from: &'a Currency,
+        to: &'a Currency,
+        date: NaiveDate,
+    ) -> RateFuture<'a> {
+        Box::pin(async move { self.rate(from, to, date).await })
+    }
+}
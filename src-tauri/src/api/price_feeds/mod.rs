@@ -1,5 +1,206 @@
 pub mod coingecko;
 pub mod fixer;
+pub mod manual;
 
 pub use coingecko::CoinGeckoClient;
 pub use fixer::FixerClient;
+pub use manual::{find_manual_rate, ManualProvider};
+
+use crate::core::currency::{Currency, ExchangeRateSource};
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+
+/// Rate for a currency pair, `None` when the provider does not quote it
+pub type RateFuture<'a> = Pin<Box<dyn Future<Output = Result<Option<Decimal>>> + Send + 'a>>;
+
+/// Where `CurrencyService` fetches rates it does not have stored
+pub trait PriceProvider: Send + Sync {
+    /// Source recorded with the rates from this provider
+    fn source(&self) -> ExchangeRateSource;
+
+    /// Current rate: how much of `to` one unit of `from` is worth
+    fn spot_rate<'a>(&'a self, from: &'a Currency, to: &'a Currency) -> RateFuture<'a>;
+
+    /// Rate on `date`
+    fn historical_rate<'a>(
+        &'a self,
+        from: &'a Currency,
+        to: &'a Currency,
+        date: NaiveDate,
+    ) -> RateFuture<'a>;
+}
+
+/// Providers that can take part in the fallback chain
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ProviderKind {
+    #[serde(rename = "coingecko")]
+    CoinGecko,
+    #[serde(rename = "fixer")]
+    Fixer,
+    #[serde(rename = "manual")]
+    Manual,
+}
+
+impl std::fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderKind::CoinGecko => write!(f, "coingecko"),
+            ProviderKind::Fixer => write!(f, "fixer"),
+            ProviderKind::Manual => write!(f, "manual"),
+        }
+    }
+}
+
+impl FromStr for ProviderKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "coingecko" => Ok(ProviderKind::CoinGecko),
+            "fixer" => Ok(ProviderKind::Fixer),
+            "manual" => Ok(ProviderKind::Manual),
+            _ => anyhow::bail!("Unknown price provider {}", s),
+        }
+    }
+}
+
+/// Which providers to ask on a cache miss, first to last
+#[derive(Debug, Clone)]
+pub struct PriceProviderConfig {
+    /// Order for profiles that have not set their own in the account settings
+    pub priority: Vec<ProviderKind>,
+    /// Base URL overrides, e.g. for a proxy
+    pub coingecko_url: Option<String>,
+    pub fixer_url: Option<String>,
+}
+
+impl Default for PriceProviderConfig {
+    fn default() -> Self {
+        Self {
+            priority: vec![
+                ProviderKind::CoinGecko,
+                ProviderKind::Fixer,
+                ProviderKind::Manual,
+            ],
+            coingecko_url: None,
+            fixer_url: None,
+        }
+    }
+}
+
+/// CoinGecko coin and quote currency for a pair, and whether the price has to
+/// be inverted because only the quote currency is a coin
+fn coin_pair<'a>(from: &'a Currency, to: &'a Currency) -> Option<(&'a str, &'a Currency, bool)> {
+    match (&from.coingecko_id, &to.coingecko_id) {
+        (Some(coin_id), _) => Some((coin_id, to, false)),
+        (None, Some(coin_id)) => Some((coin_id, from, true)),
+        (None, None) => None,
+    }
+}
+
+fn invert(rate: Decimal) -> Result<Decimal> {
+    if rate.is_zero() {
+        anyhow::bail!("Cannot invert a zero rate");
+    }
+    Ok(Decimal::ONE / rate)
+}
+
+/// The clients return prices as strings to keep them out of float arithmetic
+fn parse_rate(rate: &str) -> Result<Decimal> {
+    Decimal::from_str(rate).with_context(|| format!("Invalid rate {}", rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::currency::CurrencyType;
+    use crate::db::test_pool;
+
+    fn currency(code: &str, currency_type: CurrencyType, coingecko_id: Option<&str>) -> Currency {
+        Currency {
+            id: code.to_lowercase(),
+            code: code.to_string(),
+            name: code.to_string(),
+            currency_type,
+            decimals: 2,
+            is_supported: true,
+            coingecko_id: coingecko_id.map(str::to_string),
+            fixer_id: None,
+            symbol: None,
+            icon_url: None,
+            is_substrate: false,
+            chain_id: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_providers_against_mock_server() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/simple/price")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("ids".into(), "kusama".into()),
+                mockito::Matcher::UrlEncoded("vs_currencies".into(), "eur".into()),
+            ]))
+            .with_body(r#"{"kusama":{"eur":20.0}}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/2024-03-01")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("base".into(), "EUR".into()),
+                mockito::Matcher::UrlEncoded("symbols".into(), "CHF".into()),
+            ]))
+            .with_body(
+                r#"{"success":true,"historical":true,"date":"2024-03-01",
+                    "timestamp":1709251199,"base":"EUR","rates":{"CHF":0.9512}}"#,
+            )
+            .create_async()
+            .await;
+
+        let ksm = currency("KSM", CurrencyType::Crypto, Some("kusama"));
+        let eur = currency("EUR", CurrencyType::Fiat, None);
+        let chf = currency("CHF", CurrencyType::Fiat, None);
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+
+        let coingecko = CoinGeckoClient::new(None).with_base_url(server.url());
+        let rate = coingecko.spot_rate(&ksm, &eur).await.unwrap();
+        assert_eq!(rate, Some(Decimal::new(20, 0)));
+        let rate = coingecko.spot_rate(&eur, &ksm).await.unwrap();
+        assert_eq!(rate, Some(Decimal::new(5, 2)));
+        assert_eq!(coingecko.spot_rate(&eur, &chf).await.unwrap(), None);
+
+        let fixer = FixerClient::new("key".to_string()).with_base_url(server.url());
+        let rate = fixer.historical_rate(&eur, &chf, date).await.unwrap();
+        assert_eq!(rate, Some(Decimal::new(9512, 4)));
+        assert_eq!(fixer.spot_rate(&ksm, &eur).await.unwrap(), None);
+        // Unmatched requests get a 501 from the mock server
+        assert!(fixer.historical_rate(&chf, &eur, date).await.is_err());
+
+        let pool = test_pool().await;
+        sqlx::query(
+            "INSERT INTO profiles (id, name) VALUES ('p1', 'Test');
+             INSERT INTO manual_rates (id, profile_id, from_currency, to_currency, rate, valid_from, valid_to)
+             VALUES ('m1', 'p1', 'EUR', 'CHF', '0.8', '2024-01-01', '2024-06-30')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let manual = ManualProvider::new(pool.clone(), "p1");
+        let rate = manual.historical_rate(&chf, &eur, date).await.unwrap();
+        assert_eq!(rate, Some(Decimal::new(125, 2)));
+        let rate = manual.historical_rate(&eur, &chf, date).await.unwrap();
+        assert_eq!(rate, Some(Decimal::new(8, 1)));
+        // Outside its validity, or for another profile, the rate does not apply
+        assert_eq!(manual.spot_rate(&eur, &chf).await.unwrap(), None);
+        let other = ManualProvider::new(pool, "p2");
+        assert_eq!(other.historical_rate(&eur, &chf, date).await.unwrap(), None);
+    }
+}
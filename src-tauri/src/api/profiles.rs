@@ -0,0 +1,115 @@
+use crate::core::{Account, Profile};
+use crate::db::Database;
+
+#[tauri::command]
+pub async fn list_profiles(
+    db: tauri::State<'_, Database>,
+    include_archived: Option<bool>,
+) -> Result<Vec<Profile>, String> {
+    db.list_profiles(include_archived.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn create_profile(
+    db: tauri::State<'_, Database>,
+    name: String,
+    avatar_url: Option<String>,
+) -> Result<Profile, String> {
+    db.create_profile(&name, avatar_url.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn rename_profile(
+    db: tauri::State<'_, Database>,
+    profile_id: String,
+    name: String,
+) -> Result<Profile, String> {
+    db.rename_profile(&profile_id, &name)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_profile_avatar(
+    db: tauri::State<'_, Database>,
+    profile_id: String,
+    avatar_url: Option<String>,
+) -> Result<Profile, String> {
+    db.set_profile_avatar(&profile_id, avatar_url.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Archive a profile, or restore it with `archived = false`
+#[tauri::command]
+pub async fn set_profile_archived(
+    db: tauri::State<'_, Database>,
+    profile_id: String,
+    archived: bool,
+) -> Result<Profile, String> {
+    db.set_profile_archived(&profile_id, archived)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Permanently delete a profile with its accounts, transactions, settings,
+/// sync state, tax lots and journal
+#[tauri::command]
+pub async fn delete_profile(
+    db: tauri::State<'_, Database>,
+    profile_id: String,
+) -> Result<(), String> {
+    db.delete_profile(&profile_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_accounts(
+    db: tauri::State<'_, Database>,
+    profile_id: String,
+) -> Result<Vec<Account>, String> {
+    db.list_accounts(&profile_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Add an address to a profile. EVM chains take an H160 address, Substrate
+/// chains an SS58 address with the chain's prefix.
+#[tauri::command]
+pub async fn add_account(
+    db: tauri::State<'_, Database>,
+    profile_id: String,
+    chain: String,
+    address: String,
+    nickname: Option<String>,
+) -> Result<Account, String> {
+    db.add_account(&profile_id, &chain, &address, nickname.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn rename_account(
+    db: tauri::State<'_, Database>,
+    account_id: String,
+    nickname: Option<String>,
+) -> Result<Account, String> {
+    db.rename_account(&account_id, nickname.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn remove_account(
+    db: tauri::State<'_, Database>,
+    account_id: String,
+) -> Result<(), String> {
+    db.remove_account(&account_id)
+        .await
+        .map_err(|e| e.to_string())
+}
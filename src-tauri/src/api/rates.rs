@@ -0,0 +1,84 @@
+use crate::core::currency::{DailyPrice, ManualRate, NewManualRate};
+use crate::core::currency_service::CurrencyService;
+use chrono::NaiveDate;
+use std::sync::Arc;
+
+/// The profile's manual rate book, used by the fixed conversion method
+#[tauri::command]
+pub async fn list_manual_rates(
+    currency_service: tauri::State<'_, Arc<CurrencyService>>,
+    profile_id: String,
+) -> Result<Vec<ManualRate>, String> {
+    currency_service
+        .list_manual_rates(&profile_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn add_manual_rate(
+    currency_service: tauri::State<'_, Arc<CurrencyService>>,
+    profile_id: String,
+    rate: NewManualRate,
+) -> Result<ManualRate, String> {
+    currency_service
+        .add_manual_rate(&profile_id, &rate)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Import manual rates from CSV, returning how many were added
+#[tauri::command]
+pub async fn import_manual_rates(
+    currency_service: tauri::State<'_, Arc<CurrencyService>>,
+    profile_id: String,
+    csv: String,
+) -> Result<usize, String> {
+    currency_service
+        .import_manual_rates(&profile_id, &csv)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_manual_rate(
+    currency_service: tauri::State<'_, Arc<CurrencyService>>,
+    profile_id: String,
+    rate_id: String,
+) -> Result<(), String> {
+    currency_service
+        .delete_manual_rate(&profile_id, &rate_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Stored daily prices of an asset in a fiat currency, inclusive
+#[tauri::command]
+pub async fn get_price_history(
+    currency_service: tauri::State<'_, Arc<CurrencyService>>,
+    asset: String,
+    fiat: String,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<DailyPrice>, String> {
+    currency_service
+        .get_price_history(&asset, &fiat, from, to)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Fetch the missing daily prices in a date range, returning how many were added
+#[tauri::command]
+pub async fn backfill_price_history(
+    currency_service: tauri::State<'_, Arc<CurrencyService>>,
+    profile_id: String,
+    asset: String,
+    fiat: String,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<usize, String> {
+    currency_service
+        .backfill_price_history(&profile_id, &asset, &fiat, from, to)
+        .await
+        .map_err(|e| e.to_string())
+}
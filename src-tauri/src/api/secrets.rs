@@ -0,0 +1,74 @@
+use crate::core::currency::PriceFeed;
+use crate::core::currency_service::CurrencyService;
+use crate::core::secrets::{SecretStore, SecretStoreStatus};
+use crate::db::Database;
+use anyhow::Result;
+use std::sync::Arc;
+
+/// Whether the secret store has a passphrase yet and whether it is unlocked
+#[tauri::command]
+pub async fn get_secrets_status(
+    db: tauri::State<'_, Database>,
+    secrets: tauri::State<'_, Arc<SecretStore>>,
+) -> Result<SecretStoreStatus, String> {
+    secrets.status(&db.pool).await.map_err(|e| e.to_string())
+}
+
+/// Unlock the secret store, setting its passphrase on first use
+#[tauri::command]
+pub async fn unlock_secrets(
+    db: tauri::State<'_, Database>,
+    secrets: tauri::State<'_, Arc<SecretStore>>,
+    passphrase: String,
+) -> Result<(), String> {
+    secrets
+        .unlock(&db.pool, &passphrase)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn lock_secrets(secrets: tauri::State<'_, Arc<SecretStore>>) -> Result<(), String> {
+    secrets.lock().await;
+    Ok(())
+}
+
+/// Set or clear a price feed API key. Keys are never sent back to the frontend.
+#[tauri::command]
+pub async fn set_price_feed_api_key(
+    currency_service: tauri::State<'_, Arc<CurrencyService>>,
+    profile_id: String,
+    feed: PriceFeed,
+    api_key: Option<String>,
+) -> Result<(), String> {
+    currency_service
+        .set_api_key(&profile_id, feed, api_key.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Price feeds the profile has an API key for
+#[tauri::command]
+pub async fn get_configured_api_keys(
+    currency_service: tauri::State<'_, Arc<CurrencyService>>,
+    profile_id: String,
+) -> Result<Vec<PriceFeed>, String> {
+    currency_service
+        .configured_api_keys(&profile_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Re-encrypt all stored secrets under a new passphrase, returns how many
+#[tauri::command]
+pub async fn rotate_secrets_key(
+    db: tauri::State<'_, Database>,
+    secrets: tauri::State<'_, Arc<SecretStore>>,
+    current_passphrase: String,
+    new_passphrase: String,
+) -> Result<usize, String> {
+    secrets
+        .rotate(&db.pool, &current_passphrase, &new_passphrase)
+        .await
+        .map_err(|e| e.to_string())
+}
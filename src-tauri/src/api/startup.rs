@@ -0,0 +1,7 @@
+use crate::startup::StartupStatus;
+
+/// Why the app failed to start, `None` if it started normally
+#[tauri::command]
+pub fn get_startup_error(status: tauri::State<'_, StartupStatus>) -> Option<String> {
+    status.error.clone()
+}
@@ -0,0 +1,92 @@
+use crate::sync::{AccountSyncState, SyncJob, SyncManager, SyncScheduler, SYNC_EVENT};
+use crate::EVMIndexerState;
+use anyhow::Result;
+use std::sync::Arc;
+use tauri::Emitter;
+
+/// Start syncing an address in the background. Progress arrives as
+/// `sync://progress` events. Returns false if the job was already running.
+#[tauri::command]
+pub async fn start_sync(
+    app_handle: tauri::AppHandle,
+    sync_manager: tauri::State<'_, Arc<SyncManager>>,
+    evm_indexer: tauri::State<'_, EVMIndexerState>,
+    scheduler: tauri::State<'_, SyncScheduler>,
+    profile_id: String,
+    chain: String,
+    address: String,
+) -> Result<bool, String> {
+    let job = SyncJob {
+        profile_id,
+        chain,
+        address,
+    };
+
+    {
+        let mut indexer = evm_indexer.lock().await;
+        if job.address.starts_with("0x") && !indexer.is_connected(&job.chain) {
+            indexer
+                .connect(&job.chain)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(scheduler.start(
+        sync_manager.inner().clone(),
+        evm_indexer.inner().clone(),
+        job,
+        move |event| {
+            let _ = app_handle.emit(SYNC_EVENT, event);
+        },
+    ))
+}
+
+/// Stop a running sync after its current chunk. Returns false if it was not running.
+#[tauri::command]
+pub async fn cancel_sync(
+    scheduler: tauri::State<'_, SyncScheduler>,
+    profile_id: String,
+    chain: String,
+    address: String,
+) -> Result<bool, String> {
+    Ok(scheduler.cancel(&SyncJob {
+        profile_id,
+        chain,
+        address,
+    }))
+}
+
+#[tauri::command]
+pub async fn get_running_syncs(
+    scheduler: tauri::State<'_, SyncScheduler>,
+) -> Result<Vec<SyncJob>, String> {
+    Ok(scheduler.running())
+}
+
+/// Checkpoint and last error of every account in a profile
+#[tauri::command]
+pub async fn get_sync_states(
+    sync_manager: tauri::State<'_, Arc<SyncManager>>,
+    profile_id: String,
+) -> Result<Vec<AccountSyncState>, String> {
+    sync_manager
+        .sync_states(&profile_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Start an account's sync at `start_block`, e.g. its first activity
+#[tauri::command]
+pub async fn set_sync_start_block(
+    sync_manager: tauri::State<'_, Arc<SyncManager>>,
+    profile_id: String,
+    chain: String,
+    address: String,
+    start_block: u64,
+) -> Result<(), String> {
+    sync_manager
+        .set_start_block(&profile_id, &chain, &address, start_block)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,37 @@
+use crate::db::transactions::{PageRequest, TransactionFilter, TransactionPage};
+use crate::db::Database;
+
+/// One page of a profile's transactions, see `PageRequest` for sorting and
+/// the cursor of the next page
+#[tauri::command]
+pub async fn query_transactions(
+    db: tauri::State<'_, Database>,
+    filter: TransactionFilter,
+    page: Option<PageRequest>,
+) -> Result<TransactionPage, String> {
+    db.query_transactions(&filter, &page.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_transaction_tags(
+    db: tauri::State<'_, Database>,
+    transaction_id: String,
+) -> Result<Vec<String>, String> {
+    db.get_transaction_tags(&transaction_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Replace the tags of a transaction
+#[tauri::command]
+pub async fn set_transaction_tags(
+    db: tauri::State<'_, Database>,
+    transaction_id: String,
+    tags: Vec<String>,
+) -> Result<(), String> {
+    db.set_transaction_tags(&transaction_id, &tags)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -1,5 +1,7 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use ethers::types::Address as H160Address;
+use ethers::utils::to_checksum;
+use sp_core::crypto::{AccountId32, Ss58Codec};
 
 #[allow(dead_code)]
 pub struct UnifiedAddress {
@@ -61,3 +63,109 @@ impl UnifiedAddress {
         }
     }
 }
+
+/// Address formats a chain accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressFormat {
+    /// 20-byte `0x` addresses
+    pub evm: bool,
+    /// SS58 network prefix, `None` if the chain has no SS58 accounts
+    pub ss58_prefix: Option<u16>,
+}
+
+/// Address formats of the chains the indexers support
+pub fn address_format(chain: &str) -> Option<AddressFormat> {
+    let (evm, ss58_prefix) = match chain {
+        "polkadot" => (false, Some(0)),
+        "kusama" => (false, Some(2)),
+        // Astar keeps separate EVM and Substrate accounts
+        "astar" => (true, Some(5)),
+        "moonbeam" | "moonriver" | "acala-evm" | "paseo" => (true, None),
+        _ => return None,
+    };
+
+    Some(AddressFormat { evm, ss58_prefix })
+}
+
+/// Check that `address` is valid on `chain` and return it the way it is
+/// stored: EVM addresses lowercase, SS58 addresses as given
+pub fn validate_address(chain: &str, address: &str) -> Result<String> {
+    let format = address_format(chain).with_context(|| format!("Unsupported chain {}", chain))?;
+    let address = address.trim();
+
+    if address.starts_with("0x") {
+        if !format.evm {
+            anyhow::bail!("{} does not use EVM addresses", chain);
+        }
+        return validate_h160(address);
+    }
+
+    let Some(prefix) = format.ss58_prefix else {
+        anyhow::bail!("{} addresses start with 0x", chain);
+    };
+    let (_, version) = AccountId32::from_ss58check_with_version(address)
+        .map_err(|e| anyhow::anyhow!("Invalid SS58 address {}: {:?}", address, e))?;
+    let found = u16::from(version);
+    if found != prefix {
+        anyhow::bail!(
+            "{} is an address for SS58 prefix {}, {} uses prefix {}",
+            address,
+            found,
+            chain,
+            prefix
+        );
+    }
+
+    Ok(address.to_string())
+}
+
+fn validate_h160(address: &str) -> Result<String> {
+    let digits = &address[2..];
+    if digits.len() != 40 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("Invalid EVM address {}", address);
+    }
+
+    let h160 = address.parse::<H160Address>()?;
+    // Mixed case carries an EIP-55 checksum, which catches typos
+    let mixed_case = digits.chars().any(|c| c.is_ascii_lowercase())
+        && digits.chars().any(|c| c.is_ascii_uppercase());
+    if mixed_case && to_checksum(&h160, None) != address {
+        anyhow::bail!("Checksum mismatch in EVM address {}", address);
+    }
+
+    Ok(address.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLKADOT: &str = "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5";
+    const GENERIC: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+    const CHECKSUMMED: &str = "0xAcc15dC74880C9944775448304B263D191c6077F";
+
+    #[test]
+    fn test_validate_address() {
+        assert_eq!(validate_address("polkadot", POLKADOT).unwrap(), POLKADOT);
+        assert_eq!(
+            validate_address("moonbeam", CHECKSUMMED).unwrap(),
+            CHECKSUMMED.to_lowercase()
+        );
+        assert!(
+            validate_address("moonbeam", &CHECKSUMMED.to_uppercase().replace("0X", "0x")).is_ok()
+        );
+
+        // Wrong prefix, wrong family, bad checksum, bad length, unknown chain
+        assert!(validate_address("polkadot", GENERIC).is_err());
+        assert!(validate_address("kusama", POLKADOT).is_err());
+        assert!(validate_address("polkadot", CHECKSUMMED).is_err());
+        assert!(validate_address("moonbeam", POLKADOT).is_err());
+        assert!(validate_address(
+            "moonbeam",
+            &CHECKSUMMED.replace('A', "a").replacen('c', "C", 1)
+        )
+        .is_err());
+        assert!(validate_address("moonbeam", "0x1234").is_err());
+        assert!(validate_address("solana", POLKADOT).is_err());
+    }
+}
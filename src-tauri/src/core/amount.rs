@@ -0,0 +1,297 @@
+#![allow(dead_code)]
+
+use anyhow::{Context, Result};
+use ethereum_types::{U256, U512};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Most decimals a token can have, 10^77 is the largest power of ten in a U256
+pub const MAX_DECIMALS: u8 = 77;
+
+/// Largest scale and mantissa of a `Decimal`
+const DECIMAL_MAX_SCALE: u32 = 28;
+const DECIMAL_MAX_MANTISSA: u128 = (1 << 96) - 1;
+
+/// Token amount as raw integer units (wei, planck) and the token's decimals.
+/// Holds any 256-bit balance exactly, where `Decimal` stops at 28 digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TokenAmount {
+    /// Serialized as a decimal string
+    #[serde(
+        serialize_with = "units_to_string",
+        deserialize_with = "units_from_string"
+    )]
+    units: U256,
+    decimals: u8,
+}
+
+impl TokenAmount {
+    pub fn new(units: U256, decimals: u8) -> Result<Self> {
+        if decimals > MAX_DECIMALS {
+            anyhow::bail!(
+                "Tokens have at most {} decimals, not {}",
+                MAX_DECIMALS,
+                decimals
+            );
+        }
+        Ok(Self { units, decimals })
+    }
+
+    pub fn zero(decimals: u8) -> Result<Self> {
+        Self::new(U256::zero(), decimals)
+    }
+
+    /// Amount from an integer string in the smallest unit, as stored in
+    /// `transactions.value`
+    pub fn from_units(units: &str, decimals: i32) -> Result<Self> {
+        let decimals =
+            u8::try_from(decimals).with_context(|| format!("Invalid decimals {}", decimals))?;
+        let units = U256::from_dec_str(units.trim())
+            .map_err(|e| anyhow::anyhow!("Invalid amount {}: {:?}", units, e))?;
+        Self::new(units, decimals)
+    }
+
+    /// Amount from whole tokens such as "1.5". More fractional digits than
+    /// the token has are an error, not rounded away.
+    pub fn parse(amount: &str, decimals: u8) -> Result<Self> {
+        let trimmed = amount.trim();
+        let (whole, fraction) = trimmed.split_once('.').unwrap_or((trimmed, ""));
+        let fraction = fraction.trim_end_matches('0');
+        let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if (whole.is_empty() && fraction.is_empty()) || !digits(whole) || !digits(fraction) {
+            anyhow::bail!("Invalid token amount {}", amount);
+        }
+        if fraction.len() > decimals as usize {
+            anyhow::bail!(
+                "{} has more than {} decimals, the precision of the token",
+                amount,
+                decimals
+            );
+        }
+
+        let padded = format!(
+            "{}{}{}",
+            whole,
+            fraction,
+            "0".repeat(decimals as usize - fraction.len())
+        );
+        let units = U256::from_dec_str(&padded)
+            .map_err(|_| anyhow::anyhow!("Token amount {} is too large", amount))?;
+        Self::new(units, decimals)
+    }
+
+    /// Integer amount in the smallest unit
+    pub fn units(&self) -> U256 {
+        self.units
+    }
+
+    pub fn decimals(&self) -> u8 {
+        self.decimals
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.units.is_zero()
+    }
+
+    /// `None` on overflow or when the decimals differ
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+        Some(Self {
+            units: self.units.checked_add(other.units)?,
+            decimals: self.decimals,
+        })
+    }
+
+    /// `None` below zero or when the decimals differ
+    pub fn checked_sub(&self, other: &Self) -> Option<Self> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+        Some(Self {
+            units: self.units.checked_sub(other.units)?,
+            decimals: self.decimals,
+        })
+    }
+
+    /// Whole tokens as a `Decimal`, rounded half away from zero to the
+    /// fractional digits that fit. Fails when the whole part does not fit.
+    pub fn to_decimal(self) -> Result<Decimal> {
+        to_decimal(U512::from(self.units), self.decimals as u32, false)
+    }
+
+    /// Value at `rate` per whole token, such as a fiat price. The product is
+    /// exact and only rounded at the end to fit a `Decimal`.
+    pub fn mul_rate(&self, rate: Decimal) -> Result<Decimal> {
+        let mantissa = U256::from(rate.mantissa().unsigned_abs());
+        to_decimal(
+            self.units.full_mul(mantissa),
+            self.decimals as u32 + rate.scale(),
+            rate.is_sign_negative(),
+        )
+        .with_context(|| format!("{} at {} does not fit a decimal", self, rate))
+    }
+}
+
+/// Exact whole tokens without trailing zeros, e.g. "1.5"
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let one = U256::exp10(self.decimals as usize);
+        let (whole, fraction) = self.units.div_mod(one);
+        write!(f, "{}", whole)?;
+        if !fraction.is_zero() {
+            let fraction = format!(
+                "{:0>width$}",
+                fraction.to_string(),
+                width = self.decimals as usize
+            );
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        Ok(())
+    }
+}
+
+/// `units / 10^scale` as a `Decimal`, dropping as few fractional digits as
+/// needed for it to fit
+fn to_decimal(units: U512, scale: u32, negative: bool) -> Result<Decimal> {
+    let mut dropped = scale.saturating_sub(DECIMAL_MAX_SCALE);
+    loop {
+        let mantissa = round_div(units, U512::exp10(dropped as usize));
+        if mantissa <= U512::from(DECIMAL_MAX_MANTISSA) {
+            let mantissa = mantissa.low_u128() as i128;
+            let mantissa = if negative { -mantissa } else { mantissa };
+            return Ok(Decimal::from_i128_with_scale(mantissa, scale - dropped));
+        }
+        if dropped == scale {
+            anyhow::bail!("Amount does not fit a decimal");
+        }
+        dropped += 1;
+    }
+}
+
+/// Division rounding half away from zero
+fn round_div(n: U512, d: U512) -> U512 {
+    let (quotient, remainder) = n.div_mod(d);
+    if remainder * 2 >= d {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+fn units_to_string<S: Serializer>(units: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&units.to_string())
+}
+
+fn units_from_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+    let units = String::deserialize(deserializer)?;
+    U256::from_dec_str(&units).map_err(|e| serde::de::Error::custom(format!("{:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::str::FromStr;
+
+    fn units() -> impl Strategy<Value = U256> {
+        any::<[u64; 4]>().prop_map(U256)
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        let dot = TokenAmount::from_units("15000000000", 10).unwrap();
+        assert_eq!(dot.to_string(), "1.5");
+        assert_eq!(TokenAmount::zero(18).unwrap().to_string(), "0");
+        assert_eq!(TokenAmount::parse("1", 0).unwrap().units(), U256::one());
+
+        // A trillion ETH in wei, beyond the 28 digits of a Decimal
+        let wei = "1000000000000000000000000000001";
+        let eth = TokenAmount::from_units(wei, 18).unwrap();
+        assert_eq!(eth.to_string(), "1000000000000.000000000000000001");
+        assert_eq!(
+            eth.to_decimal().unwrap(),
+            Decimal::from_str("1000000000000.0000000000000000").unwrap()
+        );
+        assert_eq!(
+            eth.mul_rate(Decimal::from_str("2.5").unwrap()).unwrap(),
+            Decimal::from_str("2500000000000.0000000000000000").unwrap()
+        );
+
+        // More decimals than 10_u64.pow handles
+        let tiny = TokenAmount::from_units("1", 30).unwrap();
+        assert_eq!(tiny.to_string(), "0.000000000000000000000000000001");
+        assert_eq!(tiny.to_decimal().unwrap(), Decimal::ZERO);
+
+        let max = TokenAmount::new(U256::MAX, 0).unwrap();
+        assert!(max.to_decimal().is_err());
+        assert!(max
+            .checked_add(&TokenAmount::new(U256::one(), 0).unwrap())
+            .is_none());
+        assert!(TokenAmount::new(U256::one(), 78).is_err());
+        assert!(TokenAmount::from_units("-1", 18).is_err());
+        assert!(TokenAmount::parse("1.05", 1).is_err());
+        assert!(TokenAmount::parse(".", 1).is_err());
+        assert_eq!(TokenAmount::parse("1.50", 1).unwrap().to_string(), "1.5");
+
+        let json = serde_json::to_string(&eth).unwrap();
+        assert_eq!(json, format!(r#"{{"units":"{}","decimals":18}}"#, wei));
+        assert_eq!(serde_json::from_str::<TokenAmount>(&json).unwrap(), eth);
+    }
+
+    proptest! {
+        #[test]
+        fn display_parses_back(units in units(), decimals in 0..=MAX_DECIMALS) {
+            let amount = TokenAmount::new(units, decimals).unwrap();
+            prop_assert_eq!(TokenAmount::parse(&amount.to_string(), decimals).unwrap(), amount);
+            prop_assert_eq!(TokenAmount::from_units(&units.to_string(), decimals as i32).unwrap(), amount);
+        }
+
+        #[test]
+        fn decimals_are_exact_when_they_fit(units in any::<u64>(), decimals in 0..=28u8) {
+            let amount = TokenAmount::new(U256::from(units), decimals).unwrap();
+            let expected = Decimal::from_i128_with_scale(units as i128, decimals as u32);
+            prop_assert_eq!(amount.to_decimal().unwrap(), expected);
+            prop_assert_eq!(amount.mul_rate(Decimal::ONE).unwrap(), expected);
+        }
+
+        #[test]
+        fn rates_multiply_exactly(
+            units in any::<u64>(),
+            decimals in 0..=18u8,
+            rate in any::<u32>(),
+            rate_scale in 0..=8u32,
+        ) {
+            let amount = TokenAmount::new(U256::from(units), decimals).unwrap();
+            let rate = Decimal::from_i128_with_scale(rate as i128, rate_scale);
+            let expected = amount.to_decimal().unwrap().checked_mul(rate);
+            // Decimal rounds products past 28 digits itself, compare only exact ones
+            if let Some(expected) = expected.filter(|e| e.scale() == decimals as u32 + rate_scale) {
+                prop_assert_eq!(amount.mul_rate(rate).unwrap(), expected);
+                prop_assert_eq!(amount.mul_rate(-rate).unwrap(), -expected);
+            }
+        }
+
+        #[test]
+        fn rounding_keeps_the_whole_part(units in units(), decimals in 0..=MAX_DECIMALS) {
+            let amount = TokenAmount::new(units, decimals).unwrap();
+            if let Ok(decimal) = amount.to_decimal() {
+                let whole = amount.units() / U256::exp10(decimals as usize);
+                let truncated = decimal.trunc().to_string();
+                // Rounding half up may carry into the whole part
+                prop_assert!(truncated == whole.to_string() || truncated == (whole + 1).to_string());
+            }
+        }
+
+        #[test]
+        fn addition_and_subtraction_round_trip(a in units(), b in units(), decimals in 0..=MAX_DECIMALS) {
+            let a = TokenAmount::new(a, decimals).unwrap();
+            let b = TokenAmount::new(b, decimals).unwrap();
+            if let Some(sum) = a.checked_add(&b) {
+                prop_assert_eq!(sum.checked_sub(&b), Some(a));
+            }
+        }
+    }
+}
@@ -1,11 +1,16 @@
 #![allow(dead_code)]
 
+use super::secrets;
+use crate::api::price_feeds::ProviderKind;
+use anyhow::Result;
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::str::FromStr;
 
 /// Currency type enumeration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
-#[sqlx(type_name = "TEXT")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
 pub enum CurrencyType {
     #[serde(rename = "fiat")]
     Fiat,
@@ -36,13 +41,17 @@ pub struct Currency {
     pub fixer_id: Option<String>,
     pub symbol: Option<String>,
     pub icon_url: Option<String>,
+    /// Native token of a Substrate chain
+    pub is_substrate: bool,
+    /// Chain the token is native to, e.g. `polkadot`
+    pub chain_id: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
 
 /// Exchange rate source enumeration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
-#[sqlx(type_name = "TEXT")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
 pub enum ExchangeRateSource {
     #[serde(rename = "coingecko")]
     CoinGecko,
@@ -76,13 +85,58 @@ pub struct ExchangeRate {
     pub source: ExchangeRateSource,
     pub ttl_seconds: i32,
     pub metadata: Option<String>,
+    /// Rate for a past date, never expired by the spot TTL
+    pub is_historical: bool,
     pub created_at: String,
     pub updated_at: String,
 }
 
+/// Rate from the manual rate book, valid for a date range
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ManualRate {
+    pub id: String,
+    pub profile_id: String,
+    pub from_currency: String,
+    pub to_currency: String,
+    pub rate: String,
+    pub valid_from: NaiveDate,
+    /// Inclusive, `None` while the rate is open-ended
+    pub valid_to: Option<NaiveDate>,
+    pub note: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Price of an asset on one day, from the permanent price history
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DailyPrice {
+    pub asset: String,
+    pub fiat: String,
+    pub date: NaiveDate,
+    /// First price of the day, `None` when the feed has one price per day
+    pub open: Option<String>,
+    /// Last price of the day
+    pub close: String,
+    pub source: ExchangeRateSource,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Manual rate as entered or imported, one CSV row per rate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewManualRate {
+    pub from_currency: String,
+    pub to_currency: String,
+    /// Decimal string, kept as text so CSV import cannot round it
+    pub rate: String,
+    pub valid_from: NaiveDate,
+    pub valid_to: Option<NaiveDate>,
+    pub note: Option<String>,
+}
+
 /// Conversion method enumeration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
-#[sqlx(type_name = "TEXT")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
 pub enum ConversionMethod {
     #[serde(rename = "spot")]
     Spot,
@@ -102,9 +156,34 @@ impl std::fmt::Display for ConversionMethod {
     }
 }
 
+/// Cost basis method used to match disposals to acquisition lots
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum CostBasisMethod {
+    #[serde(rename = "fifo")]
+    Fifo,
+    #[serde(rename = "lifo")]
+    Lifo,
+    #[serde(rename = "hifo")]
+    Hifo,
+    #[serde(rename = "average")]
+    Average,
+}
+
+impl std::fmt::Display for CostBasisMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CostBasisMethod::Fifo => write!(f, "fifo"),
+            CostBasisMethod::Lifo => write!(f, "lifo"),
+            CostBasisMethod::Hifo => write!(f, "hifo"),
+            CostBasisMethod::Average => write!(f, "average"),
+        }
+    }
+}
+
 /// Currency display format enumeration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
-#[sqlx(type_name = "TEXT")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
 pub enum CurrencyDisplayFormat {
     #[serde(rename = "symbol")]
     Symbol,
@@ -132,13 +211,15 @@ pub struct AccountSettings {
     pub primary_currency: String,
     pub reporting_currencies: Option<String>, // Comma-separated list
     pub conversion_method: ConversionMethod,
+    pub cost_basis_method: CostBasisMethod,
     pub decimal_places: i32,
     pub use_thousands_separator: bool,
     pub currency_display_format: CurrencyDisplayFormat,
     pub auto_convert: bool,
     pub cache_exchange_rates: bool,
-    pub coingecko_api_key: Option<String>,
-    pub fixer_api_key: Option<String>,
+    /// Comma-separated price providers, `None` for the default order
+    #[serde(default)]
+    pub price_provider_priority: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -160,6 +241,65 @@ impl AccountSettings {
             Some(currencies.join(","))
         };
     }
+
+    /// Primary currency followed by the other reporting currencies, once each
+    pub fn conversion_currencies(&self) -> Vec<String> {
+        let mut currencies = vec![self.primary_currency.clone()];
+        for currency in self.get_reporting_currencies() {
+            if !currency.is_empty() && !currencies.contains(&currency) {
+                currencies.push(currency);
+            }
+        }
+        currencies
+    }
+
+    /// Price providers in the order the profile wants them asked, `None`
+    /// when it keeps the default order
+    pub fn price_providers(&self) -> Result<Option<Vec<ProviderKind>>> {
+        let Some(priority) = self
+            .price_provider_priority
+            .as_deref()
+            .filter(|p| !p.trim().is_empty())
+        else {
+            return Ok(None);
+        };
+
+        let mut providers = Vec::new();
+        for kind in priority.split(',') {
+            let kind = ProviderKind::from_str(kind.trim())?;
+            if !providers.contains(&kind) {
+                providers.push(kind);
+            }
+        }
+        Ok(Some(providers))
+    }
+}
+
+/// Price feed that needs an API key
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PriceFeed {
+    #[serde(rename = "coingecko")]
+    CoinGecko,
+    #[serde(rename = "fixer")]
+    Fixer,
+}
+
+impl PriceFeed {
+    /// Name of the API key in the secret store
+    pub fn secret_name(&self) -> &'static str {
+        match self {
+            PriceFeed::CoinGecko => secrets::COINGECKO_API_KEY,
+            PriceFeed::Fixer => secrets::FIXER_API_KEY,
+        }
+    }
+}
+
+/// Decrypted API keys for the price feed clients.
+/// Deliberately neither `Serialize` nor `Debug`.
+#[derive(Clone, Default)]
+pub struct PriceFeedKeys {
+    pub coingecko: Option<String>,
+    pub fixer: Option<String>,
 }
 
 /// Enhanced transaction structure with currency conversion
@@ -190,6 +330,22 @@ pub struct TransactionWithConversion {
     pub updated_at: String,
 }
 
+/// Value of a transaction in one of its profile's reporting currencies
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TransactionConversion {
+    pub transaction_id: String,
+    pub currency: String,
+    pub amount: String,
+    /// Per whole token
+    pub exchange_rate: String,
+    /// `None` when the transaction is in this currency
+    pub exchange_rate_source: Option<String>,
+    pub exchange_rate_timestamp: Option<String>,
+    pub conversion_method: ConversionMethod,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
 /// Helper struct for currency conversion calculations
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CurrencyConversion {
@@ -199,6 +355,10 @@ pub struct CurrencyConversion {
     pub converted_amount: String,
     pub exchange_rate: String,
     pub timestamp: String,
+    /// Where the rate came from, `None` when no conversion was needed
+    pub source: Option<ExchangeRateSource>,
+    /// Time the rate applies to, which may differ from `timestamp`
+    pub rate_timestamp: Option<String>,
 }
 
 #[cfg(test)]
@@ -213,13 +373,13 @@ mod tests {
             primary_currency: "USD".to_string(),
             reporting_currencies: Some("EUR,GBP,JPY".to_string()),
             conversion_method: ConversionMethod::Historical,
+            cost_basis_method: CostBasisMethod::Fifo,
             decimal_places: 2,
             use_thousands_separator: true,
             currency_display_format: CurrencyDisplayFormat::Symbol,
             auto_convert: true,
             cache_exchange_rates: true,
-            coingecko_api_key: None,
-            fixer_api_key: None,
+            price_provider_priority: None,
             created_at: "2025-01-01".to_string(),
             updated_at: "2025-01-01".to_string(),
         };
@@ -229,5 +389,17 @@ mod tests {
 
         settings.set_reporting_currencies(vec!["CAD".to_string(), "AUD".to_string()]);
         assert_eq!(settings.reporting_currencies, Some("CAD,AUD".to_string()));
+
+        settings.reporting_currencies = Some("CHF, USD,,CAD".to_string());
+        assert_eq!(settings.conversion_currencies(), vec!["USD", "CHF", "CAD"]);
+
+        assert_eq!(settings.price_providers().unwrap(), None);
+        settings.price_provider_priority = Some("manual, coingecko,manual".to_string());
+        assert_eq!(
+            settings.price_providers().unwrap(),
+            Some(vec![ProviderKind::Manual, ProviderKind::CoinGecko])
+        );
+        settings.price_provider_priority = Some("binance".to_string());
+        assert!(settings.price_providers().is_err());
     }
 }
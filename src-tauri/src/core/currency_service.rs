@@ -1,23 +1,89 @@
 #![allow(dead_code)]
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use rust_decimal::Decimal;
-use sqlx::{Pool, Sqlite};
+use serde::Serialize;
+use sqlx::{FromRow, Pool, Sqlite};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::str::FromStr;
+use std::sync::Arc;
+use uuid::Uuid;
 
+use super::amount::TokenAmount;
 use super::currency::{
-    AccountSettings, ConversionMethod, Currency, CurrencyConversion, ExchangeRate,
-    TransactionWithConversion,
+    AccountSettings, ConversionMethod, Currency, CurrencyConversion, CurrencyType, DailyPrice,
+    ExchangeRate, ExchangeRateSource, ManualRate, NewManualRate, PriceFeed, PriceFeedKeys,
+    TransactionConversion, TransactionWithConversion,
 };
+use super::secrets::SecretStore;
+use crate::api::price_feeds::{
+    find_manual_rate, CoinGeckoClient, FixerClient, ManualProvider, PriceProvider,
+    PriceProviderConfig, ProviderKind,
+};
+
+/// Historical feeds publish one rate per day at midnight UTC, so by default a
+/// stored rate up to a day away from the transaction is close enough
+pub const DEFAULT_HISTORICAL_TOLERANCE: Duration = Duration::days(1);
+
+/// Currencies compound rates are routed through, in order of preference
+pub const DEFAULT_PIVOT_CURRENCIES: &[&str] = &["USD"];
+
+/// Days of prices fetched per `market_chart/range` request
+const PRICE_HISTORY_CHUNK_DAYS: i64 = 365;
+
+/// How long fetched spot rates stay fresh
+const CRYPTO_RATE_TTL: i32 = 300;
+const FIAT_RATE_TTL: i32 = 86_400;
 
 /// Currency Service for database operations and conversions
 pub struct CurrencyService {
     pool: Pool<Sqlite>,
+    secrets: Option<Arc<SecretStore>>,
+    historical_tolerance: Duration,
+    price_providers: PriceProviderConfig,
+    pivot_currencies: Vec<String>,
 }
 
 impl CurrencyService {
     pub fn new(pool: Pool<Sqlite>) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            secrets: None,
+            historical_tolerance: DEFAULT_HISTORICAL_TOLERANCE,
+            price_providers: PriceProviderConfig::default(),
+            pivot_currencies: DEFAULT_PIVOT_CURRENCIES
+                .iter()
+                .map(|code| code.to_string())
+                .collect(),
+        }
+    }
+
+    /// Service that can read and write the price feed API keys
+    pub fn with_secrets(pool: Pool<Sqlite>, secrets: Arc<SecretStore>) -> Self {
+        Self {
+            secrets: Some(secrets),
+            ..Self::new(pool)
+        }
+    }
+
+    /// How far from the requested time a stored rate may be for historical
+    /// conversion before a fresh one is fetched
+    pub fn with_historical_tolerance(mut self, tolerance: Duration) -> Self {
+        self.historical_tolerance = tolerance;
+        self
+    }
+
+    /// Price providers to ask on a cache miss, and in which order
+    pub fn with_price_providers(mut self, config: PriceProviderConfig) -> Self {
+        self.price_providers = config;
+        self
+    }
+
+    /// Currencies to route through when a pair has no direct rate
+    pub fn with_pivot_currencies(mut self, pivots: Vec<String>) -> Self {
+        self.pivot_currencies = pivots;
+        self
     }
 
     /// Get all supported currencies
@@ -56,6 +122,18 @@ impl CurrencyService {
         Ok(currencies)
     }
 
+    /// Native tokens of Substrate chains
+    pub async fn get_substrate_currencies(&self) -> Result<Vec<Currency>> {
+        let currencies = sqlx::query_as::<_, Currency>(
+            "SELECT * FROM currencies WHERE is_substrate = 1 AND is_supported = 1 ORDER BY code",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to fetch Substrate currencies")?;
+
+        Ok(currencies)
+    }
+
     /// Get account settings for a profile
     pub async fn get_account_settings(&self, profile_id: &str) -> Result<Option<AccountSettings>> {
         let settings = sqlx::query_as::<_, AccountSettings>(
@@ -69,27 +147,31 @@ impl CurrencyService {
         Ok(settings)
     }
 
-    /// Update account settings
-    pub async fn update_account_settings(&self, settings: &AccountSettings) -> Result<()> {
+    /// Save a profile's settings. Returns true when the primary currency,
+    /// reporting currencies or conversion method changed, which leaves
+    /// conversions for `convert_transactions` to do.
+    pub async fn update_account_settings(&self, settings: &AccountSettings) -> Result<bool> {
+        // Refuse an unknown provider now rather than on the next rate lookup
+        let providers = settings.price_providers()?;
+        let previous = self.conversion_targets(&settings.profile_id).await?;
         sqlx::query(
             r#"
             INSERT INTO account_settings (
                 id, profile_id, primary_currency, reporting_currencies, conversion_method,
-                decimal_places, use_thousands_separator, currency_display_format,
-                auto_convert, cache_exchange_rates, coingecko_api_key, fixer_api_key,
-                created_at, updated_at
+                cost_basis_method, decimal_places, use_thousands_separator, currency_display_format,
+                auto_convert, cache_exchange_rates, price_provider_priority, created_at, updated_at
             ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
             ON CONFLICT(profile_id) DO UPDATE SET
                 primary_currency = excluded.primary_currency,
                 reporting_currencies = excluded.reporting_currencies,
                 conversion_method = excluded.conversion_method,
+                cost_basis_method = excluded.cost_basis_method,
                 decimal_places = excluded.decimal_places,
                 use_thousands_separator = excluded.use_thousands_separator,
                 currency_display_format = excluded.currency_display_format,
                 auto_convert = excluded.auto_convert,
                 cache_exchange_rates = excluded.cache_exchange_rates,
-                coingecko_api_key = excluded.coingecko_api_key,
-                fixer_api_key = excluded.fixer_api_key,
+                price_provider_priority = excluded.price_provider_priority,
                 updated_at = datetime('now')
             "#,
         )
@@ -98,18 +180,76 @@ impl CurrencyService {
         .bind(&settings.primary_currency)
         .bind(&settings.reporting_currencies)
         .bind(settings.conversion_method.to_string())
+        .bind(settings.cost_basis_method.to_string())
         .bind(settings.decimal_places)
         .bind(settings.use_thousands_separator)
         .bind(settings.currency_display_format.to_string())
         .bind(settings.auto_convert)
         .bind(settings.cache_exchange_rates)
-        .bind(&settings.coingecko_api_key)
-        .bind(&settings.fixer_api_key)
+        .bind(providers.map(|providers| {
+            providers
+                .iter()
+                .map(ProviderKind::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        }))
         .execute(&self.pool)
         .await
         .context("Failed to update account settings")?;
 
-        Ok(())
+        Ok(previous
+            != (
+                settings.conversion_currencies(),
+                settings.conversion_method.clone(),
+            ))
+    }
+
+    /// Store a price feed API key encrypted, or remove it with `None`
+    pub async fn set_api_key(
+        &self,
+        profile_id: &str,
+        feed: PriceFeed,
+        api_key: Option<&str>,
+    ) -> Result<()> {
+        self.secret_store()?
+            .set(&self.pool, profile_id, feed.secret_name(), api_key)
+            .await
+    }
+
+    /// Price feeds a profile has an API key for
+    pub async fn configured_api_keys(&self, profile_id: &str) -> Result<Vec<PriceFeed>> {
+        let names = self.secret_store()?.names(&self.pool, profile_id).await?;
+
+        Ok([PriceFeed::CoinGecko, PriceFeed::Fixer]
+            .into_iter()
+            .filter(|feed| names.iter().any(|n| n == feed.secret_name()))
+            .collect())
+    }
+
+    /// Decrypted API keys, only for constructing the price feed clients.
+    /// While the store is locked the feeds are used without keys.
+    pub(crate) async fn price_feed_keys(&self, profile_id: &str) -> Result<PriceFeedKeys> {
+        let Some(secrets) = &self.secrets else {
+            return Ok(PriceFeedKeys::default());
+        };
+        if !secrets.is_unlocked().await {
+            return Ok(PriceFeedKeys::default());
+        }
+
+        Ok(PriceFeedKeys {
+            coingecko: secrets
+                .get(&self.pool, profile_id, PriceFeed::CoinGecko.secret_name())
+                .await?,
+            fixer: secrets
+                .get(&self.pool, profile_id, PriceFeed::Fixer.secret_name())
+                .await?,
+        })
+    }
+
+    fn secret_store(&self) -> Result<&SecretStore> {
+        self.secrets
+            .as_deref()
+            .context("Currency service was created without a secret store")
     }
 
     /// Get exchange rate from cache
@@ -122,7 +262,7 @@ impl CurrencyService {
         let rate = sqlx::query_as::<_, ExchangeRate>(
             r#"
             SELECT * FROM exchange_rates
-            WHERE from_currency = ? AND to_currency = ?
+            WHERE from_currency = ? AND to_currency = ? AND is_historical = 0
             AND (unixepoch('now') - unixepoch(timestamp)) <= ttl_seconds
             ORDER BY timestamp DESC
             LIMIT 1
@@ -143,8 +283,8 @@ impl CurrencyService {
             r#"
             INSERT INTO exchange_rates (
                 id, from_currency, to_currency, rate, timestamp, source,
-                ttl_seconds, metadata, created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
+                ttl_seconds, metadata, is_historical, created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
             "#,
         )
         .bind(&rate.id)
@@ -155,6 +295,7 @@ impl CurrencyService {
         .bind(rate.source.to_string())
         .bind(rate.ttl_seconds)
         .bind(&rate.metadata)
+        .bind(rate.is_historical)
         .execute(&self.pool)
         .await
         .context("Failed to cache exchange rate")?;
@@ -162,9 +303,351 @@ impl CurrencyService {
         Ok(())
     }
 
-    /// Convert amount from one currency to another
+    /// Stored historical rate closest to `at`, if one lies within the
+    /// historical tolerance. Spot rates only stand for the moment they were
+    /// cached and are left out.
+    pub async fn get_nearest_exchange_rate(
+        &self,
+        from_currency: &str,
+        to_currency: &str,
+        at: DateTime<Utc>,
+    ) -> Result<Option<ExchangeRate>> {
+        let rate = sqlx::query_as::<_, ExchangeRate>(
+            r#"
+            SELECT * FROM exchange_rates
+            WHERE from_currency = ?1 AND to_currency = ?2 AND is_historical = 1
+            AND abs(unixepoch(timestamp) - ?3) <= ?4
+            ORDER BY abs(unixepoch(timestamp) - ?3)
+            LIMIT 1
+            "#,
+        )
+        .bind(from_currency)
+        .bind(to_currency)
+        .bind(at.timestamp())
+        .bind(self.historical_tolerance.num_seconds())
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to fetch historical exchange rate")?;
+
+        Ok(rate)
+    }
+
+    /// Current rate: the cached one while it is fresh, otherwise fetched
+    /// from the price providers or compounded through a pivot currency, and
+    /// cached
+    pub async fn get_spot_rate(
+        &self,
+        profile_id: &str,
+        from_currency: &str,
+        to_currency: &str,
+    ) -> Result<ExchangeRate> {
+        self.get_rate(profile_id, from_currency, to_currency, None)
+            .await
+    }
+
+    /// Rate in effect at `at`: the nearest stored rate, or on a miss the daily
+    /// rate from the price providers or compounded through a pivot currency,
+    /// which is then kept for good
+    pub async fn get_historical_rate(
+        &self,
+        profile_id: &str,
+        from_currency: &str,
+        to_currency: &str,
+        at: DateTime<Utc>,
+    ) -> Result<ExchangeRate> {
+        self.get_rate(profile_id, from_currency, to_currency, Some(at))
+            .await
+    }
+
+    /// Spot rate, or the historical rate at `at`
+    async fn get_rate(
+        &self,
+        profile_id: &str,
+        from_currency: &str,
+        to_currency: &str,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<ExchangeRate> {
+        let error = match self
+            .get_direct_rate(profile_id, from_currency, to_currency, at)
+            .await
+        {
+            Ok(rate) => return Ok(rate),
+            Err(e) => e,
+        };
+
+        match self
+            .compound_rate(profile_id, from_currency, to_currency, at)
+            .await?
+        {
+            Some(rate) => Ok(rate),
+            None => Err(error),
+        }
+    }
+
+    async fn get_direct_rate(
+        &self,
+        profile_id: &str,
+        from_currency: &str,
+        to_currency: &str,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<ExchangeRate> {
+        let stored = match at {
+            Some(at) => {
+                self.get_nearest_exchange_rate(from_currency, to_currency, at)
+                    .await?
+            }
+            None => {
+                self.get_cached_exchange_rate(from_currency, to_currency)
+                    .await?
+            }
+        };
+        if let Some(rate) = stored {
+            return Ok(rate);
+        }
+        if let Some(at) = at {
+            if let Some(rate) = self
+                .get_price_history_rate(from_currency, to_currency, at.date_naive())
+                .await?
+            {
+                return Ok(rate);
+            }
+        }
+
+        self.fetch_rate(
+            profile_id,
+            from_currency,
+            to_currency,
+            at.map(|at| at.date_naive()),
+        )
+        .await
+    }
+
+    /// Multiply the rates to and from the first pivot currency both are
+    /// available for, and cache the product as a `compound` rate that lists
+    /// its components. `None` when no pivot connects the pair.
+    async fn compound_rate(
+        &self,
+        profile_id: &str,
+        from_currency: &str,
+        to_currency: &str,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<ExchangeRate>> {
+        for pivot in &self.pivot_currencies {
+            if pivot == from_currency || pivot == to_currency {
+                continue;
+            }
+            let Ok(first) = self
+                .get_direct_rate(profile_id, from_currency, pivot, at)
+                .await
+            else {
+                continue;
+            };
+            let Ok(second) = self
+                .get_direct_rate(profile_id, pivot, to_currency, at)
+                .await
+            else {
+                continue;
+            };
+
+            let rate = Decimal::from_str(&first.rate)?
+                .checked_mul(Decimal::from_str(&second.rate)?)
+                .with_context(|| {
+                    format!(
+                        "Compound rate for {}/{} via {} overflows",
+                        from_currency, to_currency, pivot
+                    )
+                })?;
+            // The product is only as fresh as its oldest component
+            let oldest =
+                if parse_timestamp(&first.timestamp)? <= parse_timestamp(&second.timestamp)? {
+                    &first
+                } else {
+                    &second
+                };
+            let components: Vec<_> = [&first, &second]
+                .iter()
+                .map(|leg| {
+                    let mut component = serde_json::json!({
+                        "from_currency": leg.from_currency,
+                        "to_currency": leg.to_currency,
+                        "rate": leg.rate,
+                        "timestamp": leg.timestamp,
+                        "source": leg.source,
+                    });
+                    // Price history rates have no `exchange_rates` row to point to
+                    match price_history_reference(leg) {
+                        Some(reference) => component["reference"] = reference,
+                        None => component["id"] = leg.id.clone().into(),
+                    }
+                    component
+                })
+                .collect();
+
+            let now = Utc::now().format(SQLITE_DATETIME).to_string();
+            let rate = ExchangeRate {
+                id: Uuid::new_v4().to_string(),
+                from_currency: from_currency.to_string(),
+                to_currency: to_currency.to_string(),
+                rate: rate.normalize().to_string(),
+                timestamp: oldest.timestamp.clone(),
+                source: ExchangeRateSource::Compound,
+                ttl_seconds: first.ttl_seconds.min(second.ttl_seconds),
+                metadata: Some(
+                    serde_json::json!({ "pivot": pivot, "components": components }).to_string(),
+                ),
+                is_historical: at.is_some(),
+                created_at: now.clone(),
+                updated_at: now,
+            };
+            self.cache_exchange_rate(&rate).await?;
+            return Ok(Some(rate));
+        }
+
+        Ok(None)
+    }
+
+    /// Providers in the profile's priority order, or the service's default
+    /// order. Fixer is skipped without an API key.
+    async fn price_providers(
+        &self,
+        profile_id: &str,
+        keys: PriceFeedKeys,
+    ) -> Result<Vec<Box<dyn PriceProvider>>> {
+        let config = &self.price_providers;
+        let priority = match self.get_account_settings(profile_id).await? {
+            Some(settings) => settings.price_providers()?,
+            None => None,
+        }
+        .unwrap_or_else(|| config.priority.clone());
+
+        Ok(priority
+            .iter()
+            .filter_map(|kind| -> Option<Box<dyn PriceProvider>> {
+                match kind {
+                    ProviderKind::CoinGecko => {
+                        Some(Box::new(self.coingecko_client(keys.coingecko.clone())))
+                    }
+                    ProviderKind::Fixer => {
+                        let client = FixerClient::new(keys.fixer.clone()?);
+                        Some(Box::new(match &config.fixer_url {
+                            Some(url) => client.with_base_url(url),
+                            None => client,
+                        }))
+                    }
+                    ProviderKind::Manual => {
+                        Some(Box::new(ManualProvider::new(self.pool.clone(), profile_id)))
+                    }
+                }
+            })
+            .collect())
+    }
+
+    fn coingecko_client(&self, api_key: Option<String>) -> CoinGeckoClient {
+        let client = CoinGeckoClient::new(api_key);
+        match &self.price_providers.coingecko_url {
+            Some(url) => client.with_base_url(url),
+            None => client,
+        }
+    }
+
+    /// Ask the providers in turn for the current rate, or the rate on `date`,
+    /// and cache the first answer
+    async fn fetch_rate(
+        &self,
+        profile_id: &str,
+        from_currency: &str,
+        to_currency: &str,
+        date: Option<NaiveDate>,
+    ) -> Result<ExchangeRate> {
+        let from = self
+            .get_currency(from_currency)
+            .await?
+            .with_context(|| format!("Unknown currency {}", from_currency))?;
+        let to = self
+            .get_currency(to_currency)
+            .await?
+            .with_context(|| format!("Unknown currency {}", to_currency))?;
+        let keys = self.price_feed_keys(profile_id).await?;
+
+        let mut errors = Vec::new();
+        for provider in self.price_providers(profile_id, keys).await? {
+            let result = match date {
+                Some(date) => provider.historical_rate(&from, &to, date).await,
+                None => provider.spot_rate(&from, &to).await,
+            };
+            let rate = match result {
+                Ok(Some(rate)) => rate,
+                Ok(None) => continue,
+                Err(e) => {
+                    eprintln!(
+                        "{} failed to price {}/{}: {:#}",
+                        provider.source(),
+                        from_currency,
+                        to_currency,
+                        e
+                    );
+                    errors.push(format!("{}: {}", provider.source(), e));
+                    continue;
+                }
+            };
+
+            let now = Utc::now().format(SQLITE_DATETIME).to_string();
+            let rate = ExchangeRate {
+                id: Uuid::new_v4().to_string(),
+                from_currency: from_currency.to_string(),
+                to_currency: to_currency.to_string(),
+                rate: rate.normalize().to_string(),
+                timestamp: match date {
+                    Some(date) => date
+                        .and_hms_opt(0, 0, 0)
+                        .unwrap()
+                        .format(SQLITE_DATETIME)
+                        .to_string(),
+                    None => now.clone(),
+                },
+                source: provider.source(),
+                ttl_seconds: match date {
+                    Some(_) => 0,
+                    None if from.currency_type == CurrencyType::Fiat
+                        && to.currency_type == CurrencyType::Fiat =>
+                    {
+                        FIAT_RATE_TTL
+                    }
+                    None => CRYPTO_RATE_TTL,
+                },
+                metadata: date.map(|date| serde_json::json!({ "date": date }).to_string()),
+                is_historical: date.is_some(),
+                created_at: now.clone(),
+                updated_at: now,
+            };
+            self.cache_exchange_rate(&rate).await?;
+            return Ok(rate);
+        }
+
+        if errors.is_empty() {
+            anyhow::bail!(
+                "No price provider has rates for {}/{}",
+                from_currency,
+                to_currency
+            );
+        }
+        anyhow::bail!(
+            "Could not fetch a rate for {}/{}: {}",
+            from_currency,
+            to_currency,
+            errors.join("; ")
+        )
+    }
+
+    /// Convert amount from one currency to another.
+    ///
+    /// `method` defaults to the profile's conversion method. Historical
+    /// conversion uses the rate in effect at `timestamp`, and rates not stored
+    /// yet are fetched with the profile's API keys. Fixed conversion only uses
+    /// the manual rate book.
     pub async fn convert(
         &self,
+        profile_id: &str,
         from_currency: &str,
         to_currency: &str,
         amount: &str,
@@ -180,46 +663,312 @@ impl CurrencyService {
                 converted_amount: amount.to_string(),
                 exchange_rate: "1.0".to_string(),
                 timestamp: timestamp.unwrap_or("").to_string(),
+                source: None,
+                rate_timestamp: None,
             });
         }
 
-        let use_historical = method == Some(ConversionMethod::Historical);
-
-        // Try to get cached rate if not using historical
-        let exchange_rate = if !use_historical {
-            self.get_cached_exchange_rate(from_currency, to_currency)
-                .await?
-        } else {
-            // For historical rates, fetch from database based on timestamp
-            // This would need a more sophisticated query
-            None
-        };
-
-        let rate_value = if let Some(rate) = exchange_rate {
-            Decimal::from_str(&rate.rate).context("Failed to parse exchange rate")?
-        } else {
-            // If no cached rate, would need to fetch from external API
-            // For now, return an error
-            anyhow::bail!(
-                "Exchange rate not available for {}/{}",
-                from_currency,
-                to_currency
-            );
+        let method = match method {
+            Some(method) => method,
+            None => self.conversion_settings(profile_id).await?.1,
         };
+        let at = timestamp.map(parse_timestamp).transpose()?;
+        let applied = self
+            .applied_rate(profile_id, from_currency, to_currency, &method, at)
+            .await?;
 
         let amount_decimal = Decimal::from_str(amount).context("Failed to parse amount")?;
-        let converted = amount_decimal * rate_value;
+        let converted = amount_decimal * applied.rate;
 
         Ok(CurrencyConversion {
             from_currency: from_currency.to_string(),
             to_currency: to_currency.to_string(),
             amount: amount.to_string(),
             converted_amount: converted.to_string(),
-            exchange_rate: rate_value.to_string(),
+            exchange_rate: applied.rate.to_string(),
             timestamp: timestamp.unwrap_or("").to_string(),
+            source: applied.source,
+            rate_timestamp: Some(applied.timestamp),
+        })
+    }
+
+    /// Primary currency and conversion method of a profile, USD at spot
+    /// rates for a profile without settings
+    async fn conversion_settings(&self, profile_id: &str) -> Result<(String, ConversionMethod)> {
+        Ok(match self.get_account_settings(profile_id).await? {
+            Some(settings) => (settings.primary_currency, settings.conversion_method),
+            None => ("USD".to_string(), ConversionMethod::Spot),
         })
     }
 
+    /// Currencies transactions are converted to, primary first, and the
+    /// conversion method
+    async fn conversion_targets(
+        &self,
+        profile_id: &str,
+    ) -> Result<(Vec<String>, ConversionMethod)> {
+        Ok(match self.get_account_settings(profile_id).await? {
+            Some(settings) => (settings.conversion_currencies(), settings.conversion_method),
+            None => (vec!["USD".to_string()], ConversionMethod::Spot),
+        })
+    }
+
+    /// Rate `method` prescribes for a conversion at `at`, or now
+    async fn applied_rate(
+        &self,
+        profile_id: &str,
+        from_currency: &str,
+        to_currency: &str,
+        method: &ConversionMethod,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<AppliedRate> {
+        match method {
+            ConversionMethod::Fixed => {
+                let date = at.unwrap_or_else(Utc::now).date_naive();
+                let (manual, rate) = self
+                    .get_manual_rate(profile_id, from_currency, to_currency, date)
+                    .await?
+                    .with_context(|| {
+                        format!(
+                            "No manual rate for {}/{} on {}, fixed conversion does not use market rates",
+                            from_currency, to_currency, date
+                        )
+                    })?;
+                Ok(AppliedRate {
+                    rate,
+                    source: Some(ExchangeRateSource::Manual),
+                    timestamp: manual.valid_from.to_string(),
+                })
+            }
+            ConversionMethod::Historical | ConversionMethod::Spot => {
+                let exchange_rate = if *method == ConversionMethod::Historical {
+                    let at = at.context("Historical conversion needs the transaction timestamp")?;
+                    self.get_historical_rate(profile_id, from_currency, to_currency, at)
+                        .await?
+                } else {
+                    self.get_spot_rate(profile_id, from_currency, to_currency)
+                        .await?
+                };
+                Ok(AppliedRate {
+                    rate: Decimal::from_str(&exchange_rate.rate)
+                        .context("Failed to parse exchange rate")?,
+                    source: Some(exchange_rate.source),
+                    timestamp: exchange_rate.timestamp,
+                })
+            }
+        }
+    }
+
+    /// Convert every transaction of the profile to its primary and reporting
+    /// currencies, where that conversion is missing or was done with another
+    /// method. The primary currency conversion is mirrored in
+    /// `amount_primary`, and conversions to currencies no longer reported are
+    /// dropped. Rates are looked up once per asset, and per day unless the
+    /// method is spot. Transactions without a rate keep their old values and
+    /// are listed in the report.
+    pub async fn convert_transactions(&self, profile_id: &str) -> Result<ConversionReport> {
+        let (currencies, method) = self.conversion_targets(profile_id).await?;
+        let primary_currency = currencies[0].clone();
+
+        let mut converted = Vec::new();
+        let mut unpriced = Vec::new();
+        for currency in &currencies {
+            let pending = sqlx::query_as::<_, PendingConversion>(
+                r#"
+                SELECT t.id, t.value, t.token_symbol, t.token_decimals, t.timestamp
+                FROM transactions t
+                LEFT JOIN transaction_conversions c
+                    ON c.transaction_id = t.id AND c.currency = ?2
+                WHERE t.profile_id = ?1
+                AND (c.transaction_id IS NULL OR c.conversion_method IS NOT ?3)
+                ORDER BY t.timestamp
+                "#,
+            )
+            .bind(profile_id)
+            .bind(currency)
+            .bind(method.to_string())
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to fetch transactions to convert")?;
+            if method == ConversionMethod::Historical {
+                self.backfill_pending(profile_id, currency, &pending).await;
+            }
+
+            let mut rates = HashMap::new();
+            for transaction in pending {
+                match self
+                    .price_transaction(profile_id, currency, &method, &transaction, &mut rates)
+                    .await
+                {
+                    Ok((amount, rate)) => {
+                        converted.push((currency.clone(), transaction.id, amount, rate))
+                    }
+                    Err(e) => unpriced.push(UnpricedTransaction {
+                        transaction_id: transaction.id,
+                        currency: currency.clone(),
+                        token_symbol: transaction.token_symbol,
+                        timestamp: transaction.timestamp,
+                        reason: format!("{:#}", e),
+                    }),
+                }
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            DELETE FROM transaction_conversions
+            WHERE transaction_id IN (SELECT id FROM transactions WHERE profile_id = ?)
+            AND currency NOT IN (SELECT value FROM json_each(?))
+            "#,
+        )
+        .bind(profile_id)
+        .bind(serde_json::to_string(&currencies)?)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to drop conversions to currencies no longer reported")?;
+        for (currency, id, amount, rate) in &converted {
+            sqlx::query(
+                r#"
+                INSERT INTO transaction_conversions (
+                    transaction_id, currency, amount, exchange_rate, exchange_rate_source,
+                    exchange_rate_timestamp, conversion_method
+                ) VALUES (?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(transaction_id, currency) DO UPDATE SET
+                    amount = excluded.amount,
+                    exchange_rate = excluded.exchange_rate,
+                    exchange_rate_source = excluded.exchange_rate_source,
+                    exchange_rate_timestamp = excluded.exchange_rate_timestamp,
+                    conversion_method = excluded.conversion_method,
+                    updated_at = datetime('now')
+                "#,
+            )
+            .bind(id)
+            .bind(currency)
+            .bind(amount.normalize().to_string())
+            .bind(rate.rate.to_string())
+            .bind(rate.source.as_ref().map(ToString::to_string))
+            .bind(&rate.timestamp)
+            .bind(method.to_string())
+            .execute(&mut *tx)
+            .await
+            .context("Failed to save transaction conversion")?;
+        }
+        sqlx::query(
+            r#"
+            UPDATE transactions SET
+                amount_primary = c.amount,
+                primary_currency = c.currency,
+                exchange_rate = c.exchange_rate,
+                exchange_rate_source = c.exchange_rate_source,
+                exchange_rate_timestamp = c.exchange_rate_timestamp,
+                conversion_method = c.conversion_method,
+                updated_at = datetime('now')
+            FROM transaction_conversions c
+            WHERE c.transaction_id = transactions.id AND c.currency = ?2
+            AND transactions.profile_id = ?1
+            AND (transactions.amount_primary IS NOT c.amount
+                OR transactions.primary_currency IS NOT c.currency
+                OR transactions.conversion_method IS NOT c.conversion_method)
+            "#,
+        )
+        .bind(profile_id)
+        .bind(&primary_currency)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to update primary currency amounts")?;
+        tx.commit().await?;
+
+        Ok(ConversionReport {
+            profile_id: profile_id.to_string(),
+            primary_currency,
+            currencies,
+            method,
+            converted: converted.len(),
+            unpriced,
+        })
+    }
+
+    /// Fill the price history over the dates of the pending transactions, so
+    /// each asset takes a few range requests instead of one per day. Failures
+    /// only cost the bulk fetch, the rates are still looked up per day.
+    async fn backfill_pending(
+        &self,
+        profile_id: &str,
+        currency: &str,
+        pending: &[PendingConversion],
+    ) {
+        let mut ranges: HashMap<&str, (NaiveDate, NaiveDate)> = HashMap::new();
+        for transaction in pending {
+            if transaction.token_symbol == currency {
+                continue;
+            }
+            let Ok(at) = parse_timestamp(&transaction.timestamp) else {
+                continue;
+            };
+            let date = at.date_naive();
+            ranges
+                .entry(&transaction.token_symbol)
+                .and_modify(|(first, last)| {
+                    *first = (*first).min(date);
+                    *last = (*last).max(date);
+                })
+                .or_insert((date, date));
+        }
+
+        for (asset, (first, last)) in ranges {
+            if let Err(e) = self
+                .backfill_price_history(profile_id, asset, currency, first, last)
+                .await
+            {
+                eprintln!(
+                    "Failed to backfill {}/{} prices from {} to {}: {:#}",
+                    asset, currency, first, last, e
+                );
+            }
+        }
+    }
+
+    /// Value of a transaction in `currency`, with the rate used. Rates and
+    /// failed lookups are kept in `rates` for the next transaction of the same
+    /// asset and day.
+    async fn price_transaction(
+        &self,
+        profile_id: &str,
+        currency: &str,
+        method: &ConversionMethod,
+        transaction: &PendingConversion,
+        rates: &mut HashMap<(String, Option<NaiveDate>), Result<AppliedRate, String>>,
+    ) -> Result<(Decimal, AppliedRate)> {
+        let amount = TokenAmount::from_units(&transaction.value, transaction.token_decimals)?;
+        if transaction.token_symbol == currency {
+            let rate = AppliedRate {
+                rate: Decimal::ONE,
+                source: None,
+                timestamp: transaction.timestamp.clone(),
+            };
+            return Ok((amount.to_decimal()?, rate));
+        }
+
+        let day = match method {
+            ConversionMethod::Spot => None,
+            _ => Some(parse_timestamp(&transaction.timestamp)?.date_naive()),
+        };
+        let key = (transaction.token_symbol.clone(), day);
+        if !rates.contains_key(&key) {
+            let at = day.map(|day| day.and_hms_opt(0, 0, 0).unwrap().and_utc());
+            let rate = self
+                .applied_rate(profile_id, &transaction.token_symbol, currency, method, at)
+                .await
+                .map_err(|e| format!("{:#}", e));
+            rates.insert(key.clone(), rate);
+        }
+
+        let rate = rates[&key].clone().map_err(anyhow::Error::msg)?;
+        let amount = amount.mul_rate(rate.rate)?;
+        Ok((amount, rate))
+    }
+
     /// Update transaction with currency conversion
     pub async fn update_transaction_conversion(
         &self,
@@ -279,12 +1028,311 @@ impl CurrencyService {
         Ok(transactions)
     }
 
-    /// Clean up stale exchange rates
+    /// Conversions of a transaction, one per reporting currency
+    pub async fn get_transaction_conversions(
+        &self,
+        transaction_id: &str,
+    ) -> Result<Vec<TransactionConversion>> {
+        sqlx::query_as::<_, TransactionConversion>(
+            "SELECT * FROM transaction_conversions WHERE transaction_id = ? ORDER BY currency",
+        )
+        .bind(transaction_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to fetch transaction conversions")
+    }
+
+    /// Conversions of a profile's transactions to `currency`, by transaction id
+    pub async fn get_conversions_to(
+        &self,
+        profile_id: &str,
+        currency: &str,
+    ) -> Result<HashMap<String, TransactionConversion>> {
+        let conversions = sqlx::query_as::<_, TransactionConversion>(
+            r#"
+            SELECT c.* FROM transaction_conversions c
+            JOIN transactions t ON t.id = c.transaction_id
+            WHERE t.profile_id = ? AND c.currency = ?
+            "#,
+        )
+        .bind(profile_id)
+        .bind(currency)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to fetch transaction conversions")?;
+
+        Ok(conversions
+            .into_iter()
+            .map(|conversion| (conversion.transaction_id.clone(), conversion))
+            .collect())
+    }
+
+    /// Manual rate book of a profile, by pair and period
+    pub async fn list_manual_rates(&self, profile_id: &str) -> Result<Vec<ManualRate>> {
+        let rates = sqlx::query_as::<_, ManualRate>(
+            "SELECT * FROM manual_rates WHERE profile_id = ?
+             ORDER BY from_currency, to_currency, valid_from",
+        )
+        .bind(profile_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to fetch manual rates")?;
+
+        Ok(rates)
+    }
+
+    /// Manual rate for the pair that is valid on `date`
+    /// Manual rate valid on `date` and its rate from `from_currency` to
+    /// `to_currency`, inverting an entry for the opposite pair
+    pub async fn get_manual_rate(
+        &self,
+        profile_id: &str,
+        from_currency: &str,
+        to_currency: &str,
+        date: NaiveDate,
+    ) -> Result<Option<(ManualRate, Decimal)>> {
+        find_manual_rate(&self.pool, profile_id, from_currency, to_currency, date).await
+    }
+
+    pub async fn add_manual_rate(
+        &self,
+        profile_id: &str,
+        rate: &NewManualRate,
+    ) -> Result<ManualRate> {
+        let mut tx = self.pool.begin().await?;
+        let id = insert_manual_rate(&mut tx, profile_id, rate).await?;
+        tx.commit().await?;
+
+        self.get_manual_rate_by_id(&id).await
+    }
+
+    /// Import rates from CSV with the columns `from_currency`, `to_currency`,
+    /// `rate`, `valid_from`, `valid_to` and `note`. Nothing is imported if any
+    /// row is invalid.
+    pub async fn import_manual_rates(&self, profile_id: &str, csv: &str) -> Result<usize> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(csv.as_bytes());
+        let mut tx = self.pool.begin().await?;
+
+        let mut imported = 0;
+        for (i, row) in reader.deserialize::<NewManualRate>().enumerate() {
+            // Line 1 is the header
+            let line = i + 2;
+            let rate = row.map_err(|e| anyhow::anyhow!("Line {}: {}", line, e))?;
+            insert_manual_rate(&mut tx, profile_id, &rate)
+                .await
+                .map_err(|e| anyhow::anyhow!("Line {}: {:#}", line, e))?;
+            imported += 1;
+        }
+
+        tx.commit().await?;
+        Ok(imported)
+    }
+
+    pub async fn delete_manual_rate(&self, profile_id: &str, id: &str) -> Result<()> {
+        let deleted = sqlx::query("DELETE FROM manual_rates WHERE id = ? AND profile_id = ?")
+            .bind(id)
+            .bind(profile_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete manual rate")?
+            .rows_affected();
+        if deleted == 0 {
+            anyhow::bail!("Manual rate {} not found", id);
+        }
+
+        Ok(())
+    }
+
+    async fn get_manual_rate_by_id(&self, id: &str) -> Result<ManualRate> {
+        sqlx::query_as::<_, ManualRate>("SELECT * FROM manual_rates WHERE id = ?")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to fetch manual rate")
+    }
+
+    /// Stored daily prices of `asset` in `fiat` between two dates, inclusive
+    pub async fn get_price_history(
+        &self,
+        asset: &str,
+        fiat: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<DailyPrice>> {
+        sqlx::query_as::<_, DailyPrice>(
+            r#"
+            SELECT * FROM price_history
+            WHERE asset = ? AND fiat = ? AND date BETWEEN ? AND ?
+            ORDER BY date
+            "#,
+        )
+        .bind(asset)
+        .bind(fiat)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to fetch price history")
+    }
+
+    /// Fill the price history of `asset` in `fiat` between two dates from
+    /// CoinGecko's range endpoint, a year of prices per request. Stored days
+    /// and today, which has no close yet, are skipped, as are assets without
+    /// a CoinGecko coin. Returns the number of days added.
+    pub async fn backfill_price_history(
+        &self,
+        profile_id: &str,
+        asset: &str,
+        fiat: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<usize> {
+        let asset_currency = self
+            .get_currency(asset)
+            .await?
+            .with_context(|| format!("Unknown currency {}", asset))?;
+        self.get_currency(fiat)
+            .await?
+            .with_context(|| format!("Unknown currency {}", fiat))?;
+        let Some(coin_id) = asset_currency.coingecko_id else {
+            return Ok(0);
+        };
+
+        let yesterday = Utc::now().date_naive() - Duration::days(1);
+        let stored: HashSet<NaiveDate> = self
+            .get_price_history(asset, fiat, from, to)
+            .await?
+            .into_iter()
+            .map(|price| price.date)
+            .collect();
+        let missing: Vec<NaiveDate> = from
+            .iter_days()
+            .take_while(|date| *date <= to.min(yesterday))
+            .filter(|date| !stored.contains(date))
+            .collect();
+        let (Some(&first), Some(&last)) = (missing.first(), missing.last()) else {
+            return Ok(0);
+        };
+
+        let client = self.coingecko_client(self.price_feed_keys(profile_id).await?.coingecko);
+        let midnight = |date: NaiveDate| date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let mut days = BTreeMap::new();
+        let mut start = first;
+        while start <= last {
+            let end = (start + Duration::days(PRICE_HISTORY_CHUNK_DAYS - 1)).min(last);
+            // The price at midnight after `end` is the close of `end`
+            let prices = client
+                .get_price_range(
+                    &coin_id,
+                    fiat,
+                    midnight(start),
+                    midnight(end + Duration::days(1)),
+                )
+                .await?;
+            days.extend(daily_prices(prices, start, end)?);
+            start = end + Duration::days(1);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let mut added = 0;
+        for (date, (open, close, count)) in &days {
+            if stored.contains(date) {
+                continue;
+            }
+            sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO price_history (asset, fiat, date, open, close, source)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(asset)
+            .bind(fiat)
+            .bind(date)
+            .bind((*count > 1).then(|| open.normalize().to_string()))
+            .bind(close.normalize().to_string())
+            .bind(ExchangeRateSource::CoinGecko.to_string())
+            .execute(&mut *tx)
+            .await
+            .context("Failed to save price history")?;
+            added += 1;
+        }
+        tx.commit().await?;
+
+        Ok(added)
+    }
+
+    /// Close on `date` from the price history as a rate, read either way round.
+    /// The rate is not stored, its metadata references the price instead.
+    async fn get_price_history_rate(
+        &self,
+        from_currency: &str,
+        to_currency: &str,
+        date: NaiveDate,
+    ) -> Result<Option<ExchangeRate>> {
+        let price = sqlx::query_as::<_, DailyPrice>(
+            r#"
+            SELECT * FROM price_history
+            WHERE date = ?1
+            AND ((asset = ?2 AND fiat = ?3) OR (asset = ?3 AND fiat = ?2))
+            "#,
+        )
+        .bind(date)
+        .bind(from_currency)
+        .bind(to_currency)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to fetch price history")?;
+        let Some(price) = price else {
+            return Ok(None);
+        };
+
+        let close = Decimal::from_str(&price.close).context("Failed to parse price")?;
+        let rate = if price.asset == from_currency {
+            close
+        } else if close.is_zero() {
+            return Ok(None);
+        } else {
+            Decimal::ONE / close
+        };
+        Ok(Some(ExchangeRate {
+            id: format!("price_history:{}:{}:{}", price.asset, price.fiat, date),
+            from_currency: from_currency.to_string(),
+            to_currency: to_currency.to_string(),
+            rate: rate.normalize().to_string(),
+            timestamp: date
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                .format(SQLITE_DATETIME)
+                .to_string(),
+            source: price.source,
+            ttl_seconds: 0,
+            metadata: Some(
+                serde_json::json!({
+                    "reference": {
+                        "asset": price.asset,
+                        "fiat": price.fiat,
+                        "date": date,
+                        "source": "price_history",
+                    }
+                })
+                .to_string(),
+            ),
+            is_historical: true,
+            created_at: price.created_at,
+            updated_at: price.updated_at,
+        }))
+    }
+
+    /// Clean up stale exchange rates. Historical rates and the price history
+    /// are kept.
     pub async fn cleanup_stale_rates(&self) -> Result<u64> {
         let result = sqlx::query(
             r#"
             DELETE FROM exchange_rates
-            WHERE (unixepoch('now') - unixepoch(timestamp)) > ttl_seconds
+            WHERE is_historical = 0
+            AND (unixepoch('now') - unixepoch(timestamp)) > ttl_seconds
             "#,
         )
         .execute(&self.pool)
@@ -295,9 +1343,1014 @@ impl CurrencyService {
     }
 }
 
+/// Rate applied to a conversion and where it came from
+#[derive(Debug, Clone)]
+struct AppliedRate {
+    rate: Decimal,
+    /// `None` when no conversion was needed
+    source: Option<ExchangeRateSource>,
+    /// When the rate was in effect, or the first day of a manual rate
+    timestamp: String,
+}
+
+#[derive(FromRow)]
+struct PendingConversion {
+    id: String,
+    value: String,
+    token_symbol: String,
+    token_decimals: i32,
+    timestamp: String,
+}
+
+/// Transaction `convert_transactions` could not price in `currency`
+#[derive(Debug, Clone, Serialize)]
+pub struct UnpricedTransaction {
+    pub transaction_id: String,
+    pub currency: String,
+    pub token_symbol: String,
+    pub timestamp: String,
+    pub reason: String,
+}
+
+/// Event carrying the report of a conversion run started in the background
+pub const CONVERSION_EVENT: &str = "currency://conversions";
+
+/// Outcome of converting a profile's transactions to its reporting currencies
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversionReport {
+    pub profile_id: String,
+    pub primary_currency: String,
+    /// Primary currency first
+    pub currencies: Vec<String>,
+    pub method: ConversionMethod,
+    /// Conversions done, one per transaction and currency
+    pub converted: usize,
+    pub unpriced: Vec<UnpricedTransaction>,
+}
+
+/// Check a manual rate and insert it, refusing periods that overlap another
+/// rate for the same pair
+async fn insert_manual_rate(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    profile_id: &str,
+    rate: &NewManualRate,
+) -> Result<String> {
+    let value = Decimal::from_str(rate.rate.trim())
+        .with_context(|| format!("Invalid rate {}", rate.rate))?;
+    if value <= Decimal::ZERO {
+        anyhow::bail!("Rate must be positive");
+    }
+    if rate.from_currency == rate.to_currency {
+        anyhow::bail!("A rate needs two different currencies");
+    }
+    if rate
+        .valid_to
+        .is_some_and(|valid_to| valid_to < rate.valid_from)
+    {
+        anyhow::bail!("Rate period ends before it starts");
+    }
+
+    let known: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM currencies WHERE code IN (?, ?)")
+        .bind(&rate.from_currency)
+        .bind(&rate.to_currency)
+        .fetch_one(&mut **tx)
+        .await
+        .context("Failed to check currencies")?;
+    if known < 2 {
+        anyhow::bail!(
+            "Unknown currency in {}/{}",
+            rate.from_currency,
+            rate.to_currency
+        );
+    }
+
+    let overlapping: Option<NaiveDate> = sqlx::query_scalar(
+        r#"
+        SELECT valid_from FROM manual_rates
+        WHERE profile_id = ?1 AND from_currency = ?2 AND to_currency = ?3
+        AND (?5 IS NULL OR valid_from <= ?5)
+        AND (valid_to IS NULL OR valid_to >= ?4)
+        LIMIT 1
+        "#,
+    )
+    .bind(profile_id)
+    .bind(&rate.from_currency)
+    .bind(&rate.to_currency)
+    .bind(rate.valid_from)
+    .bind(rate.valid_to)
+    .fetch_optional(&mut **tx)
+    .await
+    .context("Failed to check for overlapping manual rates")?;
+    if let Some(valid_from) = overlapping {
+        anyhow::bail!(
+            "{}/{} already has a manual rate valid from {} in this period",
+            rate.from_currency,
+            rate.to_currency,
+            valid_from
+        );
+    }
+
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"
+        INSERT INTO manual_rates (
+            id, profile_id, from_currency, to_currency, rate, valid_from, valid_to, note
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&id)
+    .bind(profile_id)
+    .bind(&rate.from_currency)
+    .bind(&rate.to_currency)
+    .bind(value.normalize().to_string())
+    .bind(rate.valid_from)
+    .bind(rate.valid_to)
+    .bind(rate.note.as_deref().filter(|note| !note.is_empty()))
+    .execute(&mut **tx)
+    .await
+    .context("Failed to save manual rate")?;
+
+    Ok(id)
+}
+
+/// The `price_history` row a rate was read from
+fn price_history_reference(rate: &ExchangeRate) -> Option<serde_json::Value> {
+    let metadata: serde_json::Value = serde_json::from_str(rate.metadata.as_deref()?).ok()?;
+    let reference = metadata.get("reference")?;
+    (reference["source"] == "price_history").then(|| reference.clone())
+}
+
+/// First and last price of each day in `start..=end`, and how many there were.
+/// CoinGecko charts are hourly for ranges up to 90 days and daily beyond, with
+/// daily prices stamped 00:00 UTC, i.e. the close of the day before. A price at
+/// exactly midnight closes the previous day in both shapes.
+fn daily_prices(
+    mut prices: Vec<(DateTime<Utc>, String)>,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<BTreeMap<NaiveDate, (Decimal, Decimal, usize)>> {
+    prices.sort_by_key(|(at, _)| *at);
+
+    let mut days: BTreeMap<NaiveDate, (Decimal, Decimal, usize)> = BTreeMap::new();
+    for (at, price) in prices {
+        let date = if at.time() == NaiveTime::MIN {
+            at.date_naive() - Duration::days(1)
+        } else {
+            at.date_naive()
+        };
+        if date < start || date > end {
+            continue;
+        }
+        let price = Decimal::from_str(&price)
+            .with_context(|| format!("Invalid price {} from CoinGecko", price))?;
+        days.entry(date)
+            .and_modify(|(_, close, count)| {
+                *close = price;
+                *count += 1;
+            })
+            .or_insert((price, price, 1));
+    }
+
+    Ok(days)
+}
+
+/// Format SQLite's `datetime()` produces
+const SQLITE_DATETIME: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Accepts RFC 3339, SQLite datetimes and plain dates (as midnight UTC)
+fn parse_timestamp(timestamp: &str) -> Result<DateTime<Utc>> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(timestamp) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    if let Ok(at) = NaiveDateTime::parse_from_str(timestamp, SQLITE_DATETIME) {
+        return Ok(at.and_utc());
+    }
+    NaiveDate::parse_from_str(timestamp, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+        .with_context(|| format!("Invalid timestamp {}", timestamp))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::substrate_currency::SubstrateCurrencyHandler;
+    use crate::db::test_pool;
+
+    #[tokio::test]
+    async fn test_single_currency_registry() {
+        let pool = test_pool().await;
+        let service = CurrencyService::new(pool.clone());
+
+        let dot = service.get_currency("DOT").await.unwrap().unwrap();
+        assert!(dot.is_substrate);
+        assert_eq!(dot.chain_id.as_deref(), Some("polkadot"));
+        let usd = service.get_currency("USD").await.unwrap().unwrap();
+        assert!(!usd.is_substrate);
+
+        let substrate: Vec<String> = service
+            .get_substrate_currencies()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|c| c.code)
+            .collect();
+        assert_eq!(substrate, vec!["ASTR", "BNC", "DOT", "GLMR", "KSM", "iBTC"]);
+
+        let ksm = SubstrateCurrencyHandler::new(pool.clone())
+            .native_currency("kusama")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ksm.code, "KSM");
+
+        let leftovers: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM sqlite_master
+             WHERE name IN ('currencies_mvp', 'exchange_rates_mvp', 'account_currency_settings')",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[tokio::test]
+    async fn test_price_feeds_run_without_keys_while_locked() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO profiles (id, name) VALUES ('p1', 'Test')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let secrets = Arc::new(SecretStore::new());
+        let service = CurrencyService::with_secrets(pool.clone(), secrets.clone());
+
+        secrets.unlock(&pool, "passphrase").await.unwrap();
+        service
+            .set_api_key("p1", PriceFeed::Fixer, Some("fixer-key"))
+            .await
+            .unwrap();
+        secrets.lock().await;
 
-    // Add tests here when ready
+        let keys = service.price_feed_keys("p1").await.unwrap();
+        assert_eq!(keys.fixer, None);
+
+        secrets.unlock(&pool, "passphrase").await.unwrap();
+        let keys = service.price_feed_keys("p1").await.unwrap();
+        assert_eq!(keys.fixer.as_deref(), Some("fixer-key"));
+        assert_eq!(keys.coingecko, None);
+    }
+
+    #[tokio::test]
+    async fn test_historical_conversion_uses_nearest_rate() {
+        let pool = test_pool().await;
+        // The example rates seeded by early migrations are gone
+        let seeded: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM exchange_rates WHERE id LIKE '%-initial' OR id LIKE '%-init'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(seeded, 0);
+
+        sqlx::query(
+            "INSERT INTO exchange_rates
+                 (id, from_currency, to_currency, rate, timestamp, source, ttl_seconds, is_historical)
+             VALUES
+                 ('d1', 'DOT', 'USD', '6.0', '2024-03-01 00:00:00', 'coingecko', 0, 1),
+                 ('d2', 'DOT', 'USD', '7.0', '2024-03-02 00:00:00', 'coingecko', 0, 1),
+                 ('s1', 'DOT', 'USD', '9.0', '2024-03-01 20:00:00', 'coingecko', 300, 0),
+                 ('e1', 'EUR', 'GBP', '0.85', '2024-03-01 00:00:00', 'fixer', 0, 1)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let service = CurrencyService::new(pool.clone());
+
+        let historical = Some(ConversionMethod::Historical);
+        let conversion = service
+            .convert(
+                "p1",
+                "DOT",
+                "USD",
+                "2",
+                Some("2024-03-01T20:00:00Z"),
+                historical.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(conversion.converted_amount, "14.0");
+        assert_eq!(conversion.source, Some(ExchangeRateSource::CoinGecko));
+        assert_eq!(
+            conversion.rate_timestamp.as_deref(),
+            Some("2024-03-02 00:00:00")
+        );
+
+        // Outside the tolerance the rate has to be fetched, and without a
+        // Fixer key nothing quotes fiat pairs
+        let service = service.with_historical_tolerance(Duration::hours(1));
+        let err = service
+            .convert(
+                "p1",
+                "EUR",
+                "GBP",
+                "1",
+                Some("2024-03-01 12:00:00"),
+                historical.clone(),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("No price provider"), "{}", err);
+        assert!(service
+            .convert("p1", "DOT", "USD", "1", None, historical)
+            .await
+            .is_err());
+
+        // Historical rates are never spot rates and never expire
+        assert!(service
+            .get_cached_exchange_rate("EUR", "GBP")
+            .await
+            .unwrap()
+            .is_none());
+        service.cleanup_stale_rates().await.unwrap();
+        let kept: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM exchange_rates WHERE is_historical = 1")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(kept, 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetched_rates_fall_back_and_are_cached() {
+        let pool = test_pool().await;
+        sqlx::query("DELETE FROM exchange_rates")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let spot = server
+            .mock("GET", "/simple/price")
+            .match_query(mockito::Matcher::Any)
+            .with_status(429)
+            .expect(1)
+            .create_async()
+            .await;
+        let history = server
+            .mock("GET", "/coins/polkadot/history")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "date".into(),
+                "01-03-2024".into(),
+            ))
+            .with_body(r#"{"market_data":{"current_price":{"usd":6.25,"eur":5.75}}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        sqlx::query("INSERT INTO profiles (id, name) VALUES ('p1', 'Test')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let service =
+            CurrencyService::new(pool.clone()).with_price_providers(PriceProviderConfig {
+                coingecko_url: Some(server.url()),
+                ..PriceProviderConfig::default()
+            });
+        let manual_rate = |from: &str, to: &str, rate: &str| NewManualRate {
+            from_currency: from.to_string(),
+            to_currency: to.to_string(),
+            rate: rate.to_string(),
+            valid_from: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+            valid_to: None,
+            note: None,
+        };
+        service
+            .add_manual_rate("p1", &manual_rate("DOT", "USD", "7"))
+            .await
+            .unwrap();
+
+        // CoinGecko is rate limited, so the rate book answers and is cached
+        for _ in 0..2 {
+            let conversion = service
+                .convert("p1", "DOT", "USD", "2", None, Some(ConversionMethod::Spot))
+                .await
+                .unwrap();
+            assert_eq!(conversion.converted_amount, "14");
+            assert_eq!(conversion.source, Some(ExchangeRateSource::Manual));
+        }
+        spot.assert_async().await;
+
+        // Historical rates come from CoinGecko, also for fiat to coin
+        let conversion = service
+            .convert(
+                "p1",
+                "USD",
+                "DOT",
+                "12.5",
+                Some("2024-03-01T09:30:00Z"),
+                Some(ConversionMethod::Historical),
+            )
+            .await
+            .unwrap();
+        assert_eq!(conversion.converted_amount, "2.000");
+        assert_eq!(conversion.source, Some(ExchangeRateSource::CoinGecko));
+        assert_eq!(
+            conversion.rate_timestamp.as_deref(),
+            Some("2024-03-01 00:00:00")
+        );
+        let stored = service
+            .get_nearest_exchange_rate("USD", "DOT", parse_timestamp("2024-03-01").unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(stored.is_historical);
+        assert_eq!(stored.rate, "0.16");
+        history.assert_async().await;
+
+        // A profile that puts its rate book first never reaches the network
+        sqlx::query(
+            "INSERT INTO account_settings (id, profile_id, price_provider_priority)
+             VALUES ('s1', 'p1', 'manual,coingecko')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        service
+            .add_manual_rate("p1", &manual_rate("USD", "KSM", "0.04"))
+            .await
+            .unwrap();
+        let rate = service.get_spot_rate("p1", "KSM", "USD").await.unwrap();
+        assert_eq!(rate.rate, "25");
+        assert_eq!(rate.ttl_seconds, CRYPTO_RATE_TTL);
+    }
+
+    #[tokio::test]
+    async fn test_compound_rate_through_pivot() {
+        let pool = test_pool().await;
+        sqlx::query(
+            "INSERT INTO exchange_rates
+                 (id, from_currency, to_currency, rate, timestamp, source, ttl_seconds, is_historical)
+             VALUES
+                 ('dot-chf', 'DOT', 'CHF', '6.5', datetime('now', '-2 minutes'), 'coingecko', 300, 0),
+                 ('chf-cad', 'CHF', 'CAD', '1.5', datetime('now'), 'fixer', 86400, 0),
+                 ('h-dot-usd', 'DOT', 'USD', '7.25', '2024-03-01 00:00:00', 'coingecko', 0, 1),
+                 ('h-usd-inr', 'USD', 'INR', '82.9', '2024-03-01 00:00:00', 'fixer', 0, 1)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let service = CurrencyService::new(pool.clone())
+            .with_price_providers(PriceProviderConfig {
+                priority: vec![],
+                ..PriceProviderConfig::default()
+            })
+            .with_pivot_currencies(vec!["EUR".to_string(), "CHF".to_string()]);
+
+        let rate = service.get_spot_rate("p1", "DOT", "CAD").await.unwrap();
+        assert_eq!(rate.source, ExchangeRateSource::Compound);
+        assert_eq!(rate.rate, "9.75");
+        assert_eq!(rate.ttl_seconds, 300);
+        let metadata: serde_json::Value =
+            serde_json::from_str(rate.metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(metadata["pivot"], "CHF");
+        assert_eq!(metadata["components"][0]["id"], "dot-chf");
+        assert_eq!(metadata["components"][1]["id"], "chf-cad");
+        assert_eq!(
+            metadata["components"][0]["timestamp"].as_str(),
+            Some(rate.timestamp.as_str())
+        );
+        let cached = service
+            .get_cached_exchange_rate("DOT", "CAD")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(cached.id, rate.id);
+
+        // Historical rates compound through the default pivot and are kept
+        let service = service.with_pivot_currencies(vec!["USD".to_string()]);
+        let conversion = service
+            .convert(
+                "p1",
+                "DOT",
+                "INR",
+                "2",
+                Some("2024-03-01T15:00:00Z"),
+                Some(ConversionMethod::Historical),
+            )
+            .await
+            .unwrap();
+        assert_eq!(conversion.exchange_rate, "601.025");
+        assert_eq!(conversion.source, Some(ExchangeRateSource::Compound));
+        assert_eq!(
+            conversion.rate_timestamp.as_deref(),
+            Some("2024-03-01 00:00:00")
+        );
+
+        // Without a path the error is the one for the direct rate
+        let err = service.get_spot_rate("p1", "KSM", "CHF").await.unwrap_err();
+        assert!(err.to_string().contains("No price provider"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_fixed_conversion_uses_manual_rate_book() {
+        let pool = test_pool().await;
+        sqlx::query(
+            "INSERT INTO profiles (id, name) VALUES ('p1', 'Foundation');
+             INSERT INTO account_settings (id, profile_id, primary_currency, conversion_method)
+             VALUES ('s1', 'p1', 'CHF', 'fixed');
+             INSERT INTO exchange_rates
+                 (id, from_currency, to_currency, rate, timestamp, source, ttl_seconds)
+             VALUES ('dot-usd-spot', 'DOT', 'USD', '7.50', datetime('now'), 'coingecko', 300);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let service = CurrencyService::new(pool.clone());
+
+        let january = NewManualRate {
+            from_currency: "DOT".to_string(),
+            to_currency: "USD".to_string(),
+            rate: "6.80".to_string(),
+            valid_from: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            valid_to: NaiveDate::from_ymd_opt(2024, 1, 31),
+            note: Some("Board minutes 2023-12".to_string()),
+        };
+        service.add_manual_rate("p1", &january).await.unwrap();
+        let overlap = NewManualRate {
+            rate: "7".to_string(),
+            valid_from: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            valid_to: None,
+            ..january.clone()
+        };
+        assert!(service.add_manual_rate("p1", &overlap).await.is_err());
+
+        let imported = service
+            .import_manual_rates(
+                "p1",
+                "from_currency,to_currency,rate,valid_from,valid_to,note\n\
+                 DOT,USD,7.123456789012345678,2024-02-01,2024-02-29,\n\
+                 USD,CHF,0.88,2024-01-01,,open ended\n",
+            )
+            .await
+            .unwrap();
+        assert_eq!(imported, 2);
+        // One bad row rejects the whole file
+        let err = service
+            .import_manual_rates(
+                "p1",
+                "from_currency,to_currency,rate,valid_from,valid_to,note\n\
+                 KSM,USD,30,2024-01-01,,\n\
+                 KSM,USD,-1,2024-02-01,,\n",
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Line 3"), "{}", err);
+        assert_eq!(service.list_manual_rates("p1").await.unwrap().len(), 3);
+
+        let february = service
+            .convert("p1", "DOT", "USD", "10", Some("2024-02-29T23:00:00Z"), None)
+            .await
+            .unwrap();
+        assert_eq!(february.exchange_rate, "7.123456789012345678");
+        assert_eq!(february.source, Some(ExchangeRateSource::Manual));
+        assert_eq!(february.rate_timestamp.as_deref(), Some("2024-02-01"));
+        // Entered as USD/CHF, answers CHF/USD too
+        let inverse = service
+            .convert("p1", "CHF", "USD", "10", Some("2024-02-10"), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            inverse.exchange_rate,
+            (Decimal::ONE / Decimal::from_str("0.88").unwrap()).to_string()
+        );
+
+        // The cached market rate for DOT/USD is never used instead
+        let err = service
+            .convert("p1", "DOT", "USD", "10", Some("2024-03-01"), None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("No manual rate"), "{}", err);
+        let spot = service
+            .convert("p1", "DOT", "USD", "10", None, Some(ConversionMethod::Spot))
+            .await
+            .unwrap();
+        assert_eq!(spot.source, Some(ExchangeRateSource::CoinGecko));
+    }
+
+    #[tokio::test]
+    async fn test_convert_transactions_batches_and_reruns() {
+        let pool = test_pool().await;
+        sqlx::query(
+            "INSERT INTO profiles (id, name) VALUES ('p1', 'Foundation');
+             INSERT INTO account_settings (id, profile_id, primary_currency, conversion_method)
+             VALUES ('s1', 'p1', 'USD', 'historical');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        for (id, symbol, value, timestamp) in [
+            ("t1", "DOT", "10000000000", "2024-03-05T09:00:00+00:00"),
+            ("t2", "DOT", "25000000000", "2024-03-05T21:00:00+00:00"),
+            ("t3", "DOT", "10000000000", "2024-03-07T12:00:00+00:00"),
+            ("t4", "USD", "1250", "2024-03-07T12:00:00+00:00"),
+        ] {
+            sqlx::query(
+                "INSERT INTO transactions (id, profile_id, chain, hash, block_number, timestamp,
+                     from_address, value, token_symbol, token_decimals, transaction_type, status,
+                     metadata)
+                 VALUES (?, 'p1', 'polkadot', ?, 1, ?, 'a', ?, ?, ?, 'transfer', 'success', '{}')",
+            )
+            .bind(id)
+            .bind(id)
+            .bind(timestamp)
+            .bind(value)
+            .bind(symbol)
+            .bind(if symbol == "USD" { 2 } else { 10 })
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        // One request for the two DOT transactions of March 5th, none for USD,
+        // and March 7th is not available
+        let mut server = mockito::Server::new_async().await;
+        let history = server
+            .mock("GET", "/coins/polkadot/history")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "date".into(),
+                "05-03-2024".into(),
+            ))
+            .with_body(r#"{"market_data":{"current_price":{"usd":8.5}}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let service =
+            CurrencyService::new(pool.clone()).with_price_providers(PriceProviderConfig {
+                priority: vec![ProviderKind::CoinGecko],
+                coingecko_url: Some(server.url()),
+                ..PriceProviderConfig::default()
+            });
+
+        let report = service.convert_transactions("p1").await.unwrap();
+        history.assert_async().await;
+        assert_eq!(report.converted, 3);
+        assert_eq!(report.unpriced.len(), 1);
+        assert_eq!(report.unpriced[0].transaction_id, "t3");
+        let amounts: Vec<(String, Option<String>, Option<String>)> = sqlx::query_as(
+            "SELECT id, amount_primary, exchange_rate_source FROM transactions ORDER BY id",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            amounts,
+            vec![
+                ("t1".into(), Some("8.5".into()), Some("coingecko".into())),
+                ("t2".into(), Some("21.25".into()), Some("coingecko".into())),
+                ("t3".into(), None, None),
+                ("t4".into(), Some("12.5".into()), None),
+            ]
+        );
+
+        // Converted rows are left alone on the next run
+        let report = service.convert_transactions("p1").await.unwrap();
+        assert_eq!((report.converted, report.unpriced.len()), (0, 1));
+
+        // A new primary currency and method make every conversion stale
+        let mut settings = service.get_account_settings("p1").await.unwrap().unwrap();
+        assert!(!service.update_account_settings(&settings).await.unwrap());
+        // The provider order is saved normalized and leaves conversions alone
+        settings.price_provider_priority = Some("fixer, manual".to_string());
+        assert!(!service.update_account_settings(&settings).await.unwrap());
+        let saved = service.get_account_settings("p1").await.unwrap().unwrap();
+        assert_eq!(
+            saved.price_provider_priority.as_deref(),
+            Some("fixer,manual")
+        );
+        settings.price_provider_priority = Some("binance".to_string());
+        assert!(service.update_account_settings(&settings).await.is_err());
+        settings.price_provider_priority = None;
+        settings.primary_currency = "EUR".to_string();
+        settings.conversion_method = ConversionMethod::Fixed;
+        assert!(service.update_account_settings(&settings).await.unwrap());
+        service
+            .add_manual_rate(
+                "p1",
+                &NewManualRate {
+                    from_currency: "DOT".to_string(),
+                    to_currency: "EUR".to_string(),
+                    rate: "6".to_string(),
+                    valid_from: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+                    valid_to: None,
+                    note: None,
+                },
+            )
+            .await
+            .unwrap();
+        let report = service.convert_transactions("p1").await.unwrap();
+        assert_eq!(report.primary_currency, "EUR");
+        assert_eq!(report.converted, 3);
+        assert_eq!(report.unpriced[0].token_symbol, "USD");
+        assert!(report.unpriced[0].reason.contains("No manual rate"));
+    }
+
+    #[tokio::test]
+    async fn test_conversions_for_every_reporting_currency() {
+        let pool = test_pool().await;
+        sqlx::query(
+            "INSERT INTO profiles (id, name) VALUES ('p1', 'Stiftung');
+             INSERT INTO account_settings
+                 (id, profile_id, primary_currency, reporting_currencies, conversion_method)
+             VALUES ('s1', 'p1', 'CHF', 'USD', 'fixed');
+             INSERT INTO transactions (id, profile_id, chain, hash, block_number, timestamp,
+                 from_address, value, token_symbol, token_decimals, transaction_type, status,
+                 metadata)
+             VALUES
+                 ('t1', 'p1', 'polkadot', 'h1', 1, '2024-03-05T09:00:00+00:00', 'a',
+                  '20000000000', 'DOT', 10, 'transfer', 'success', '{}'),
+                 ('t2', 'p1', 'polkadot', 'h2', 2, '2024-03-06T09:00:00+00:00', 'a',
+                  '500', 'USD', 2, 'transfer', 'success', '{}');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let service = CurrencyService::new(pool.clone());
+        let valid_from = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        for (from, to, rate) in [
+            ("DOT", "CHF", "6"),
+            ("DOT", "USD", "7"),
+            ("USD", "CHF", "0.9"),
+        ] {
+            service
+                .add_manual_rate(
+                    "p1",
+                    &NewManualRate {
+                        from_currency: from.to_string(),
+                        to_currency: to.to_string(),
+                        rate: rate.to_string(),
+                        valid_from,
+                        valid_to: None,
+                        note: None,
+                    },
+                )
+                .await
+                .unwrap();
+        }
+
+        let report = service.convert_transactions("p1").await.unwrap();
+        assert_eq!(report.currencies, vec!["CHF", "USD"]);
+        assert_eq!((report.converted, report.unpriced.len()), (4, 0));
+        let amounts = |currency: &'static str| {
+            let service = &service;
+            async move {
+                let mut amounts: Vec<(String, String)> = service
+                    .get_conversions_to("p1", currency)
+                    .await
+                    .unwrap()
+                    .into_values()
+                    .map(|c| (c.transaction_id, c.amount))
+                    .collect();
+                amounts.sort();
+                amounts
+            }
+        };
+        assert_eq!(
+            amounts("CHF").await,
+            vec![("t1".into(), "12".into()), ("t2".into(), "4.5".into())]
+        );
+        assert_eq!(
+            amounts("USD").await,
+            vec![("t1".into(), "14".into()), ("t2".into(), "5".into())]
+        );
+        let primary: Vec<(Option<String>, Option<String>)> =
+            sqlx::query_as("SELECT amount_primary, primary_currency FROM transactions ORDER BY id")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            primary,
+            vec![
+                (Some("12".into()), Some("CHF".into())),
+                (Some("4.5".into()), Some("CHF".into())),
+            ]
+        );
+        let t2 = service.get_transaction_conversions("t2").await.unwrap();
+        assert_eq!(t2[1].currency, "USD");
+        assert_eq!(t2[1].exchange_rate_source, None);
+
+        // Swapping a reporting currency drops its conversions and converts
+        // the new one
+        let mut settings = service.get_account_settings("p1").await.unwrap().unwrap();
+        settings.set_reporting_currencies(vec!["EUR".to_string()]);
+        assert!(service.update_account_settings(&settings).await.unwrap());
+        let report = service.convert_transactions("p1").await.unwrap();
+        assert_eq!(report.converted, 0);
+        assert_eq!(report.unpriced.len(), 2);
+        assert!(report.unpriced.iter().all(|u| u.currency == "EUR"));
+        assert!(amounts("USD").await.is_empty());
+        assert_eq!(amounts("CHF").await.len(), 2);
+    }
+
+    #[test]
+    fn test_daily_prices_from_hourly_and_daily_charts() {
+        let date = |day: u32| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+        let at = |day: u32, hour: u32, minute: u32| {
+            date(day).and_hms_opt(hour, minute, 0).unwrap().and_utc()
+        };
+        let price = |at: DateTime<Utc>, price: &str| (at, price.to_string());
+        let decimal = |price: &str| Decimal::from_str(price).unwrap();
+
+        // Up to 90 days: hourly prices during each day
+        let hourly = daily_prices(
+            vec![
+                price(at(2, 0, 4), "6.1"),
+                price(at(1, 23, 4), "6.0"),
+                price(at(1, 0, 4), "5.0"),
+                price(at(1, 12, 4), "5.5"),
+            ],
+            date(1),
+            date(1),
+        )
+        .unwrap();
+        assert_eq!(
+            hourly.into_iter().collect::<Vec<_>>(),
+            vec![(date(1), (decimal("5.0"), decimal("6.0"), 3))]
+        );
+
+        // Beyond 90 days: one price per day at midnight, closing the day before
+        let daily = daily_prices(
+            vec![
+                price(at(1, 0, 0), "4.0"),
+                price(at(2, 0, 0), "5.0"),
+                price(at(3, 0, 0), "6.0"),
+            ],
+            date(1),
+            date(2),
+        )
+        .unwrap();
+        assert_eq!(
+            daily.into_iter().collect::<Vec<_>>(),
+            vec![
+                (date(1), (decimal("5.0"), decimal("5.0"), 1)),
+                (date(2), (decimal("6.0"), decimal("6.0"), 1)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_price_history_backfills_ranges_and_is_kept() {
+        let pool = test_pool().await;
+        sqlx::query("DELETE FROM exchange_rates")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let midnight = |day: u32| {
+            NaiveDate::from_ymd_opt(2024, 3, day)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                .and_utc()
+                .timestamp()
+        };
+        let mut server = mockito::Server::new_async().await;
+        let range = server
+            .mock("GET", "/coins/polkadot/market_chart/range")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("vs_currency".into(), "usd".into()),
+                mockito::Matcher::UrlEncoded("from".into(), midnight(1).to_string()),
+                mockito::Matcher::UrlEncoded("to".into(), midnight(4).to_string()),
+            ]))
+            .with_body(format!(
+                r#"{{"prices":[[{},6.5],[{},6.0],[{},5.5],[{},7.0]],"market_caps":[],"total_volumes":[]}}"#,
+                (midnight(1) + 43_200) * 1000,
+                (midnight(1) + 3_600) * 1000,
+                midnight(1) * 1000,
+                midnight(3) * 1000,
+            ))
+            .expect(1)
+            .create_async()
+            .await;
+        // The second run only asks for the day still missing
+        let missing = server
+            .mock("GET", "/coins/polkadot/market_chart/range")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "from".into(),
+                midnight(3).to_string(),
+            ))
+            .with_body(r#"{"prices":[]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let service =
+            CurrencyService::new(pool.clone()).with_price_providers(PriceProviderConfig {
+                priority: vec![ProviderKind::CoinGecko],
+                coingecko_url: Some(server.url()),
+                ..PriceProviderConfig::default()
+            });
+
+        let first = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let third = NaiveDate::from_ymd_opt(2024, 3, 3).unwrap();
+        // March 1st has hourly prices, the midnight price closes February 29th
+        // and March 3rd's closes March 2nd
+        let added = service
+            .backfill_price_history("p1", "DOT", "USD", first, third)
+            .await
+            .unwrap();
+        assert_eq!(added, 2);
+        let added = service
+            .backfill_price_history("p1", "DOT", "USD", first, third)
+            .await
+            .unwrap();
+        assert_eq!(added, 0);
+        range.assert_async().await;
+        missing.assert_async().await;
+
+        let history: Vec<(Option<String>, String)> = service
+            .get_price_history("DOT", "USD", first, third)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|price| (price.open, price.close))
+            .collect();
+        assert_eq!(
+            history,
+            vec![(Some("6".into()), "6.5".into()), (None, "7".into())]
+        );
+
+        // Historical conversion reads the close without asking CoinGecko
+        let historical = Some(ConversionMethod::Historical);
+        let dot = service
+            .convert(
+                "p1",
+                "DOT",
+                "USD",
+                "2",
+                Some("2024-03-02T15:00:00Z"),
+                historical.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(dot.converted_amount, "14");
+        let usd = service
+            .convert(
+                "p1",
+                "USD",
+                "DOT",
+                "13",
+                Some("2024-03-01T15:00:00Z"),
+                historical,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            Decimal::from_str(&usd.converted_amount)
+                .unwrap()
+                .round_dp(20),
+            Decimal::from(2)
+        );
+
+        // A compound rate references the price it was built from
+        sqlx::query(
+            "INSERT INTO exchange_rates
+                 (id, from_currency, to_currency, rate, timestamp, source, ttl_seconds, is_historical)
+             VALUES ('usd-eur', 'USD', 'EUR', '0.5', '2024-03-02 00:00:00', 'fixer', 0, 1)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let eur = service
+            .convert(
+                "p1",
+                "DOT",
+                "EUR",
+                "2",
+                Some("2024-03-02T15:00:00Z"),
+                Some(ConversionMethod::Historical),
+            )
+            .await
+            .unwrap();
+        assert_eq!(eur.converted_amount, "7.0");
+        let compound = service
+            .get_nearest_exchange_rate("DOT", "EUR", parse_timestamp("2024-03-02").unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        let metadata: serde_json::Value =
+            serde_json::from_str(compound.metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(
+            metadata["components"][0]["reference"],
+            serde_json::json!({
+                "asset": "DOT",
+                "fiat": "USD",
+                "date": "2024-03-02",
+                "source": "price_history",
+            })
+        );
+        assert_eq!(metadata["components"][1]["id"], "usd-eur");
+
+        service.cleanup_stale_rates().await.unwrap();
+        let kept: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM price_history")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(kept, 2);
+    }
 }
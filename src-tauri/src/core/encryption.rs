@@ -1,43 +1,309 @@
 #![allow(dead_code)]
 
+//! Password-based AES-256-GCM envelopes.
+//!
+//! Layout, all integers little-endian:
+//!
+//! | bytes | field                                   |
+//! |-------|-----------------------------------------|
+//! | 4     | magic `PENC`                            |
+//! | 1     | format version                          |
+//! | 1     | KDF id, `1` = Argon2id                  |
+//! | 12    | KDF memory (KiB), iterations, lanes     |
+//! | 16    | salt                                    |
+//! | 12    | nonce                                   |
+//! | rest  | ciphertext and tag                      |
+//!
+//! Everything before the ciphertext is authenticated as associated data.
+//! Data without the magic is the legacy format: SHA-256 of the password as key
+//! and a fixed nonce. It can still be decrypted so callers can re-encrypt it.
+
 use aes_gcm::{
-    aead::{Aead, KeyInit},
-    Aes256Gcm, Nonce,
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
 };
 use anyhow::Result;
+use argon2::{Algorithm, Argon2, Params, Version};
 use sha2::{Digest, Sha256};
 
+const MAGIC: &[u8; 4] = b"PENC";
+const FORMAT_VERSION: u8 = 1;
+const KDF_ARGON2ID: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = 4 + 1 + 1 + 12 + SALT_LEN + NONCE_LEN;
+
+/// Argon2id cost, stored in every envelope so it can be raised later
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub lanes: u32,
+}
+
+impl Default for KdfParams {
+    /// OWASP's minimum recommendation for Argon2id
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            lanes: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    /// Reject costs no envelope of ours would carry, so a crafted header
+    /// cannot make us allocate gigabytes
+    fn check(&self) -> Result<()> {
+        if self.memory_kib > 1024 * 1024 || self.iterations > 64 || self.lanes > 16 {
+            anyhow::bail!("Unsupported key derivation parameters {:?}", self);
+        }
+        Ok(())
+    }
+}
+
 pub struct Encryptor {
+    password: String,
+    params: KdfParams,
+    salt: [u8; SALT_LEN],
     cipher: Aes256Gcm,
 }
 
 impl Encryptor {
     pub fn new(password: &str) -> Result<Self> {
-        // Derive key from password using SHA256
-        let mut hasher = Sha256::new();
-        hasher.update(password.as_bytes());
-        let key = hasher.finalize();
+        Self::with_params(password, KdfParams::default())
+    }
 
-        let cipher = Aes256Gcm::new(&key);
+    /// Derive the key for new envelopes once, under a fresh random salt
+    pub fn with_params(password: &str, params: KdfParams) -> Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = derive_cipher(password, &params, &salt)?;
 
-        Ok(Self { cipher })
+        Ok(Self {
+            password: password.to_string(),
+            params,
+            salt,
+            cipher,
+        })
     }
 
     pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
-        let nonce = Nonce::from_slice(b"unique nonce"); // Use random nonce in production
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        self.seal(&nonce, data)
+    }
+
+    /// Open an envelope, or legacy data encrypted under the same password
+    pub fn decrypt(&self, encrypted_data: &[u8]) -> Result<Vec<u8>> {
+        if is_legacy(encrypted_data) {
+            return decrypt_legacy(&self.password, encrypted_data);
+        }
+
+        let header = Header::parse(encrypted_data)?;
+        let derived;
+        let cipher = if header.params == self.params && header.salt == self.salt {
+            &self.cipher
+        } else {
+            derived = derive_cipher(&self.password, &header.params, &header.salt)?;
+            &derived
+        };
+
+        cipher
+            .decrypt(
+                Nonce::from_slice(&header.nonce),
+                Payload {
+                    msg: &encrypted_data[HEADER_LEN..],
+                    aad: &encrypted_data[..HEADER_LEN],
+                },
+            )
+            .map_err(|_| anyhow::anyhow!("Decryption failed: wrong password or tampered data"))
+    }
+
+    /// Re-encrypt data in the current format, whatever format it is in now
+    pub fn upgrade(&self, encrypted_data: &[u8]) -> Result<Vec<u8>> {
+        self.encrypt(&self.decrypt(encrypted_data)?)
+    }
+
+    fn seal(
+        &self,
+        nonce: &Nonce<<Aes256Gcm as AeadCore>::NonceSize>,
+        data: &[u8],
+    ) -> Result<Vec<u8>> {
+        let header = Header {
+            params: self.params,
+            salt: self.salt,
+            nonce: nonce.as_slice().try_into()?,
+        }
+        .to_bytes();
+
         let ciphertext = self
             .cipher
-            .encrypt(nonce, data)
+            .encrypt(
+                nonce,
+                Payload {
+                    msg: data,
+                    aad: &header,
+                },
+            )
             .map_err(|e| anyhow::anyhow!("Encryption failed: {}", e))?;
-        Ok(ciphertext)
+
+        let mut envelope = header;
+        envelope.extend_from_slice(&ciphertext);
+        Ok(envelope)
     }
+}
 
-    pub fn decrypt(&self, encrypted_data: &[u8]) -> Result<Vec<u8>> {
-        let nonce = Nonce::from_slice(b"unique nonce");
-        let plaintext = self
-            .cipher
-            .decrypt(nonce, encrypted_data)
-            .map_err(|e| anyhow::anyhow!("Decryption failed: {}", e))?;
-        Ok(plaintext)
+/// True for data written before envelopes were introduced
+pub fn is_legacy(data: &[u8]) -> bool {
+    !data.starts_with(MAGIC)
+}
+
+/// Decrypt the legacy format: SHA-256 of the password as key, fixed nonce
+pub fn decrypt_legacy(password: &str, encrypted_data: &[u8]) -> Result<Vec<u8>> {
+    let key = Sha256::digest(password.as_bytes());
+    Aes256Gcm::new(&key)
+        .decrypt(Nonce::from_slice(b"unique nonce"), encrypted_data)
+        .map_err(|e| anyhow::anyhow!("Decryption failed: {}", e))
+}
+
+struct Header {
+    params: KdfParams,
+    salt: [u8; SALT_LEN],
+    nonce: [u8; NONCE_LEN],
+}
+
+impl Header {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(FORMAT_VERSION);
+        bytes.push(KDF_ARGON2ID);
+        bytes.extend_from_slice(&self.params.memory_kib.to_le_bytes());
+        bytes.extend_from_slice(&self.params.iterations.to_le_bytes());
+        bytes.extend_from_slice(&self.params.lanes.to_le_bytes());
+        bytes.extend_from_slice(&self.salt);
+        bytes.extend_from_slice(&self.nonce);
+        bytes
+    }
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < HEADER_LEN {
+            anyhow::bail!("Encrypted data is truncated");
+        }
+        if data[4] != FORMAT_VERSION {
+            anyhow::bail!("Unsupported encryption format version {}", data[4]);
+        }
+        if data[5] != KDF_ARGON2ID {
+            anyhow::bail!("Unsupported key derivation function {}", data[5]);
+        }
+
+        let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let params = KdfParams {
+            memory_kib: u32_at(6),
+            iterations: u32_at(10),
+            lanes: u32_at(14),
+        };
+        params.check()?;
+
+        Ok(Self {
+            params,
+            salt: data[18..18 + SALT_LEN].try_into()?,
+            nonce: data[18 + SALT_LEN..HEADER_LEN].try_into()?,
+        })
+    }
+}
+
+fn derive_cipher(password: &str, params: &KdfParams, salt: &[u8]) -> Result<Aes256Gcm> {
+    let params = Params::new(params.memory_kib, params.iterations, params.lanes, Some(32))
+        .map_err(|e| anyhow::anyhow!("Invalid key derivation parameters: {}", e))?;
+
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Key derivation failed: {}", e))?;
+
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap parameters, the format does not depend on the cost
+    const FAST: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        lanes: 1,
+    };
+
+    fn fast(password: &str) -> Encryptor {
+        Encryptor::with_params(password, FAST).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let encryptor = fast("correct horse");
+        let first = encryptor.encrypt(b"api key").unwrap();
+        let second = encryptor.encrypt(b"api key").unwrap();
+
+        // Fresh nonce per message
+        assert_ne!(first, second);
+        assert_eq!(encryptor.decrypt(&first).unwrap(), b"api key");
+        assert_eq!(encryptor.decrypt(&second).unwrap(), b"api key");
+
+        // Another instance derives the key from the envelope's salt
+        let other = fast("correct horse");
+        assert_eq!(other.decrypt(&first).unwrap(), b"api key");
+    }
+
+    #[test]
+    fn test_known_envelope() {
+        // Password "pacioli", salt 0x00..0f, nonce 0xa0..ab, plaintext "hello"
+        let envelope = hex::decode(concat!(
+            "50454e43010140000000010000000100",
+            "0000000102030405060708090a0b0c0d",
+            "0e0fa0a1a2a3a4a5a6a7a8a9aaab",
+            "c8fabae36b645370f62d927e1e4a1e75fdb24bc615",
+        ))
+        .unwrap();
+        assert_eq!(fast("pacioli").decrypt(&envelope).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_tampering_and_wrong_password() {
+        let encryptor = fast("correct horse");
+        let envelope = encryptor.encrypt(b"api key").unwrap();
+
+        assert!(fast("battery staple").decrypt(&envelope).is_err());
+
+        // Any flipped bit, header or ciphertext, fails authentication
+        for at in [10, 20, HEADER_LEN - 1, HEADER_LEN, envelope.len() - 1] {
+            let mut tampered = envelope.clone();
+            tampered[at] ^= 1;
+            assert!(encryptor.decrypt(&tampered).is_err(), "byte {}", at);
+        }
+
+        assert!(encryptor.decrypt(&envelope[..HEADER_LEN + 4]).is_err());
+
+        let mut expensive = envelope.clone();
+        expensive[6..10].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(encryptor.decrypt(&expensive).is_err());
+    }
+
+    #[test]
+    fn test_upgrade_legacy() {
+        let key = Sha256::digest(b"correct horse");
+        let legacy = Aes256Gcm::new(&key)
+            .encrypt(Nonce::from_slice(b"unique nonce"), b"api key".as_ref())
+            .unwrap();
+        assert!(is_legacy(&legacy));
+
+        let encryptor = fast("correct horse");
+        assert_eq!(encryptor.decrypt(&legacy).unwrap(), b"api key");
+
+        let upgraded = encryptor.upgrade(&legacy).unwrap();
+        assert!(!is_legacy(&upgraded));
+        assert_eq!(encryptor.decrypt(&upgraded).unwrap(), b"api key");
     }
 }
@@ -1,13 +1,14 @@
-mod address;
+pub mod address;
+pub mod amount;
 pub mod currency;
 pub mod currency_service;
-mod encryption;
+pub mod encryption;
+pub mod secrets;
 pub mod substrate_currency;
 
+use amount::TokenAmount;
 use chrono::{DateTime, Utc};
-use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use std::str::FromStr;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -18,6 +19,8 @@ pub struct Transaction {
     pub profile_id: Option<String>,
     pub chain: String,
     pub hash: String,
+    /// Log or event index within the parent transaction, -1 for the transaction itself
+    pub log_index: i64,
     pub from_address: String,
     pub to_address: Option<String>,
     pub value: String,
@@ -29,19 +32,30 @@ pub struct Transaction {
     pub status: String,
     pub fee: Option<String>,
     pub metadata: serde_json::Value,
+    /// Value converted to `primary_currency`, set by the currency service
+    #[sqlx(default)]
+    pub amount_primary: Option<String>,
+    #[sqlx(default)]
+    pub primary_currency: Option<String>,
+    /// Rate per whole token used to compute `amount_primary`
+    #[sqlx(default)]
+    pub exchange_rate: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
 impl Transaction {
-    #[allow(dead_code)]
-    pub fn value_decimal(&self) -> Result<Decimal, rust_decimal::Error> {
-        Decimal::from_str(&self.value)
+    /// Value in the token's smallest unit, with its decimals
+    pub fn amount(&self) -> anyhow::Result<TokenAmount> {
+        TokenAmount::from_units(&self.value, self.token_decimals)
     }
 
-    #[allow(dead_code)]
-    pub fn fee_decimal(&self) -> Result<Option<Decimal>, rust_decimal::Error> {
-        self.fee.as_ref().map(|f| Decimal::from_str(f)).transpose()
+    /// Fee in the same token as the value
+    pub fn fee_amount(&self) -> anyhow::Result<Option<TokenAmount>> {
+        self.fee
+            .as_deref()
+            .map(|fee| TokenAmount::from_units(fee, self.token_decimals))
+            .transpose()
     }
 }
 
@@ -54,10 +68,22 @@ pub struct Token {
     pub contract_address: Option<String>,
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+    pub avatar_url: Option<String>,
+    /// Archived profiles are hidden but keep their data
+    pub archived_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Account {
+    #[sqlx(try_from = "String")]
     pub id: Uuid,
+    pub profile_id: String,
     pub address: String,
     pub chain: String,
     pub nickname: Option<String>,
@@ -71,7 +97,7 @@ pub struct Balance {
     pub account_id: Uuid,
     pub token_symbol: String,
     pub chain: String,
-    pub amount: Decimal,
+    pub amount: TokenAmount,
     pub updated_at: DateTime<Utc>,
 }
 
@@ -94,4 +120,14 @@ pub struct SyncStatus {
     pub current_block: i64,
     pub is_syncing: bool,
     pub progress: f64,
+    /// Synced data up to this block is past the chain's finalized head
+    pub finalized_block: i64,
+}
+
+/// A block's position in the chain, as seen by the node we are connected to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockRef {
+    pub number: u64,
+    pub hash: String,
+    pub parent_hash: String,
 }
@@ -0,0 +1,360 @@
+#![allow(dead_code)]
+
+use super::encryption::Encryptor;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{FromRow, Pool, Sqlite};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Names of the secrets kept per profile
+pub const COINGECKO_API_KEY: &str = "coingecko_api_key";
+pub const FIXER_API_KEY: &str = "fixer_api_key";
+
+/// Known plaintext that tells whether a passphrase opens the store
+const VERIFIER: &[u8] = b"pacioli secret store";
+
+/// Secrets such as API keys, encrypted at rest in the `secrets` table.
+///
+/// The store starts locked. `unlock` sets the passphrase on first use and
+/// checks it afterwards; until then secrets can be neither read nor written.
+#[derive(Default)]
+pub struct SecretStore {
+    encryptor: RwLock<Option<Arc<Encryptor>>>,
+    /// Secrets decrypted since the last unlock, keyed by profile and name.
+    /// Every envelope has its own salt, so each decryption derives a key.
+    decrypted: RwLock<HashMap<(String, String), String>>,
+}
+
+/// Whether the store has a passphrase yet and whether it is open
+#[derive(Debug, Clone, Serialize)]
+pub struct SecretStoreStatus {
+    pub initialized: bool,
+    pub unlocked: bool,
+}
+
+impl SecretStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn is_unlocked(&self) -> bool {
+        self.encryptor.read().await.is_some()
+    }
+
+    pub async fn status(&self, pool: &Pool<Sqlite>) -> Result<SecretStoreStatus> {
+        Ok(SecretStoreStatus {
+            initialized: load_verifier(pool).await?.is_some(),
+            unlocked: self.is_unlocked().await,
+        })
+    }
+
+    pub async fn lock(&self) {
+        *self.encryptor.write().await = None;
+        self.decrypted.write().await.clear();
+    }
+
+    /// Open the store, and move any plaintext API keys left in
+    /// `account_settings` into it
+    pub async fn unlock(&self, pool: &Pool<Sqlite>, passphrase: &str) -> Result<()> {
+        let encryptor = Encryptor::new(passphrase)?;
+
+        match load_verifier(pool).await? {
+            Some(verifier) => {
+                encryptor
+                    .decrypt(&verifier)
+                    .map_err(|_| anyhow::anyhow!("Wrong passphrase for the secret store"))?;
+            }
+            None => save_verifier(pool, &encryptor.encrypt(VERIFIER)?).await?,
+        }
+
+        move_plaintext_keys(pool, &encryptor).await?;
+        *self.encryptor.write().await = Some(Arc::new(encryptor));
+        self.decrypted.write().await.clear();
+
+        Ok(())
+    }
+
+    /// Store a secret, or remove it when `value` is `None`
+    pub async fn set(
+        &self,
+        pool: &Pool<Sqlite>,
+        profile_id: &str,
+        name: &str,
+        value: Option<&str>,
+    ) -> Result<()> {
+        let key = (profile_id.to_string(), name.to_string());
+        let Some(value) = value.filter(|v| !v.is_empty()) else {
+            sqlx::query("DELETE FROM secrets WHERE profile_id = ? AND name = ?")
+                .bind(profile_id)
+                .bind(name)
+                .execute(pool)
+                .await
+                .context("Failed to delete secret")?;
+            self.decrypted.write().await.remove(&key);
+            return Ok(());
+        };
+
+        let ciphertext = self.encryptor().await?.encrypt(value.as_bytes())?;
+        save_secret(pool, profile_id, name, &ciphertext).await?;
+        self.decrypted.write().await.insert(key, value.to_string());
+
+        Ok(())
+    }
+
+    pub async fn get(
+        &self,
+        pool: &Pool<Sqlite>,
+        profile_id: &str,
+        name: &str,
+    ) -> Result<Option<String>> {
+        let ciphertext: Option<Vec<u8>> =
+            sqlx::query_scalar("SELECT ciphertext FROM secrets WHERE profile_id = ? AND name = ?")
+                .bind(profile_id)
+                .bind(name)
+                .fetch_optional(pool)
+                .await
+                .context("Failed to fetch secret")?;
+        let Some(ciphertext) = ciphertext else {
+            return Ok(None);
+        };
+
+        let encryptor = self.encryptor().await?;
+        let key = (profile_id.to_string(), name.to_string());
+        if let Some(value) = self.decrypted.read().await.get(&key) {
+            return Ok(Some(value.clone()));
+        }
+
+        let plaintext = encryptor
+            .decrypt(&ciphertext)
+            .with_context(|| format!("Failed to decrypt {}", name))?;
+        let value = String::from_utf8(plaintext)?;
+        self.decrypted.write().await.insert(key, value.clone());
+        Ok(Some(value))
+    }
+
+    /// Names of the secrets a profile has, without decrypting them
+    pub async fn names(&self, pool: &Pool<Sqlite>, profile_id: &str) -> Result<Vec<String>> {
+        let names =
+            sqlx::query_scalar("SELECT name FROM secrets WHERE profile_id = ? ORDER BY name")
+                .bind(profile_id)
+                .fetch_all(pool)
+                .await
+                .context("Failed to fetch secret names")?;
+
+        Ok(names)
+    }
+
+    /// Re-encrypt every stored secret under a new passphrase.
+    /// Returns the number of secrets rotated.
+    pub async fn rotate(
+        &self,
+        pool: &Pool<Sqlite>,
+        current_passphrase: &str,
+        new_passphrase: &str,
+    ) -> Result<usize> {
+        self.unlock(pool, current_passphrase).await?;
+        let current = self.encryptor().await?;
+        let next = Encryptor::new(new_passphrase)?;
+
+        let rows =
+            sqlx::query_as::<_, SecretRow>("SELECT profile_id, name, ciphertext FROM secrets")
+                .fetch_all(pool)
+                .await
+                .context("Failed to fetch secrets")?;
+
+        let mut tx = pool.begin().await?;
+        for row in &rows {
+            let ciphertext = next.encrypt(&current.decrypt(&row.ciphertext)?)?;
+            sqlx::query(
+                "UPDATE secrets SET ciphertext = ?, updated_at = datetime('now')
+                 WHERE profile_id = ? AND name = ?",
+            )
+            .bind(ciphertext)
+            .bind(&row.profile_id)
+            .bind(&row.name)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to save rotated secret")?;
+        }
+        sqlx::query(
+            "UPDATE secret_store SET verifier = ?, updated_at = datetime('now') WHERE id = 1",
+        )
+        .bind(next.encrypt(VERIFIER)?)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to save secret store verifier")?;
+        tx.commit().await?;
+
+        *self.encryptor.write().await = Some(Arc::new(next));
+        self.decrypted.write().await.clear();
+        Ok(rows.len())
+    }
+
+    async fn encryptor(&self) -> Result<Arc<Encryptor>> {
+        self.encryptor
+            .read()
+            .await
+            .clone()
+            .context("Secret store is locked")
+    }
+}
+
+#[derive(FromRow)]
+struct SecretRow {
+    profile_id: String,
+    name: String,
+    ciphertext: Vec<u8>,
+}
+
+#[derive(FromRow)]
+struct PlaintextKeys {
+    profile_id: String,
+    coingecko_api_key: Option<String>,
+    fixer_api_key: Option<String>,
+}
+
+async fn load_verifier(pool: &Pool<Sqlite>) -> Result<Option<Vec<u8>>> {
+    let verifier = sqlx::query_scalar("SELECT verifier FROM secret_store WHERE id = 1")
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch secret store verifier")?;
+
+    Ok(verifier)
+}
+
+async fn save_verifier(pool: &Pool<Sqlite>, verifier: &[u8]) -> Result<()> {
+    sqlx::query("INSERT INTO secret_store (id, verifier) VALUES (1, ?)")
+        .bind(verifier)
+        .execute(pool)
+        .await
+        .context("Failed to save secret store verifier")?;
+
+    Ok(())
+}
+
+async fn save_secret(
+    pool: &Pool<Sqlite>,
+    profile_id: &str,
+    name: &str,
+    ciphertext: &[u8],
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO secrets (profile_id, name, ciphertext)
+        VALUES (?, ?, ?)
+        ON CONFLICT(profile_id, name) DO UPDATE SET
+            ciphertext = excluded.ciphertext,
+            updated_at = datetime('now')
+        "#,
+    )
+    .bind(profile_id)
+    .bind(name)
+    .bind(ciphertext)
+    .execute(pool)
+    .await
+    .context("Failed to save secret")?;
+
+    Ok(())
+}
+
+/// API keys written before the store existed sit in plain text in `account_settings`
+async fn move_plaintext_keys(pool: &Pool<Sqlite>, encryptor: &Encryptor) -> Result<()> {
+    let rows = sqlx::query_as::<_, PlaintextKeys>(
+        "SELECT profile_id, coingecko_api_key, fixer_api_key FROM account_settings
+         WHERE coingecko_api_key IS NOT NULL OR fixer_api_key IS NOT NULL",
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch plaintext API keys")?;
+
+    for row in rows {
+        for (name, value) in [
+            (COINGECKO_API_KEY, &row.coingecko_api_key),
+            (FIXER_API_KEY, &row.fixer_api_key),
+        ] {
+            if let Some(value) = value.as_deref().filter(|v| !v.is_empty()) {
+                let ciphertext = encryptor.encrypt(value.as_bytes())?;
+                save_secret(pool, &row.profile_id, name, &ciphertext).await?;
+            }
+        }
+
+        sqlx::query(
+            "UPDATE account_settings SET coingecko_api_key = NULL, fixer_api_key = NULL
+             WHERE profile_id = ?",
+        )
+        .bind(&row.profile_id)
+        .execute(pool)
+        .await
+        .context("Failed to clear plaintext API keys")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+
+    #[tokio::test]
+    async fn test_unlock_store_and_rotate() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO profiles (id, name) VALUES ('p1', 'Test')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO account_settings (id, profile_id, coingecko_api_key)
+             VALUES ('s1', 'p1', 'CG-plain')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let store = SecretStore::new();
+        assert!(!store.status(&pool).await.unwrap().initialized);
+        assert!(store
+            .set(&pool, "p1", FIXER_API_KEY, Some("x"))
+            .await
+            .is_err());
+
+        // First unlock sets the passphrase and moves the plaintext key
+        store.unlock(&pool, "first").await.unwrap();
+        assert!(store.status(&pool).await.unwrap().unlocked);
+        let plain: Option<String> =
+            sqlx::query_scalar("SELECT coingecko_api_key FROM account_settings")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(plain, None);
+        assert_eq!(
+            store.get(&pool, "p1", COINGECKO_API_KEY).await.unwrap(),
+            Some("CG-plain".to_string())
+        );
+
+        store
+            .set(&pool, "p1", FIXER_API_KEY, Some("fixer-key"))
+            .await
+            .unwrap();
+        let stored: Vec<u8> = sqlx::query_scalar("SELECT ciphertext FROM secrets WHERE name = ?")
+            .bind(FIXER_API_KEY)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(!stored.windows(9).any(|w| w == b"fixer-key"));
+
+        store.lock().await;
+        assert!(store.get(&pool, "p1", FIXER_API_KEY).await.is_err());
+        assert!(store.unlock(&pool, "wrong").await.is_err());
+
+        assert_eq!(store.rotate(&pool, "first", "second").await.unwrap(), 2);
+        assert!(store.unlock(&pool, "first").await.is_err());
+        store.unlock(&pool, "second").await.unwrap();
+        assert_eq!(
+            store.get(&pool, "p1", FIXER_API_KEY).await.unwrap(),
+            Some("fixer-key".to_string())
+        );
+    }
+}
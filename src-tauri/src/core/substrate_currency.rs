@@ -1,10 +1,10 @@
 #![allow(dead_code)]
 
+use super::amount::TokenAmount;
+use super::currency::Currency;
 use anyhow::{Context, Result};
-use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Sqlite};
-use std::str::FromStr;
 
 /// Polkadot/Substrate currency handler
 /// Handles special requirements for Substrate chains:
@@ -67,25 +67,23 @@ impl SubstrateCurrencyHandler {
     /// Convert Substrate token amount from planck (smallest unit) to token units
     ///
     /// # Arguments
-    /// * `planck_amount` - Amount in planck (e.g., 10^18 for 1 DOT)
+    /// * `planck_amount` - Amount in planck (e.g., 10^10 for 1 DOT)
     /// * `decimals` - Token decimals (10 for DOT, 12 for KSM)
     ///
     /// # Returns
-    /// String representation with full precision
+    /// Exact string representation without trailing zeros
     ///
     /// # Example
-    /// ```
+    /// ```ignore
     /// // 1 DOT = 10^10 planck
-    /// let amount = convert_from_planck("10000000000", 10);
-    /// assert_eq!(amount, "1.0000000000");
+    /// let amount = SubstrateCurrencyHandler::convert_from_planck("15000000000", 10)?;
+    /// assert_eq!(amount, "1.5");
     /// ```
     pub fn convert_from_planck(planck_amount: &str, decimals: u8) -> Result<String> {
-        let planck = Decimal::from_str(planck_amount).context("Failed to parse planck amount")?;
-
-        let divisor = Decimal::from(10_u64.pow(decimals as u32));
-        let token_amount = planck / divisor;
+        let amount = TokenAmount::from_units(planck_amount, decimals as i32)
+            .context("Failed to parse planck amount")?;
 
-        Ok(token_amount.to_string())
+        Ok(amount.to_string())
     }
 
     /// Convert token amount to planck (smallest unit)
@@ -95,15 +93,13 @@ impl SubstrateCurrencyHandler {
     /// * `decimals` - Token decimals
     ///
     /// # Returns
-    /// String representation of planck amount
+    /// String representation of planck amount. Amounts more precise than
+    /// the token are an error.
     pub fn convert_to_planck(token_amount: &str, decimals: u8) -> Result<String> {
-        let amount = Decimal::from_str(token_amount).context("Failed to parse token amount")?;
-
-        let multiplier = Decimal::from(10_u64.pow(decimals as u32));
-        let planck = amount * multiplier;
+        let amount =
+            TokenAmount::parse(token_amount, decimals).context("Failed to parse token amount")?;
 
-        // Return as integer string (no decimals)
-        Ok(planck.floor().to_string())
+        Ok(amount.units().to_string())
     }
 
     /// Track XCM transfer
@@ -275,6 +271,19 @@ impl SubstrateCurrencyHandler {
         }))
     }
 
+    /// Native token of a Substrate chain, from the currency registry
+    pub async fn native_currency(&self, chain_id: &str) -> Result<Option<Currency>> {
+        let currency = sqlx::query_as::<_, Currency>(
+            "SELECT * FROM currencies WHERE is_substrate = 1 AND chain_id = ?",
+        )
+        .bind(chain_id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to fetch native currency")?;
+
+        Ok(currency)
+    }
+
     /// Validate Substrate address format
     /// SS58 address validation (simplified)
     pub fn validate_substrate_address(address: &str, _expected_prefix: u8) -> bool {
@@ -317,6 +326,10 @@ mod tests {
         let result = SubstrateCurrencyHandler::convert_from_planck("15000000000", 10);
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "1.5");
+
+        // 20 decimals overflowed 10_u64.pow
+        let result = SubstrateCurrencyHandler::convert_from_planck("123000000000000000000000", 20);
+        assert_eq!(result.unwrap(), "1230");
     }
 
     #[test]
@@ -330,6 +343,9 @@ mod tests {
         let result = SubstrateCurrencyHandler::convert_to_planck("1.5", 10);
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "15000000000");
+
+        // Below one planck
+        assert!(SubstrateCurrencyHandler::convert_to_planck("0.00000000001", 10).is_err());
     }
 
     #[test]
@@ -0,0 +1,488 @@
+#![allow(dead_code)]
+
+use super::DATABASE_FILE;
+use crate::core::encryption::{decrypt_legacy, Encryptor};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{Connection, Pool, Sqlite, SqliteConnection};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Layout version of the archive, bumped when entries or the manifest change
+pub const FORMAT_VERSION: u32 = 2;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "database.sqlite";
+const SETTINGS_PREFIX: &str = "settings/";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub created_at: DateTime<Utc>,
+    /// Latest migration applied to the snapshot
+    pub schema_version: i64,
+    /// Entries other than the manifest are encryption envelopes
+    #[serde(default)]
+    pub encrypted: bool,
+    /// Format 1 only: salt of the legacy per-entry keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_salt: Option<String>,
+    pub files: Vec<BackupFile>,
+}
+
+/// Archive entry with the size and SHA-256 of its plaintext
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupFile {
+    pub name: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Write a zip archive of the database and the settings files in `data_dir`.
+///
+/// The database is copied with `VACUUM INTO`, so the snapshot is consistent
+/// while the pool stays in use. With a passphrase every entry but the
+/// manifest is encrypted.
+pub async fn create_backup(
+    pool: &Pool<Sqlite>,
+    data_dir: &Path,
+    destination: &Path,
+    passphrase: Option<&str>,
+) -> Result<BackupManifest> {
+    let snapshot = data_dir.join(format!(".{}.snapshot", Uuid::new_v4().simple()));
+    let vacuum = sqlx::query("VACUUM INTO ?")
+        .bind(snapshot.to_string_lossy().to_string())
+        .execute(pool)
+        .await
+        .context("Failed to snapshot database");
+    let database = vacuum.and_then(|_| fs::read(&snapshot).context("Failed to read snapshot"));
+    let _ = fs::remove_file(&snapshot);
+
+    let mut entries = vec![(DATABASE_ENTRY.to_string(), database?)];
+    for (name, path) in settings_files(data_dir)? {
+        let data = fs::read(&path)
+            .with_context(|| format!("Failed to read settings file {}", path.display()))?;
+        entries.push((format!("{}{}", SETTINGS_PREFIX, name), data));
+    }
+
+    let manifest = BackupManifest {
+        format_version: FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now(),
+        schema_version: schema_version(pool).await?,
+        encrypted: passphrase.is_some(),
+        encryption_salt: None,
+        files: entries
+            .iter()
+            .map(|(name, data)| BackupFile {
+                name: name.clone(),
+                size: data.len() as u64,
+                sha256: checksum(data),
+            })
+            .collect(),
+    };
+
+    // Written next to the destination and renamed, so a failed backup leaves no archive
+    let partial = destination.with_extension("partial");
+    let written = write_archive(&partial, &manifest, &entries, passphrase);
+    if let Err(e) = written
+        .and_then(|_| fs::rename(&partial, destination).context("Failed to move backup into place"))
+    {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+
+    Ok(manifest)
+}
+
+/// Replace the database and settings in `data_dir` with the contents of an archive.
+///
+/// The archive is fully read and verified, and every file staged next to the
+/// one it replaces, before anything is touched. The pool is checkpointed and
+/// closed only then, so after an error before the swap the pool is still
+/// open; once it is closed the app has to reopen the database, whether the
+/// swap succeeded or not. The replaced database is kept as `<db>.rollback`.
+pub async fn restore_backup(
+    pool: &Pool<Sqlite>,
+    data_dir: &Path,
+    archive: &Path,
+    passphrase: Option<&str>,
+) -> Result<BackupManifest> {
+    let backup = read_backup(archive, passphrase)?;
+    check_schema_version(backup.manifest.schema_version, latest_schema_version())?;
+
+    let live = data_dir.join(DATABASE_FILE);
+    let mut staged = vec![(sidecar(&live, ".restore"), live.clone())];
+    let prepared = stage_restore(pool, data_dir, &backup, &mut staged).await;
+    if let Err(e) = prepared {
+        for (partial, _) in &staged {
+            let _ = fs::remove_file(partial);
+        }
+        return Err(e);
+    }
+    pool.close().await;
+
+    if let Err(e) = swap_database(&live, &staged[0].0) {
+        for (partial, _) in &staged {
+            let _ = fs::remove_file(partial);
+        }
+        return Err(e);
+    }
+    for (partial, path) in &staged[1..] {
+        fs::rename(partial, path)
+            .with_context(|| format!("Failed to restore settings file {}", path.display()))?;
+    }
+
+    Ok(backup.manifest)
+}
+
+/// Write the database and settings of `backup` to the sidecars in `staged`,
+/// adding one per settings file, and checkpoint the pool so no connection
+/// writes after the swap
+async fn stage_restore(
+    pool: &Pool<Sqlite>,
+    data_dir: &Path,
+    backup: &Backup,
+    staged: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<()> {
+    write_synced(&staged[0].0, &backup.database)?;
+    check_integrity(&staged[0].0).await?;
+
+    for (name, data) in &backup.settings {
+        let path = data_dir.join(name);
+        let partial = sidecar(&path, ".restore");
+        staged.push((partial.clone(), path));
+        write_synced(&partial, data)?;
+    }
+
+    // Fold the WAL into the main file
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+        .execute(pool)
+        .await
+        .context("Failed to checkpoint database")?;
+    Ok(())
+}
+
+/// Verified contents of an archive
+struct Backup {
+    manifest: BackupManifest,
+    database: Vec<u8>,
+    settings: Vec<(String, Vec<u8>)>,
+}
+
+fn read_backup(path: &Path, passphrase: Option<&str>) -> Result<Backup> {
+    let file = fs::File::open(path).context("Failed to open backup")?;
+    let mut archive = ZipArchive::new(file).context("Backup is not a zip archive")?;
+
+    let manifest: BackupManifest =
+        serde_json::from_slice(&read_entry(&mut archive, MANIFEST_ENTRY)?)
+            .context("Failed to parse backup manifest")?;
+    if manifest.format_version > FORMAT_VERSION {
+        anyhow::bail!(
+            "Backup format {} is newer than supported format {}",
+            manifest.format_version,
+            FORMAT_VERSION
+        );
+    }
+    let encrypted = manifest.encrypted || manifest.encryption_salt.is_some();
+    if encrypted && passphrase.is_none() {
+        anyhow::bail!("Backup is encrypted, a passphrase is required");
+    }
+    let encryptor = match passphrase {
+        Some(passphrase) if manifest.encrypted => Some(Encryptor::new(passphrase)?),
+        _ => None,
+    };
+
+    let mut database = None;
+    let mut settings = Vec::new();
+    for file in &manifest.files {
+        let mut data = read_entry(&mut archive, &file.name)?;
+        let decrypted = match (&encryptor, &manifest.encryption_salt, passphrase) {
+            (Some(encryptor), _, _) => Some(encryptor.decrypt(&data)),
+            // Format 1 derived a legacy key per entry
+            (None, Some(salt), Some(passphrase)) => Some(decrypt_legacy(
+                &format!("{}:{}:{}", salt, file.name, passphrase),
+                &data,
+            )),
+            _ => None,
+        };
+        if let Some(decrypted) = decrypted {
+            data = decrypted
+                .with_context(|| format!("Failed to decrypt {}, wrong passphrase?", file.name))?;
+        }
+        if data.len() as u64 != file.size || checksum(&data) != file.sha256 {
+            anyhow::bail!("Checksum mismatch for {}", file.name);
+        }
+
+        if file.name == DATABASE_ENTRY {
+            database = Some(data);
+        } else if let Some(name) = file.name.strip_prefix(SETTINGS_PREFIX) {
+            if !is_settings_file(name) {
+                anyhow::bail!("Unexpected settings entry {}", file.name);
+            }
+            settings.push((name.to_string(), data));
+        } else {
+            anyhow::bail!("Unexpected backup entry {}", file.name);
+        }
+    }
+
+    Ok(Backup {
+        database: database.context("Backup has no database")?,
+        manifest,
+        settings,
+    })
+}
+
+fn write_archive(
+    path: &Path,
+    manifest: &BackupManifest,
+    entries: &[(String, Vec<u8>)],
+    passphrase: Option<&str>,
+) -> Result<()> {
+    let encryptor = passphrase.map(Encryptor::new).transpose()?;
+    let file = fs::File::create(path).context("Failed to create backup file")?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file(MANIFEST_ENTRY, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+
+    for (name, data) in entries {
+        zip.start_file(name.as_str(), options)?;
+        match &encryptor {
+            Some(encryptor) => zip.write_all(&encryptor.encrypt(data)?)?,
+            None => zip.write_all(data)?,
+        }
+    }
+
+    zip.finish()?.sync_all().context("Failed to write backup")?;
+    Ok(())
+}
+
+fn read_entry(archive: &mut ZipArchive<fs::File>, name: &str) -> Result<Vec<u8>> {
+    let mut entry = archive
+        .by_name(name)
+        .with_context(|| format!("Backup is missing {}", name))?;
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Move the live database aside and the staged one into its place
+fn swap_database(live: &Path, staged: &Path) -> Result<()> {
+    let rollback = sidecar(live, ".rollback");
+    let wal_files = ["-wal", "-shm"];
+
+    if live.exists() {
+        fs::rename(live, &rollback).context("Failed to keep rollback copy of database")?;
+        for suffix in wal_files {
+            let _ = fs::remove_file(sidecar(&rollback, suffix));
+            // A leftover WAL would be replayed onto the restored database
+            if sidecar(live, suffix).exists() {
+                fs::rename(sidecar(live, suffix), sidecar(&rollback, suffix))?;
+            }
+        }
+    }
+
+    if let Err(e) = fs::rename(staged, live) {
+        if rollback.exists() {
+            fs::rename(&rollback, live).context("Failed to put back rollback copy")?;
+        }
+        return Err(e).context("Failed to move restored database into place");
+    }
+
+    Ok(())
+}
+
+async fn check_integrity(path: &Path) -> Result<()> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let mut conn = SqliteConnection::connect_with(&options)
+        .await
+        .context("Failed to open restored database")?;
+    let result: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&mut conn)
+        .await
+        .context("Failed to check restored database")?;
+    conn.close().await?;
+
+    if result != "ok" {
+        anyhow::bail!("Restored database is corrupt: {}", result);
+    }
+    Ok(())
+}
+
+fn check_schema_version(backup: i64, current: i64) -> Result<()> {
+    if backup > current {
+        anyhow::bail!(
+            "Backup schema version {} is newer than this app's {}, update the app first",
+            backup,
+            current
+        );
+    }
+    Ok(())
+}
+
+async fn schema_version(pool: &Pool<Sqlite>) -> Result<i64> {
+    let version: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+            .fetch_one(pool)
+            .await
+            .context("Failed to read schema version")?;
+    Ok(version.unwrap_or_default())
+}
+
+fn latest_schema_version() -> i64 {
+    sqlx::migrate!("./migrations")
+        .iter()
+        .map(|m| m.version)
+        .max()
+        .unwrap_or_default()
+}
+
+/// JSON files at the top of the data directory
+fn settings_files(data_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(data_dir).context("Failed to read data directory")? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_file() && is_settings_file(&name) {
+            files.push((name, entry.path()));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn is_settings_file(name: &str) -> bool {
+    name.ends_with(".json") && !name.starts_with('.') && !name.contains(['/', '\\'])
+}
+
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn write_synced(path: &Path, data: &[u8]) -> Result<()> {
+    let mut file =
+        fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn checksum(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn open(data_dir: &Path) -> Pool<Sqlite> {
+        let options = SqliteConnectOptions::new()
+            .filename(data_dir.join(DATABASE_FILE))
+            .create_if_missing(true)
+            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        pool
+    }
+
+    async fn profiles(pool: &Pool<Sqlite>) -> Vec<String> {
+        sqlx::query_scalar("SELECT name FROM profiles ORDER BY name")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_backup_roundtrip() {
+        let data_dir = std::env::temp_dir().join(format!("pacioli-{}", Uuid::new_v4()));
+        fs::create_dir_all(&data_dir).unwrap();
+        let archive = data_dir.join("backup.zip");
+
+        let pool = open(&data_dir).await;
+        sqlx::query("INSERT INTO profiles (id, name) VALUES ('1', 'Kept')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        fs::write(data_dir.join("settings.json"), br#"{"theme":"dark"}"#).unwrap();
+
+        let manifest = create_backup(&pool, &data_dir, &archive, Some("secret"))
+            .await
+            .unwrap();
+        assert_eq!(manifest.schema_version, latest_schema_version());
+        assert_eq!(manifest.files.len(), 2);
+
+        sqlx::query("INSERT INTO profiles (id, name) VALUES ('2', 'Dropped')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        fs::write(data_dir.join("settings.json"), br#"{"theme":"light"}"#).unwrap();
+
+        assert!(read_backup(&archive, None).is_err());
+        assert!(read_backup(&archive, Some("wrong")).is_err());
+
+        // A settings file that cannot be staged fails the restore with the
+        // pool still open and nothing replaced
+        let blocked = sidecar(&data_dir.join("settings.json"), ".restore");
+        fs::create_dir(&blocked).unwrap();
+        assert!(restore_backup(&pool, &data_dir, &archive, Some("secret"))
+            .await
+            .is_err());
+        assert!(!pool.is_closed());
+        assert_eq!(profiles(&pool).await, vec!["Dropped", "Kept"]);
+        assert!(!sidecar(&data_dir.join(DATABASE_FILE), ".restore").exists());
+        fs::remove_dir(&blocked).unwrap();
+
+        restore_backup(&pool, &data_dir, &archive, Some("secret"))
+            .await
+            .unwrap();
+        assert!(pool.is_closed());
+
+        let pool = open(&data_dir).await;
+        assert_eq!(profiles(&pool).await, vec!["Kept"]);
+        assert_eq!(
+            fs::read(data_dir.join("settings.json")).unwrap(),
+            br#"{"theme":"dark"}"#
+        );
+        pool.close().await;
+
+        // The replaced database is kept
+        let rollback = open_rollback(&data_dir).await;
+        assert_eq!(profiles(&rollback).await, vec!["Dropped", "Kept"]);
+        rollback.close().await;
+
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+
+    async fn open_rollback(data_dir: &Path) -> Pool<Sqlite> {
+        let options = SqliteConnectOptions::new()
+            .filename(sidecar(&data_dir.join(DATABASE_FILE), ".rollback"))
+            .read_only(true);
+        SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_refuses_newer_schema() {
+        assert!(check_schema_version(20250101000001, 20250101000012).is_ok());
+        assert!(check_schema_version(20250101000012, 20250101000012).is_ok());
+        assert!(check_schema_version(20990101000001, 20250101000012).is_err());
+    }
+}
@@ -1,5 +1,14 @@
-use anyhow::Result;
+pub mod backup;
+pub mod profiles;
+pub mod transactions;
+
+use anyhow::{Context, Result};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
 use sqlx::{Pool, Sqlite, SqlitePool};
+use std::path::Path;
+
+/// File name of the database in the app data directory
+pub const DATABASE_FILE: &str = "pacioli.db";
 
 pub struct Database {
     pub pool: Pool<Sqlite>,
@@ -16,108 +25,71 @@ impl Database {
         Ok(Self { pool })
     }
 
-    pub async fn get_transactions(
-        &self,
-        profile_id: &str,
-        start_date: Option<String>,
-        end_date: Option<String>,
-    ) -> Result<Vec<crate::core::Transaction>> {
-        let mut query = "SELECT * FROM transactions WHERE profile_id = ?".to_string();
+    /// Open the app database in `data_dir`, creating it on first start, and
+    /// bring its schema up to date
+    pub async fn open(data_dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(data_dir)
+            .with_context(|| format!("Failed to create {}", data_dir.display()))?;
+
+        let options = SqliteConnectOptions::new()
+            .filename(data_dir.join(DATABASE_FILE))
+            .create_if_missing(true)
+            .foreign_keys(true)
+            .journal_mode(SqliteJournalMode::Wal);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .context("Failed to open database")?;
+
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .context("Failed to run database migrations")?;
+
+        Ok(Self { pool })
+    }
+}
 
-        if let Some(start) = start_date {
-            query.push_str(&format!(" AND timestamp >= '{}'", start));
-        }
+/// Migrated in-memory database. A single connection, since every connection
+/// to `sqlite::memory:` opens a database of its own.
+#[cfg(test)]
+pub(crate) async fn test_pool() -> Pool<Sqlite> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    pool
+}
 
-        if let Some(end) = end_date {
-            query.push_str(&format!(" AND timestamp <= '{}'", end));
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        query.push_str(" ORDER BY timestamp DESC");
+    #[tokio::test]
+    async fn test_open_creates_and_migrates() {
+        let data_dir = std::env::temp_dir()
+            .join(format!("pacioli-{}", uuid::Uuid::new_v4()))
+            .join("nested");
 
-        let transactions = sqlx::query_as(&query)
-            .bind(profile_id)
-            .fetch_all(&self.pool)
-            .await?;
+        let db = Database::open(&data_dir).await.unwrap();
+        assert!(data_dir.join(DATABASE_FILE).exists());
+        sqlx::query("INSERT INTO profiles (id, name) VALUES ('p1', 'Test')")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        db.pool.close().await;
 
-        Ok(transactions)
-    }
+        // Opening again keeps the data and applies nothing twice
+        let db = Database::open(&data_dir).await.unwrap();
+        let profiles: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM profiles")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(profiles, 1);
+        db.pool.close().await;
 
-    pub async fn init_tables(&self) -> Result<()> {
-        // Create tables if they don't exist
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS profiles (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                avatar_url TEXT,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS accounts (
-                id TEXT PRIMARY KEY,
-                profile_id TEXT NOT NULL,
-                address TEXT NOT NULL,
-                chain TEXT NOT NULL,
-                nickname TEXT,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (profile_id) REFERENCES profiles (id)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS transactions (
-                id TEXT PRIMARY KEY,
-                profile_id TEXT NOT NULL,
-                chain TEXT NOT NULL,
-                hash TEXT NOT NULL,
-                block_number INTEGER NOT NULL,
-                timestamp DATETIME NOT NULL,
-                from_address TEXT NOT NULL,
-                to_address TEXT,
-                value TEXT NOT NULL,
-                token_symbol TEXT NOT NULL,
-                token_decimals INTEGER NOT NULL,
-                transaction_type TEXT NOT NULL,
-                status TEXT NOT NULL,
-                fee TEXT,
-                metadata TEXT,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                UNIQUE(hash, chain),
-                FOREIGN KEY (profile_id) REFERENCES profiles (id)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS sync_status (
-                profile_id TEXT NOT NULL,
-                chain TEXT NOT NULL,
-                last_synced_block INTEGER NOT NULL DEFAULT 0,
-                last_sync_time DATETIME DEFAULT CURRENT_TIMESTAMP,
-                PRIMARY KEY (profile_id, chain),
-                FOREIGN KEY (profile_id) REFERENCES profiles (id)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        Ok(())
+        std::fs::remove_dir_all(data_dir.parent().unwrap()).unwrap();
     }
 }
@@ -0,0 +1,404 @@
+use super::Database;
+use crate::core::address::validate_address;
+use crate::core::{Account, Profile};
+use anyhow::{Context, Result};
+use uuid::Uuid;
+
+/// Rows that belong to a profile, children before parents. Journal postings,
+/// tax lot matches, transaction tags, sync state, secrets and manual rates
+/// follow through `ON DELETE CASCADE`.
+const PROFILE_DATA: &[(&str, &str)] = &[
+    (
+        "xcm transfers",
+        "DELETE FROM xcm_transfers WHERE transaction_id IN
+         (SELECT id FROM transactions WHERE profile_id = ?)",
+    ),
+    (
+        "transaction conversions",
+        "DELETE FROM transaction_conversions WHERE transaction_id IN
+         (SELECT id FROM transactions WHERE profile_id = ?)",
+    ),
+    (
+        "journal entries",
+        "DELETE FROM journal_entries WHERE profile_id = ?",
+    ),
+    (
+        "ledger accounts",
+        "DELETE FROM ledger_accounts WHERE profile_id = ?",
+    ),
+    (
+        "tax disposals",
+        "DELETE FROM tax_disposals WHERE profile_id = ?",
+    ),
+    ("tax lots", "DELETE FROM tax_lots WHERE profile_id = ?"),
+    (
+        "transactions",
+        "DELETE FROM transactions WHERE profile_id = ?",
+    ),
+    ("accounts", "DELETE FROM accounts WHERE profile_id = ?"),
+    (
+        "account settings",
+        "DELETE FROM account_settings WHERE profile_id = ?",
+    ),
+];
+
+impl Database {
+    pub async fn create_profile(&self, name: &str, avatar_url: Option<&str>) -> Result<Profile> {
+        let id = Uuid::new_v4().to_string();
+        sqlx::query("INSERT INTO profiles (id, name, avatar_url) VALUES (?, ?, ?)")
+            .bind(&id)
+            .bind(profile_name(name)?)
+            .bind(avatar_url)
+            .execute(&self.pool)
+            .await
+            .context("Failed to create profile")?;
+
+        self.get_profile(&id).await
+    }
+
+    pub async fn get_profile(&self, profile_id: &str) -> Result<Profile> {
+        sqlx::query_as::<_, Profile>("SELECT * FROM profiles WHERE id = ?")
+            .bind(profile_id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to fetch profile")?
+            .with_context(|| format!("Profile {} not found", profile_id))
+    }
+
+    pub async fn list_profiles(&self, include_archived: bool) -> Result<Vec<Profile>> {
+        let profiles = sqlx::query_as::<_, Profile>(
+            "SELECT * FROM profiles WHERE ? OR archived_at IS NULL ORDER BY name",
+        )
+        .bind(include_archived)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to fetch profiles")?;
+
+        Ok(profiles)
+    }
+
+    pub async fn rename_profile(&self, profile_id: &str, name: &str) -> Result<Profile> {
+        self.update_profile(
+            profile_id,
+            "UPDATE profiles SET name = ?, updated_at = datetime('now') WHERE id = ?",
+            profile_name(name)?,
+        )
+        .await
+    }
+
+    pub async fn set_profile_avatar(
+        &self,
+        profile_id: &str,
+        avatar_url: Option<&str>,
+    ) -> Result<Profile> {
+        self.update_profile(
+            profile_id,
+            "UPDATE profiles SET avatar_url = ?, updated_at = datetime('now') WHERE id = ?",
+            avatar_url,
+        )
+        .await
+    }
+
+    /// Hide a profile, or bring it back with `archived = false`
+    pub async fn set_profile_archived(&self, profile_id: &str, archived: bool) -> Result<Profile> {
+        self.update_profile(
+            profile_id,
+            "UPDATE profiles SET
+                 archived_at = CASE WHEN ? THEN COALESCE(archived_at, datetime('now')) END,
+                 updated_at = datetime('now')
+             WHERE id = ?",
+            archived,
+        )
+        .await
+    }
+
+    /// Delete a profile with everything recorded for it
+    pub async fn delete_profile(&self, profile_id: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for (what, query) in PROFILE_DATA {
+            sqlx::query(query)
+                .bind(profile_id)
+                .execute(&mut *tx)
+                .await
+                .with_context(|| format!("Failed to delete the profile's {}", what))?;
+        }
+
+        let deleted = sqlx::query("DELETE FROM profiles WHERE id = ?")
+            .bind(profile_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete profile")?
+            .rows_affected();
+        if deleted == 0 {
+            anyhow::bail!("Profile {} not found", profile_id);
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Add an address to a profile after checking it is valid on `chain`
+    pub async fn add_account(
+        &self,
+        profile_id: &str,
+        chain: &str,
+        address: &str,
+        nickname: Option<&str>,
+    ) -> Result<Account> {
+        let address = validate_address(chain, address)?;
+        let profile = self.get_profile(profile_id).await?;
+        if profile.archived_at.is_some() {
+            anyhow::bail!("Profile {} is archived", profile.name);
+        }
+
+        let existing: Option<String> = sqlx::query_scalar(
+            "SELECT id FROM accounts WHERE profile_id = ? AND chain = ? AND address = ?",
+        )
+        .bind(profile_id)
+        .bind(chain)
+        .bind(&address)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to check for an existing account")?;
+        if existing.is_some() {
+            anyhow::bail!("{} on {} is already in this profile", address, chain);
+        }
+
+        let id = Uuid::new_v4().to_string();
+        sqlx::query(
+            "INSERT INTO accounts (id, profile_id, address, chain, nickname) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&id)
+        .bind(profile_id)
+        .bind(&address)
+        .bind(chain)
+        .bind(nickname)
+        .execute(&self.pool)
+        .await
+        .context("Failed to add account")?;
+
+        self.get_account(&id).await
+    }
+
+    pub async fn get_account(&self, account_id: &str) -> Result<Account> {
+        sqlx::query_as::<_, Account>("SELECT * FROM accounts WHERE id = ?")
+            .bind(account_id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to fetch account")?
+            .with_context(|| format!("Account {} not found", account_id))
+    }
+
+    pub async fn list_accounts(&self, profile_id: &str) -> Result<Vec<Account>> {
+        let accounts = sqlx::query_as::<_, Account>(
+            "SELECT * FROM accounts WHERE profile_id = ? ORDER BY chain, created_at",
+        )
+        .bind(profile_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to fetch accounts")?;
+
+        Ok(accounts)
+    }
+
+    pub async fn rename_account(
+        &self,
+        account_id: &str,
+        nickname: Option<&str>,
+    ) -> Result<Account> {
+        let updated = sqlx::query(
+            "UPDATE accounts SET nickname = ?, updated_at = datetime('now') WHERE id = ?",
+        )
+        .bind(nickname.map(str::trim).filter(|n| !n.is_empty()))
+        .bind(account_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to rename account")?
+        .rows_affected();
+        if updated == 0 {
+            anyhow::bail!("Account {} not found", account_id);
+        }
+
+        self.get_account(account_id).await
+    }
+
+    /// Stop tracking an address. Its synced transactions stay in the profile.
+    pub async fn remove_account(&self, account_id: &str) -> Result<()> {
+        let deleted = sqlx::query("DELETE FROM accounts WHERE id = ?")
+            .bind(account_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to remove account")?
+            .rows_affected();
+        if deleted == 0 {
+            anyhow::bail!("Account {} not found", account_id);
+        }
+
+        Ok(())
+    }
+
+    async fn update_profile<'q, T>(
+        &self,
+        profile_id: &'q str,
+        query: &'q str,
+        value: T,
+    ) -> Result<Profile>
+    where
+        T: 'q + Send + sqlx::Encode<'q, sqlx::Sqlite> + sqlx::Type<sqlx::Sqlite>,
+    {
+        let updated = sqlx::query(query)
+            .bind(value)
+            .bind(profile_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update profile")?
+            .rows_affected();
+        if updated == 0 {
+            anyhow::bail!("Profile {} not found", profile_id);
+        }
+
+        self.get_profile(profile_id).await
+    }
+}
+
+fn profile_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        anyhow::bail!("Profile name cannot be empty");
+    }
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+
+    const ME: &str = "0x1111111111111111111111111111111111111111";
+    const POLKADOT: &str = "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5";
+
+    async fn database() -> Database {
+        Database {
+            pool: test_pool().await,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_profile_lifecycle() {
+        let db = database().await;
+        let profile = db.create_profile("  Treasury ", None).await.unwrap();
+        assert_eq!(profile.name, "Treasury");
+        assert!(db.create_profile(" ", None).await.is_err());
+
+        let profile = db.rename_profile(&profile.id, "Grants").await.unwrap();
+        assert_eq!(profile.name, "Grants");
+
+        db.set_profile_archived(&profile.id, true).await.unwrap();
+        assert!(db.list_profiles(false).await.unwrap().is_empty());
+        assert_eq!(db.list_profiles(true).await.unwrap().len(), 1);
+        assert!(db
+            .add_account(&profile.id, "moonbeam", ME, None)
+            .await
+            .is_err());
+
+        let profile = db.set_profile_archived(&profile.id, false).await.unwrap();
+        assert_eq!(profile.archived_at, None);
+        assert!(db.rename_profile("missing", "x").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_accounts_are_validated() {
+        let db = database().await;
+        let profile = db.create_profile("Treasury", None).await.unwrap();
+
+        let evm = db
+            .add_account(
+                &profile.id,
+                "moonbeam",
+                &ME.to_uppercase().replace("0X", "0x"),
+                Some("Hot"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(evm.address, ME);
+        assert!(db
+            .add_account(&profile.id, "moonbeam", ME, None)
+            .await
+            .is_err());
+        assert!(db
+            .add_account(&profile.id, "kusama", POLKADOT, None)
+            .await
+            .is_err());
+        db.add_account(&profile.id, "polkadot", POLKADOT, None)
+            .await
+            .unwrap();
+
+        let evm = db
+            .rename_account(&evm.id.to_string(), Some("Cold"))
+            .await
+            .unwrap();
+        assert_eq!(evm.nickname.as_deref(), Some("Cold"));
+        db.remove_account(&evm.id.to_string()).await.unwrap();
+        let accounts = db.list_accounts(&profile.id).await.unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].chain, "polkadot");
+    }
+
+    #[tokio::test]
+    async fn test_delete_profile_removes_its_data() {
+        let db = database().await;
+        let profile = db.create_profile("Treasury", None).await.unwrap();
+        let other = db.create_profile("Other", None).await.unwrap();
+        let account = db
+            .add_account(&profile.id, "moonbeam", ME, None)
+            .await
+            .unwrap();
+        db.add_account(&other.id, "moonbeam", ME, None)
+            .await
+            .unwrap();
+
+        for query in [
+            "INSERT INTO transactions (id, profile_id, chain, hash, block_number, timestamp,
+                 from_address, value, token_symbol, token_decimals, transaction_type, status)
+             VALUES ('t1', ?1, 'moonbeam', '0x01', 1, datetime('now'), '0x2', '1', 'GLMR', 18,
+                 'transfer', 'success')",
+            "INSERT INTO transaction_tags (transaction_id, tag) VALUES ('t1', 'gift')",
+            "INSERT INTO account_settings (id, profile_id) VALUES ('s1', ?1)",
+            "INSERT INTO ledger_accounts (profile_id, code, name, account_type)
+             VALUES (?1, '1000', 'Cash', 'asset')",
+            "INSERT INTO journal_entries (id, profile_id, transaction_id, entry_date, description, currency)
+             VALUES ('j1', ?1, 't1', datetime('now'), 'Transfer', 'USD')",
+            "INSERT INTO journal_postings (entry_id, line, profile_id, account_code, debit)
+             VALUES ('j1', 0, ?1, '1000', '1')",
+            "INSERT INTO account_sync_status (account_id, last_synced_block) VALUES (?2, 10)",
+        ] {
+            sqlx::query(query)
+                .bind(&profile.id)
+                .bind(account.id.to_string())
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+
+        db.delete_profile(&profile.id).await.unwrap();
+        assert!(db.get_profile(&profile.id).await.is_err());
+        for table in [
+            "transactions",
+            "transaction_tags",
+            "account_settings",
+            "ledger_accounts",
+            "journal_entries",
+            "journal_postings",
+            "account_sync_status",
+        ] {
+            let rows: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+            assert_eq!(rows, 0, "{} not cleared", table);
+        }
+        assert_eq!(db.list_accounts(&other.id).await.unwrap().len(), 1);
+        assert!(db.delete_profile(&profile.id).await.is_err());
+    }
+}
@@ -0,0 +1,653 @@
+use super::Database;
+use crate::core::amount::MAX_DECIMALS;
+use crate::core::Transaction;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, QueryBuilder, Row, Sqlite};
+
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+pub const MAX_PAGE_SIZE: u32 = 1_000;
+
+/// Digits of an amount sort key: the 78 digits of the largest U256, scaled
+/// by up to `MAX_DECIMALS`
+const AMOUNT_KEY_WIDTH: usize = 78 + MAX_DECIMALS as usize;
+
+/// Which transactions of a profile to return. Empty fields match everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransactionFilter {
+    pub profile_id: String,
+    pub chains: Vec<String>,
+    /// Row in `accounts`, matches transactions sent or received by its address
+    pub account_id: Option<String>,
+    pub token_symbols: Vec<String>,
+    pub transaction_types: Vec<String>,
+    pub statuses: Vec<String>,
+    /// Bounds on the amount in whole tokens, inclusive
+    pub min_amount: Option<Decimal>,
+    pub max_amount: Option<Decimal>,
+    /// Address on either side of the transaction, case-insensitive
+    pub counterparty: Option<String>,
+    /// Only transactions carrying all of these tags
+    pub tags: Vec<String>,
+    /// Inclusive start and exclusive end of the date range
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    Timestamp,
+    BlockNumber,
+    Amount,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PageRequest {
+    pub sort: SortField,
+    pub direction: SortDirection,
+    /// `next_cursor` of the previous page, `None` for the first page
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionPage {
+    pub transactions: Vec<Transaction>,
+    /// Pass back in `PageRequest::cursor` to get the next page, `None` on the last one
+    pub next_cursor: Option<String>,
+}
+
+/// Sort key and id of the last row of a page. Rows are ordered by
+/// `(key, id)`, so the next page starts strictly after it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Cursor {
+    sort: SortField,
+    key: CursorKey,
+    id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum CursorKey {
+    Integer(i64),
+    Text(String),
+}
+
+impl Cursor {
+    fn encode(&self) -> Result<String> {
+        Ok(hex::encode(serde_json::to_vec(self)?))
+    }
+
+    fn decode(cursor: &str) -> Result<Self> {
+        let bytes = hex::decode(cursor).context("Invalid cursor")?;
+        serde_json::from_slice(&bytes).context("Invalid cursor")
+    }
+}
+
+impl SortField {
+    fn expression(self) -> String {
+        match self {
+            SortField::Timestamp => "t.timestamp".to_string(),
+            SortField::BlockNumber => "t.block_number".to_string(),
+            // Units scaled to `MAX_DECIMALS` and zero-padded, so the text
+            // compares like the exact amount across tokens; see `amount_key`
+            SortField::Amount => format!(
+                "substr('{zeros}' || t.value || substr('{zeros}', 1, {max} - t.token_decimals), -{width})",
+                zeros = "0".repeat(AMOUNT_KEY_WIDTH),
+                max = MAX_DECIMALS,
+                width = AMOUNT_KEY_WIDTH,
+            ),
+        }
+    }
+
+    fn key(self, row: &SqliteRow) -> Result<CursorKey> {
+        Ok(match self {
+            SortField::Timestamp => CursorKey::Text(row.try_get("sort_key")?),
+            SortField::BlockNumber => CursorKey::Integer(row.try_get("sort_key")?),
+            SortField::Amount => CursorKey::Text(row.try_get("sort_key")?),
+        })
+    }
+}
+
+impl Database {
+    /// One page of a profile's transactions. All values are bound, never
+    /// spliced into the SQL.
+    pub async fn query_transactions(
+        &self,
+        filter: &TransactionFilter,
+        page: &PageRequest,
+    ) -> Result<TransactionPage> {
+        let limit = page
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let cursor = page.cursor.as_deref().map(Cursor::decode).transpose()?;
+        if cursor.as_ref().is_some_and(|c| c.sort != page.sort) {
+            anyhow::bail!("Cursor belongs to a different sort order");
+        }
+
+        let key = page.sort.expression();
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT t.*, ");
+        query.push(&key).push(" AS sort_key FROM transactions t");
+        push_filter(&mut query, filter);
+
+        if let Some(cursor) = cursor {
+            let comparison = match page.direction {
+                SortDirection::Asc => ">",
+                SortDirection::Desc => "<",
+            };
+            query.push(format_args!(" AND ({}, t.id) {} (", key, comparison));
+            match cursor.key {
+                CursorKey::Integer(value) => query.push_bind(value),
+                CursorKey::Text(value) => query.push_bind(value),
+            };
+            query.push(", ").push_bind(cursor.id).push(")");
+        }
+
+        let direction = match page.direction {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        };
+        query.push(format_args!(
+            " ORDER BY sort_key {0}, t.id {0} LIMIT ",
+            direction
+        ));
+        // One extra row tells whether there is a next page
+        query.push_bind(limit as i64 + 1);
+
+        let mut rows = query
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query transactions")?;
+
+        let next_cursor = if rows.len() > limit as usize {
+            rows.truncate(limit as usize);
+            let last = rows.last().expect("page is not empty");
+            let cursor = Cursor {
+                sort: page.sort,
+                key: page.sort.key(last)?,
+                id: last.try_get("id")?,
+            };
+            Some(cursor.encode()?)
+        } else {
+            None
+        };
+
+        let transactions = rows
+            .iter()
+            .map(Transaction::from_row)
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to read transactions")?;
+
+        Ok(TransactionPage {
+            transactions,
+            next_cursor,
+        })
+    }
+
+    /// Every transaction matching `filter`, oldest first
+    pub async fn get_transactions(&self, filter: &TransactionFilter) -> Result<Vec<Transaction>> {
+        let mut page = PageRequest {
+            direction: SortDirection::Asc,
+            limit: Some(MAX_PAGE_SIZE),
+            ..Default::default()
+        };
+        let mut transactions = Vec::new();
+        loop {
+            let result = self.query_transactions(filter, &page).await?;
+            transactions.extend(result.transactions);
+            match result.next_cursor {
+                Some(cursor) => page.cursor = Some(cursor),
+                None => return Ok(transactions),
+            }
+        }
+    }
+
+    /// Replace the tags of a transaction
+    pub async fn set_transaction_tags(&self, transaction_id: &str, tags: &[String]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM transaction_tags WHERE transaction_id = ?")
+            .bind(transaction_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to clear transaction tags")?;
+
+        for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            sqlx::query(
+                "INSERT OR IGNORE INTO transaction_tags (transaction_id, tag) VALUES (?, ?)",
+            )
+            .bind(transaction_id)
+            .bind(tag)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to save transaction tag")?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_transaction_tags(&self, transaction_id: &str) -> Result<Vec<String>> {
+        let tags = sqlx::query_scalar(
+            "SELECT tag FROM transaction_tags WHERE transaction_id = ? ORDER BY tag",
+        )
+        .bind(transaction_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to fetch transaction tags")?;
+
+        Ok(tags)
+    }
+}
+
+fn push_filter(query: &mut QueryBuilder<'_, Sqlite>, filter: &TransactionFilter) {
+    query
+        .push(" WHERE t.profile_id = ")
+        .push_bind(filter.profile_id.clone());
+
+    push_in(query, "t.chain", &filter.chains);
+    push_in(query, "t.token_symbol", &filter.token_symbols);
+    push_in(query, "t.transaction_type", &filter.transaction_types);
+    push_in(query, "t.status", &filter.statuses);
+
+    if let Some(account_id) = &filter.account_id {
+        query
+            .push(
+                " AND EXISTS (SELECT 1 FROM accounts a WHERE a.chain = t.chain
+                  AND (LOWER(a.address) = LOWER(t.from_address)
+                    OR LOWER(a.address) = LOWER(t.to_address)) AND a.id = ",
+            )
+            .push_bind(account_id.clone())
+            .push(")");
+    }
+
+    if let Some(counterparty) = &filter.counterparty {
+        let counterparty = counterparty.to_lowercase();
+        query
+            .push(" AND (LOWER(t.from_address) = ")
+            .push_bind(counterparty.clone())
+            .push(" OR LOWER(t.to_address) = ")
+            .push_bind(counterparty)
+            .push(")");
+    }
+
+    let amount = SortField::Amount.expression();
+    // Amounts are never negative: a negative minimum bounds nothing and a
+    // negative maximum matches nothing
+    if let Some(min) = filter.min_amount.and_then(amount_key) {
+        query
+            .push(format_args!(" AND {} >= ", amount))
+            .push_bind(min);
+    }
+    if let Some(max) = filter.max_amount {
+        match amount_key(max) {
+            Some(max) => query
+                .push(format_args!(" AND {} <= ", amount))
+                .push_bind(max),
+            None => query.push(" AND 0"),
+        };
+    }
+
+    if !filter.tags.is_empty() {
+        query.push(
+            " AND (SELECT COUNT(DISTINCT tag) FROM transaction_tags tt
+              WHERE tt.transaction_id = t.id AND tt.tag IN (",
+        );
+        let mut tags = query.separated(", ");
+        for tag in &filter.tags {
+            tags.push_bind(tag.clone());
+        }
+        query
+            .push(")) = ")
+            .push_bind(distinct_count(&filter.tags) as i64);
+    }
+
+    if let Some(from) = filter.from {
+        query.push(" AND t.timestamp >= ").push_bind(from);
+    }
+    if let Some(to) = filter.to {
+        query.push(" AND t.timestamp < ").push_bind(to);
+    }
+}
+
+/// Sort key of an amount in whole tokens, comparable with
+/// `SortField::Amount`. `None` below zero.
+fn amount_key(amount: Decimal) -> Option<String> {
+    if amount.is_sign_negative() && !amount.is_zero() {
+        return None;
+    }
+    let text = amount.abs().to_string();
+    let (whole, fraction) = text.split_once('.').unwrap_or((&text, ""));
+    Some(format!(
+        "{:0>whole_width$}{:0<fraction_width$}",
+        whole,
+        fraction,
+        whole_width = AMOUNT_KEY_WIDTH - MAX_DECIMALS as usize,
+        fraction_width = MAX_DECIMALS as usize,
+    ))
+}
+
+/// `AND column IN (...)`, nothing when `values` is empty
+fn push_in(query: &mut QueryBuilder<'_, Sqlite>, column: &str, values: &[String]) {
+    if values.is_empty() {
+        return;
+    }
+
+    query.push(format_args!(" AND {} IN (", column));
+    let mut separated = query.separated(", ");
+    for value in values {
+        separated.push_bind(value.clone());
+    }
+    query.push(")");
+}
+
+fn distinct_count(values: &[String]) -> usize {
+    values
+        .iter()
+        .collect::<std::collections::BTreeSet<_>>()
+        .len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    const ME: &str = "0x1111111111111111111111111111111111111abc";
+    const EXCHANGE: &str = "0x2222222222222222222222222222222222222222";
+
+    async fn database() -> Database {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO profiles (id, name) VALUES ('p1', 'Test')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO accounts (id, profile_id, address, chain) VALUES ('a1', 'p1', ?, 'moonbeam')",
+        )
+        // Checksummed, while the indexer stores the address lowercase
+        .bind(ME.to_uppercase().replace("0X", "0x"))
+        .execute(&pool)
+        .await
+        .unwrap();
+        Database { pool }
+    }
+
+    /// Transfer of `glmr` on day `day` of January 2024
+    async fn insert(
+        db: &Database,
+        day: u32,
+        from: &str,
+        to: &str,
+        glmr: i64,
+        kind: &str,
+    ) -> String {
+        let id = Uuid::new_v4().to_string();
+        sqlx::query(
+            r#"
+            INSERT INTO transactions (
+                id, profile_id, chain, hash, block_number, timestamp, from_address,
+                to_address, value, token_symbol, token_decimals, transaction_type, status,
+                metadata
+            ) VALUES (?, 'p1', 'moonbeam', ?, ?, ?, ?, ?, ?, 'GLMR', 18, ?, 'success', '{}')
+            "#,
+        )
+        .bind(&id)
+        .bind(format!("0x{:064x}", day))
+        .bind(day as i64 * 100)
+        .bind(Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap())
+        .bind(from)
+        .bind(to)
+        .bind((glmr as i128 * 10_i128.pow(18)).to_string())
+        .bind(kind)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        id
+    }
+
+    fn filter() -> TransactionFilter {
+        TransactionFilter {
+            profile_id: "p1".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn days(page: &TransactionPage) -> Vec<i64> {
+        page.transactions
+            .iter()
+            .map(|tx| tx.block_number / 100)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_cursor_pagination() {
+        let db = database().await;
+        for day in 1..=5 {
+            insert(&db, day, EXCHANGE, ME, day as i64 * 10, "transfer").await;
+        }
+
+        let mut page = PageRequest {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let mut seen = Vec::new();
+        loop {
+            let result = db.query_transactions(&filter(), &page).await.unwrap();
+            seen.push(days(&result));
+            match result.next_cursor {
+                Some(cursor) => page.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(seen, vec![vec![5, 4], vec![3, 2], vec![1]]);
+
+        // Amount sort, ascending
+        let page = PageRequest {
+            sort: SortField::Amount,
+            direction: SortDirection::Asc,
+            limit: Some(3),
+            ..Default::default()
+        };
+        let first = db.query_transactions(&filter(), &page).await.unwrap();
+        assert_eq!(days(&first), vec![1, 2, 3]);
+        let second = PageRequest {
+            cursor: first.next_cursor.clone(),
+            ..page.clone()
+        };
+        let second = db.query_transactions(&filter(), &second).await.unwrap();
+        assert_eq!(days(&second), vec![4, 5]);
+        assert_eq!(second.next_cursor, None);
+
+        // A cursor only continues the order it came from
+        let mixed = PageRequest {
+            cursor: first.next_cursor,
+            ..Default::default()
+        };
+        assert!(db.query_transactions(&filter(), &mixed).await.is_err());
+        assert_eq!(db.get_transactions(&filter()).await.unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_exact_amounts() {
+        let db = database().await;
+        insert(&db, 1, EXCHANGE, ME, 100, "transfer").await;
+        // One wei more, which a REAL cannot tell apart
+        let second = insert(&db, 2, EXCHANGE, ME, 100, "transfer").await;
+        sqlx::query("UPDATE transactions SET value = '100000000000000000001' WHERE id = ?")
+            .bind(&second)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        // 150 of a token with 6 decimals
+        let third = insert(&db, 3, EXCHANGE, ME, 0, "transfer").await;
+        sqlx::query("UPDATE transactions SET value = '150000000', token_decimals = 6 WHERE id = ?")
+            .bind(&third)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let mut page = PageRequest {
+            sort: SortField::Amount,
+            direction: SortDirection::Asc,
+            limit: Some(1),
+            ..Default::default()
+        };
+        let mut seen = Vec::new();
+        loop {
+            let result = db.query_transactions(&filter(), &page).await.unwrap();
+            seen.extend(days(&result));
+            match result.next_cursor {
+                Some(cursor) => page.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(seen, vec![1, 2, 3]);
+
+        let query = |min: Option<&str>, max: Option<&str>| {
+            let db = &db;
+            let filter = TransactionFilter {
+                min_amount: min.map(|m| m.parse().unwrap()),
+                max_amount: max.map(|m| m.parse().unwrap()),
+                ..filter()
+            };
+            async move {
+                let page = db
+                    .query_transactions(&filter, &PageRequest::default())
+                    .await
+                    .unwrap();
+                let mut days = days(&page);
+                days.sort();
+                days
+            }
+        };
+        assert_eq!(
+            query(Some("100.000000000000000001"), None).await,
+            vec![2, 3]
+        );
+        assert_eq!(query(None, Some("100")).await, vec![1]);
+        assert_eq!(query(Some("-1"), Some("150")).await, vec![1, 2, 3]);
+        assert_eq!(query(None, Some("-1")).await, Vec::<i64>::new());
+    }
+
+    #[tokio::test]
+    async fn test_filters() {
+        let db = database().await;
+        let first = insert(&db, 1, EXCHANGE, ME, 10, "transfer").await;
+        insert(&db, 2, ME, EXCHANGE, 25, "transfer").await;
+        let third = insert(&db, 3, EXCHANGE, ME, 40, "staking_reward").await;
+        insert(
+            &db,
+            4,
+            "0x3333333333333333333333333333333333333333",
+            EXCHANGE,
+            5,
+            "transfer",
+        )
+        .await;
+        db.set_transaction_tags(&first, &["gift".to_string(), "2024".to_string()])
+            .await
+            .unwrap();
+        db.set_transaction_tags(&third, &["2024".to_string()])
+            .await
+            .unwrap();
+
+        let query = |filter: TransactionFilter| {
+            let db = &db;
+            async move {
+                let page = db
+                    .query_transactions(&filter, &PageRequest::default())
+                    .await
+                    .unwrap();
+                let mut days = days(&page);
+                days.sort();
+                days
+            }
+        };
+
+        assert_eq!(
+            query(TransactionFilter {
+                account_id: Some("a1".to_string()),
+                ..filter()
+            })
+            .await,
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            query(TransactionFilter {
+                transaction_types: vec!["staking_reward".to_string()],
+                ..filter()
+            })
+            .await,
+            vec![3]
+        );
+        assert_eq!(
+            query(TransactionFilter {
+                min_amount: Some(Decimal::new(10, 0)),
+                max_amount: Some(Decimal::new(255, 1)),
+                ..filter()
+            })
+            .await,
+            vec![1, 2]
+        );
+        assert_eq!(
+            query(TransactionFilter {
+                counterparty: Some(EXCHANGE.to_uppercase().replace("0X", "0x")),
+                from: Some(Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap()),
+                to: Some(Utc.with_ymd_and_hms(2024, 1, 4, 0, 0, 0).unwrap()),
+                ..filter()
+            })
+            .await,
+            vec![2, 3]
+        );
+        assert_eq!(
+            query(TransactionFilter {
+                tags: vec!["2024".to_string(), "gift".to_string()],
+                ..filter()
+            })
+            .await,
+            vec![1]
+        );
+        assert_eq!(
+            query(TransactionFilter {
+                chains: vec!["astar".to_string()],
+                ..filter()
+            })
+            .await,
+            Vec::<i64>::new()
+        );
+
+        // Values are bound, not spliced into the query
+        assert_eq!(
+            query(TransactionFilter {
+                statuses: vec!["success' OR '1'='1".to_string()],
+                ..filter()
+            })
+            .await,
+            Vec::<i64>::new()
+        );
+        assert_eq!(
+            db.get_transaction_tags(&first).await.unwrap(),
+            vec!["2024", "gift"]
+        );
+    }
+}
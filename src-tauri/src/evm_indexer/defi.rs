@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+use crate::core::amount::TokenAmount;
 use anyhow::Result;
 use ethers::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -185,6 +186,5 @@ pub struct DeFiPosition {
 pub struct AssetAmount {
     pub token_address: Option<Address>,
     pub token_symbol: String,
-    pub amount: U256,
-    pub decimals: u8,
+    pub amount: TokenAmount,
 }
@@ -0,0 +1,827 @@
+#![allow(dead_code)]
+
+use anyhow::{Context, Result};
+use ethers::prelude::*;
+use ethers::utils::keccak256;
+use serde::Deserialize;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+/// Page size used for explorer `txlist`-style endpoints (Etherscan caps this at 10k)
+const EXPLORER_PAGE_SIZE: usize = 1000;
+
+/// How a transaction touching the address was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscoverySource {
+    /// `eth_getLogs` with the address in an indexed topic
+    Logs,
+    /// Block explorer `txlist` / `tokentx` / `txlistinternal`
+    Explorer,
+    /// `trace_filter` (internal value transfers)
+    Traces,
+    /// Block-by-block scan of top-level transactions
+    Blocks,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscoveredTransaction {
+    pub hash: H256,
+    pub block_number: u64,
+    pub source: DiscoverySource,
+    /// Value moved by contract calls inside the transaction, from traces or
+    /// the explorer's `txlistinternal`
+    pub internal_transfers: Vec<InternalTransfer>,
+}
+
+/// Native value moved by a call inside a transaction rather than by the
+/// transaction itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternalTransfer {
+    /// Position in the call tree, such as `0_1`
+    pub trace_id: String,
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+}
+
+/// Block range sizing for range-limited RPC queries
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    pub initial_range: u64,
+    pub min_range: u64,
+    pub max_range: u64,
+    /// Fetch blocks one by one when neither an explorer nor `trace_filter`
+    /// is available. Off by default, such chains fail discovery instead.
+    pub block_scan: bool,
+    /// Most blocks a block scan may cover in one discovery
+    pub max_scan_blocks: u64,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            initial_range: 2_000,
+            min_range: 1,
+            max_range: 10_000,
+            block_scan: false,
+            max_scan_blocks: 10_000,
+        }
+    }
+}
+
+/// Adaptive block range: halves when the provider rejects a query as too
+/// large and grows back after successful queries.
+#[derive(Debug, Clone)]
+pub struct RangeSizer {
+    current: u64,
+    min: u64,
+    max: u64,
+}
+
+impl RangeSizer {
+    pub fn new(config: &DiscoveryConfig) -> Self {
+        let min = config.min_range.max(1);
+        let max = config.max_range.max(min);
+        Self {
+            current: config.initial_range.clamp(min, max),
+            min,
+            max,
+        }
+    }
+
+    pub fn current(&self) -> u64 {
+        self.current
+    }
+
+    pub fn on_success(&mut self) {
+        self.current = self.current.saturating_mul(2).min(self.max);
+    }
+
+    /// Shrink the range. Returns false when it cannot shrink any further.
+    pub fn on_too_large(&mut self) -> bool {
+        if self.current <= self.min {
+            return false;
+        }
+        self.current = (self.current / 2).max(self.min);
+        true
+    }
+}
+
+/// Whether a provider error means "narrow the block range and retry".
+/// Providers phrase this differently, so match on the common wordings.
+pub fn is_range_too_large(message: &str) -> bool {
+    let message = message.to_lowercase();
+    [
+        "query returned more than",
+        "block range",
+        "range too large",
+        "range is too large",
+        "too many results",
+        "too many blocks",
+        "limit exceeded",
+        "exceeds the limit",
+        "response size exceeded",
+        "query timeout",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+/// Finds the transactions touching an address without walking every block.
+///
+/// Explorer endpoints are tried first when the chain has one configured. If the
+/// explorer is unavailable, the engine falls back to `eth_getLogs` over
+/// `Transfer` topics plus `trace_filter` for internal value transfers. Logs
+/// miss native sends, plain contract calls and failed transactions, so without
+/// traces discovery fails rather than return an incomplete history, unless
+/// `block_scan` is enabled for ranges up to `max_scan_blocks`.
+///
+/// Discovery covers whatever range it is given, so callers run it once over
+/// the whole range they need instead of once per chunk.
+pub struct TransactionDiscovery {
+    provider: Arc<Provider<Ws>>,
+    explorer_api: Option<String>,
+    config: DiscoveryConfig,
+    http: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExplorerResponse {
+    status: String,
+    message: String,
+    result: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExplorerTransaction {
+    hash: String,
+    #[serde(rename = "blockNumber")]
+    block_number: String,
+    #[serde(default)]
+    from: String,
+    #[serde(default)]
+    to: String,
+    #[serde(default)]
+    value: String,
+    #[serde(rename = "traceId")]
+    trace_id: Option<String>,
+    #[serde(rename = "isError", default)]
+    is_error: String,
+}
+
+impl TransactionDiscovery {
+    pub fn new(provider: Arc<Provider<Ws>>, explorer_api: Option<String>) -> Self {
+        Self {
+            provider,
+            explorer_api,
+            config: DiscoveryConfig::default(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub fn with_config(mut self, config: DiscoveryConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Discover transactions touching `address` in `from_block..=to_block`,
+    /// deduplicated by hash and ordered by block number.
+    pub async fn discover(
+        &self,
+        address: Address,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<DiscoveredTransaction>> {
+        let mut found: BTreeMap<H256, DiscoveredTransaction> = BTreeMap::new();
+
+        let explorer_result = match &self.explorer_api {
+            Some(api) => Some(
+                self.discover_via_explorer(api, address, from_block, to_block)
+                    .await,
+            ),
+            None => None,
+        };
+
+        match explorer_result {
+            Some(Ok(discovered)) => {
+                for tx in discovered {
+                    merge(&mut found, tx);
+                }
+            }
+            other => {
+                if let Some(Err(e)) = other {
+                    // Fall back to RPC discovery if the explorer is down or rate limited
+                    eprintln!("Explorer discovery failed, falling back to logs: {}", e);
+                }
+
+                for tx in self
+                    .discover_via_logs(address, from_block, to_block)
+                    .await?
+                {
+                    merge(&mut found, tx);
+                }
+
+                match self
+                    .discover_via_traces(address, from_block, to_block)
+                    .await
+                {
+                    Ok(discovered) => {
+                        for tx in discovered {
+                            merge(&mut found, tx);
+                        }
+                    }
+                    Err(e) if !self.config.block_scan => {
+                        // Most public RPCs do not expose trace_filter
+                        return Err(e).context(
+                            "Native transfers cannot be discovered: the chain needs a block explorer or a trace_filter endpoint",
+                        );
+                    }
+                    Err(e) => {
+                        eprintln!("Trace discovery unavailable, scanning blocks: {}", e);
+                        for tx in self
+                            .discover_via_blocks(address, from_block, to_block)
+                            .await
+                            .context("Neither a block explorer nor trace_filter is available")?
+                        {
+                            merge(&mut found, tx);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut discovered: Vec<_> = found.into_values().collect();
+        discovered.sort_by_key(|tx| tx.block_number);
+        Ok(discovered)
+    }
+
+    /// Find ERC-20/721 `Transfer` logs with the address as sender or recipient
+    pub async fn discover_via_logs(
+        &self,
+        address: Address,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<DiscoveredTransaction>> {
+        let discovered = self
+            .transfer_logs(address, from_block, to_block)
+            .await?
+            .into_iter()
+            .filter_map(|log| {
+                Some(DiscoveredTransaction {
+                    hash: log.transaction_hash?,
+                    block_number: log.block_number?.as_u64(),
+                    source: DiscoverySource::Logs,
+                    internal_transfers: Vec::new(),
+                })
+            })
+            .collect();
+
+        Ok(discovered)
+    }
+
+    /// `Transfer` logs of any contract with the address in the from or to topic
+    pub async fn transfer_logs(
+        &self,
+        address: Address,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<Log>> {
+        let transfer_topic = H256::from(keccak256("Transfer(address,address,uint256)"));
+        let address_topic = H256::from(address);
+
+        let outgoing = Filter::new().topic0(transfer_topic).topic1(address_topic);
+        let incoming = Filter::new().topic0(transfer_topic).topic2(address_topic);
+
+        let mut logs = self
+            .get_logs_adaptive(&outgoing, from_block, to_block)
+            .await?;
+        let mut seen: HashSet<_> = logs
+            .iter()
+            .map(|log| (log.transaction_hash, log.log_index))
+            .collect();
+
+        for log in self
+            .get_logs_adaptive(&incoming, from_block, to_block)
+            .await?
+        {
+            // Self-transfers match both filters
+            if seen.insert((log.transaction_hash, log.log_index)) {
+                logs.push(log);
+            }
+        }
+
+        Ok(logs)
+    }
+
+    /// Run `eth_getLogs` over `from_block..=to_block`, shrinking the range
+    /// whenever the provider rejects a query as too large.
+    pub async fn get_logs_adaptive(
+        &self,
+        filter: &Filter,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<Log>> {
+        let mut logs = Vec::new();
+        let mut sizer = RangeSizer::new(&self.config);
+        let mut start = from_block;
+
+        while start <= to_block {
+            let end = start.saturating_add(sizer.current() - 1).min(to_block);
+            let ranged = filter.clone().from_block(start).to_block(end);
+
+            match self.provider.get_logs(&ranged).await {
+                Ok(mut batch) => {
+                    logs.append(&mut batch);
+                    sizer.on_success();
+                    start = end + 1;
+                }
+                Err(e) if is_range_too_large(&e.to_string()) && sizer.on_too_large() => continue,
+                Err(e) => {
+                    return Err(e)
+                        .context(format!("eth_getLogs failed for blocks {}-{}", start, end))
+                }
+            }
+        }
+
+        Ok(logs)
+    }
+
+    /// Find internal value transfers via `trace_filter`
+    pub async fn discover_via_traces(
+        &self,
+        address: Address,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<DiscoveredTransaction>> {
+        let mut discovered = Vec::new();
+        let mut sizer = RangeSizer::new(&self.config);
+        let mut start = from_block;
+
+        while start <= to_block {
+            let end = start.saturating_add(sizer.current() - 1).min(to_block);
+
+            // fromAddress and toAddress are ANDed by the RPC, so query each side separately
+            let filters = [
+                TraceFilter::default()
+                    .from_block(start)
+                    .to_block(end)
+                    .from_address(vec![address]),
+                TraceFilter::default()
+                    .from_block(start)
+                    .to_block(end)
+                    .to_address(vec![address]),
+            ];
+
+            let mut batch = Vec::new();
+            let mut too_large = false;
+            for filter in filters {
+                match self.provider.trace_filter(filter).await {
+                    Ok(mut traces) => batch.append(&mut traces),
+                    Err(e) if is_range_too_large(&e.to_string()) => {
+                        too_large = true;
+                        break;
+                    }
+                    Err(e) => return Err(e).context("trace_filter failed"),
+                }
+            }
+
+            if too_large {
+                if sizer.on_too_large() {
+                    continue;
+                }
+                anyhow::bail!(
+                    "trace_filter rejected single-block range at block {}",
+                    start
+                );
+            }
+
+            for trace in batch {
+                if let Some(hash) = trace.transaction_hash {
+                    discovered.push(DiscoveredTransaction {
+                        hash,
+                        block_number: trace.block_number,
+                        source: DiscoverySource::Traces,
+                        internal_transfers: internal_transfer(&trace).into_iter().collect(),
+                    });
+                }
+            }
+
+            sizer.on_success();
+            start = end + 1;
+        }
+
+        Ok(discovered)
+    }
+
+    /// Fetch every block in the range and keep the transactions sent from or
+    /// to the address, including failed ones that still paid gas. Value moved
+    /// to the address inside a contract call only shows up in traces.
+    ///
+    /// One RPC call per block, so the range is capped at `max_scan_blocks`.
+    pub async fn discover_via_blocks(
+        &self,
+        address: Address,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<DiscoveredTransaction>> {
+        let blocks = to_block.saturating_sub(from_block) + 1;
+        if blocks > self.config.max_scan_blocks {
+            anyhow::bail!(
+                "Blocks {}-{} are too many to scan one by one (at most {})",
+                from_block,
+                to_block,
+                self.config.max_scan_blocks
+            );
+        }
+
+        let mut discovered = Vec::new();
+        for number in from_block..=to_block {
+            let block = self
+                .provider
+                .get_block_with_txs(number)
+                .await
+                .with_context(|| format!("Failed to fetch block {}", number))?
+                .with_context(|| format!("Block {} not found", number))?;
+
+            discovered.extend(
+                block
+                    .transactions
+                    .iter()
+                    .filter(|tx| touches(tx, address))
+                    .map(|tx| DiscoveredTransaction {
+                        hash: tx.hash,
+                        block_number: number,
+                        source: DiscoverySource::Blocks,
+                        internal_transfers: Vec::new(),
+                    }),
+            );
+        }
+
+        Ok(discovered)
+    }
+
+    /// Query an Etherscan-compatible explorer (Moonscan, Blockscout) for
+    /// normal, token and internal transactions.
+    pub async fn discover_via_explorer(
+        &self,
+        api: &str,
+        address: Address,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<DiscoveredTransaction>> {
+        let mut discovered = Vec::new();
+
+        for action in ["txlist", "tokentx", "txlistinternal"] {
+            let mut page = 1;
+            loop {
+                let batch = self
+                    .fetch_explorer_page(api, action, address, from_block, to_block, page)
+                    .await?;
+                let batch_len = batch.len();
+
+                for tx in batch {
+                    let internal_transfers = if action == "txlistinternal" {
+                        explorer_internal_transfer(&tx, discovered.len())?
+                            .into_iter()
+                            .collect()
+                    } else {
+                        Vec::new()
+                    };
+
+                    discovered.push(DiscoveredTransaction {
+                        hash: tx
+                            .hash
+                            .parse()
+                            .context("Invalid transaction hash from explorer")?,
+                        block_number: tx
+                            .block_number
+                            .parse()
+                            .context("Invalid block number from explorer")?,
+                        source: DiscoverySource::Explorer,
+                        internal_transfers,
+                    });
+                }
+
+                if batch_len < EXPLORER_PAGE_SIZE {
+                    break;
+                }
+                page += 1;
+            }
+        }
+
+        Ok(discovered)
+    }
+
+    async fn fetch_explorer_page(
+        &self,
+        api: &str,
+        action: &str,
+        address: Address,
+        from_block: u64,
+        to_block: u64,
+        page: usize,
+    ) -> Result<Vec<ExplorerTransaction>> {
+        let url = format!(
+            "{}?module=account&action={}&address={:?}&startblock={}&endblock={}&page={}&offset={}&sort=asc",
+            api, action, address, from_block, to_block, page, EXPLORER_PAGE_SIZE
+        );
+
+        let response = self
+            .http
+            .get(&url)
+            .send()
+            .await
+            .context("Failed to reach block explorer")?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Block explorer error ({})", response.status());
+        }
+
+        let data: ExplorerResponse = response
+            .json()
+            .await
+            .context("Failed to parse block explorer response")?;
+
+        parse_explorer_result(data)
+    }
+}
+
+/// Add a discovery, combining the internal transfers of a transaction that
+/// was found more than once
+fn merge(found: &mut BTreeMap<H256, DiscoveredTransaction>, tx: DiscoveredTransaction) {
+    match found.entry(tx.hash) {
+        Entry::Vacant(entry) => {
+            entry.insert(tx);
+        }
+        Entry::Occupied(mut entry) => {
+            let known = &mut entry.get_mut().internal_transfers;
+            for transfer in tx.internal_transfers {
+                if !known.iter().any(|t| t.trace_id == transfer.trace_id) {
+                    known.push(transfer);
+                }
+            }
+        }
+    }
+}
+
+/// Value moved by a successful nested call or a self-destruct. The top-level
+/// call is the transaction itself and plain `CALL`s are the only calls that
+/// move value on their own.
+fn internal_transfer(trace: &Trace) -> Option<InternalTransfer> {
+    if trace.error.is_some() {
+        return None;
+    }
+    let trace_id = trace
+        .trace_address
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join("_");
+
+    match &trace.action {
+        Action::Call(call)
+            if !trace.trace_address.is_empty()
+                && call.call_type == CallType::Call
+                && !call.value.is_zero() =>
+        {
+            Some(InternalTransfer {
+                trace_id,
+                from: call.from,
+                to: call.to,
+                value: call.value,
+            })
+        }
+        Action::Suicide(suicide) if !suicide.balance.is_zero() => Some(InternalTransfer {
+            trace_id,
+            from: suicide.address,
+            to: suicide.refund_address,
+            value: suicide.balance,
+        }),
+        _ => None,
+    }
+}
+
+/// An explorer `txlistinternal` row as an internal transfer. Rows without a
+/// trace ID are numbered by their position in the results.
+fn explorer_internal_transfer(
+    tx: &ExplorerTransaction,
+    position: usize,
+) -> Result<Option<InternalTransfer>> {
+    // Contract creations have no recipient
+    if tx.is_error == "1" || tx.to.is_empty() {
+        return Ok(None);
+    }
+    let value = U256::from_dec_str(&tx.value).context("Invalid value from explorer")?;
+    if value.is_zero() {
+        return Ok(None);
+    }
+
+    Ok(Some(InternalTransfer {
+        trace_id: tx
+            .trace_id
+            .clone()
+            .unwrap_or_else(|| format!("explorer_{}", position)),
+        from: tx.from.parse().context("Invalid address from explorer")?,
+        to: tx.to.parse().context("Invalid address from explorer")?,
+        value,
+    }))
+}
+
+/// Sent by the address, or to it directly
+fn touches(tx: &Transaction, address: Address) -> bool {
+    tx.from == address || tx.to == Some(address)
+}
+
+fn parse_explorer_result(data: ExplorerResponse) -> Result<Vec<ExplorerTransaction>> {
+    if data.status == "1" {
+        return serde_json::from_value(data.result)
+            .context("Unexpected block explorer result format");
+    }
+
+    // Etherscan-style APIs report an empty result as status 0
+    if data
+        .message
+        .to_lowercase()
+        .contains("no transactions found")
+    {
+        return Ok(Vec::new());
+    }
+
+    anyhow::bail!(
+        "Block explorer returned an error: {} ({})",
+        data.message,
+        data.result
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_sizer_shrinks_and_grows() {
+        let mut sizer = RangeSizer::new(&DiscoveryConfig {
+            initial_range: 1000,
+            min_range: 100,
+            max_range: 4000,
+            ..DiscoveryConfig::default()
+        });
+
+        assert_eq!(sizer.current(), 1000);
+        assert!(sizer.on_too_large());
+        assert_eq!(sizer.current(), 500);
+        assert!(sizer.on_too_large());
+        assert!(sizer.on_too_large());
+        assert_eq!(sizer.current(), 125);
+        assert!(sizer.on_too_large());
+        assert_eq!(sizer.current(), 100);
+        assert!(!sizer.on_too_large());
+
+        sizer.on_success();
+        assert_eq!(sizer.current(), 200);
+        for _ in 0..10 {
+            sizer.on_success();
+        }
+        assert_eq!(sizer.current(), 4000);
+    }
+
+    #[test]
+    fn test_is_range_too_large() {
+        assert!(is_range_too_large("query returned more than 10000 results"));
+        assert!(is_range_too_large(
+            "(code: -32005, message: Block range is too large)"
+        ));
+        assert!(is_range_too_large("block range too wide, max 1024"));
+        assert!(!is_range_too_large("execution reverted"));
+        assert!(!is_range_too_large("connection refused"));
+    }
+
+    #[test]
+    fn test_block_scan_matches_sender_and_recipient() {
+        let address: Address = "0x00000000000000000000000000000000000000aa"
+            .parse()
+            .unwrap();
+        let other: Address = "0x00000000000000000000000000000000000000bb"
+            .parse()
+            .unwrap();
+
+        let sent = Transaction {
+            from: address,
+            to: Some(other),
+            ..Transaction::default()
+        };
+        let received = Transaction {
+            from: other,
+            to: Some(address),
+            ..Transaction::default()
+        };
+        let deployed = Transaction {
+            from: address,
+            to: None,
+            ..Transaction::default()
+        };
+        let unrelated = Transaction {
+            from: other,
+            to: Some(other),
+            ..Transaction::default()
+        };
+
+        assert!(touches(&sent, address));
+        assert!(touches(&received, address));
+        assert!(touches(&deployed, address));
+        assert!(!touches(&unrelated, address));
+    }
+
+    #[test]
+    fn test_internal_transfers_from_traces_and_explorer() {
+        let trace: Trace = serde_json::from_value(serde_json::json!({
+            "action": {
+                "callType": "call",
+                "from": "0x00000000000000000000000000000000000000cc",
+                "to": "0x00000000000000000000000000000000000000aa",
+                "gas": "0x0",
+                "input": "0x",
+                "value": "0xde0b6b3a7640000"
+            },
+            "blockHash": format!("0x{}", "11".repeat(32)),
+            "blockNumber": 4520,
+            "result": { "gasUsed": "0x0", "output": "0x" },
+            "subtraces": 0,
+            "traceAddress": [0, 1],
+            "transactionHash": format!("0x{}", "ab".repeat(32)),
+            "transactionPosition": 3,
+            "type": "call"
+        }))
+        .unwrap();
+        let transfer = internal_transfer(&trace).unwrap();
+        assert_eq!(transfer.trace_id, "0_1");
+        assert_eq!(transfer.value, U256::exp10(18));
+
+        // The top-level call is the transaction itself
+        let mut top_level = trace.clone();
+        top_level.trace_address.clear();
+        assert!(internal_transfer(&top_level).is_none());
+        let mut reverted = trace;
+        reverted.error = Some("Reverted".to_string());
+        assert!(internal_transfer(&reverted).is_none());
+
+        let row: ExplorerTransaction = serde_json::from_value(serde_json::json!({
+            "hash": format!("0x{}", "ab".repeat(32)),
+            "blockNumber": "4520",
+            "from": "0x00000000000000000000000000000000000000cc",
+            "to": "0x00000000000000000000000000000000000000aa",
+            "value": "1000000000000000000",
+            "traceId": "0_1",
+            "isError": "0"
+        }))
+        .unwrap();
+        assert_eq!(
+            explorer_internal_transfer(&row, 0).unwrap(),
+            Some(transfer.clone())
+        );
+
+        // Found by both sources, recorded once
+        let discovered = |source| DiscoveredTransaction {
+            hash: H256::repeat_byte(0xab),
+            block_number: 4520,
+            source,
+            internal_transfers: vec![transfer.clone()],
+        };
+        let mut found = BTreeMap::new();
+        merge(&mut found, discovered(DiscoverySource::Logs));
+        merge(&mut found, discovered(DiscoverySource::Traces));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[&H256::repeat_byte(0xab)].internal_transfers.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_explorer_result() {
+        let empty = ExplorerResponse {
+            status: "0".to_string(),
+            message: "No transactions found".to_string(),
+            result: serde_json::json!([]),
+        };
+        assert!(parse_explorer_result(empty).unwrap().is_empty());
+
+        let rate_limited = ExplorerResponse {
+            status: "0".to_string(),
+            message: "NOTOK".to_string(),
+            result: serde_json::json!("Max rate limit reached"),
+        };
+        assert!(parse_explorer_result(rate_limited).is_err());
+
+        let ok = ExplorerResponse {
+            status: "1".to_string(),
+            message: "OK".to_string(),
+            result: serde_json::json!([{
+                "hash": "0x5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060",
+                "blockNumber": "4520",
+                "from": "0x0000000000000000000000000000000000000000"
+            }]),
+        };
+        let txs = parse_explorer_result(ok).unwrap();
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].block_number, "4520");
+    }
+}
@@ -58,40 +58,6 @@ impl ERC20Scanner {
         let balance = contract.balance_of(wallet_address).call().await?;
         Ok(balance)
     }
-
-    pub async fn scan_token_transfers(
-        &self,
-        token_address: Address,
-        wallet_address: Address,
-        from_block: u64,
-        to_block: u64,
-    ) -> Result<Vec<TokenTransfer>> {
-        let contract = IERC20::new(token_address, self.provider.clone());
-
-        // Get all Transfer events involving the wallet
-        let filter = contract
-            .transfer_filter()
-            .from_block(from_block)
-            .to_block(to_block);
-
-        let logs = filter.query_with_meta().await?;
-
-        let mut transfers = Vec::new();
-        for (log, meta) in logs {
-            if log.from == wallet_address || log.to == wallet_address {
-                transfers.push(TokenTransfer {
-                    block_number: meta.block_number.as_u64(),
-                    transaction_hash: meta.transaction_hash,
-                    from: log.from,
-                    to: log.to,
-                    value: log.value,
-                    token_address,
-                });
-            }
-        }
-
-        Ok(transfers)
-    }
 }
 
 #[derive(Debug, Clone)]
@@ -107,8 +73,31 @@ pub struct TokenInfo {
 pub struct TokenTransfer {
     pub block_number: u64,
     pub transaction_hash: TxHash,
+    pub log_index: u64,
     pub from: Address,
     pub to: Address,
     pub value: U256,
     pub token_address: Address,
 }
+
+impl TokenTransfer {
+    /// Decode an ERC-20 `Transfer` log. Returns None for logs that share the
+    /// topic but not the layout, such as ERC-721 transfers with an indexed token ID.
+    pub fn from_log(log: Log) -> Option<Self> {
+        let token_address = log.address;
+        let block_number = log.block_number?.as_u64();
+        let transaction_hash = log.transaction_hash?;
+        let log_index = log.log_index?.as_u64();
+        let event: TransferFilter = ethers::contract::parse_log(log).ok()?;
+
+        Some(Self {
+            block_number,
+            transaction_hash,
+            log_index,
+            from: event.from,
+            to: event.to,
+            value: event.value,
+            token_address,
+        })
+    }
+}
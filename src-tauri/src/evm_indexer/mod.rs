@@ -1,17 +1,23 @@
 #![allow(dead_code)]
 
 mod defi;
+mod discovery;
 mod erc20;
+mod token_list;
 
-use crate::core::{Token, Transaction as CoreTransaction};
+use crate::core::{BlockRef, Token, Transaction as CoreTransaction};
 use anyhow::Result;
 use ethers::prelude::*;
 use ethers::providers::{Http, Provider, Ws};
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
 
 pub use defi::{DeFiPosition, DeFiProtocolScanner};
-pub use erc20::ERC20Scanner;
+pub use discovery::{
+    DiscoveredTransaction, DiscoveryConfig, InternalTransfer, TransactionDiscovery,
+};
+pub use erc20::{ERC20Scanner, TokenTransfer};
 
 pub struct EVMIndexer {
     providers: HashMap<String, Arc<Provider<Ws>>>,
@@ -29,6 +35,9 @@ pub struct EVMChainConfig {
     pub native_token: Token,
     pub multicall_address: Option<String>,
     pub substrate_features: bool, // For Moonbeam/Astar specific features
+    /// Discover native transfers by fetching every block when the chain has
+    /// neither an explorer nor `trace_filter`. Only viable for short histories.
+    pub block_scan: bool,
 }
 
 impl EVMIndexer {
@@ -52,6 +61,7 @@ impl EVMIndexer {
                 },
                 multicall_address: Some("0x83e3b61886770de2F64AAcaD2724ED4f08F7f36B".to_string()),
                 substrate_features: true,
+                block_scan: false,
             },
         );
 
@@ -72,6 +82,7 @@ impl EVMIndexer {
                 },
                 multicall_address: Some("0x6477204E12A7236b9619385ea453F370aD897bb2".to_string()),
                 substrate_features: true,
+                block_scan: false,
             },
         );
 
@@ -92,6 +103,7 @@ impl EVMIndexer {
                 },
                 multicall_address: Some("0xd11dfc2ab34abd3e1abfba80b99aefbd6255c4b8".to_string()),
                 substrate_features: true,
+                block_scan: false,
             },
         );
 
@@ -112,6 +124,7 @@ impl EVMIndexer {
                 },
                 multicall_address: None,
                 substrate_features: true,
+                block_scan: false,
             },
         );
 
@@ -134,6 +147,7 @@ impl EVMIndexer {
                 },
                 multicall_address: None,  // To be determined
                 substrate_features: true, // PolkaVM enabled
+                block_scan: false,
             },
         );
 
@@ -160,6 +174,10 @@ impl EVMIndexer {
         Ok(())
     }
 
+    pub fn is_connected(&self, chain: &str) -> bool {
+        self.providers.contains_key(chain)
+    }
+
     pub async fn get_block_number(&self, chain: &str) -> Result<u64> {
         if let Some(provider) = self.providers.get(chain) {
             let block_number = provider.get_block_number().await?;
@@ -172,6 +190,44 @@ impl EVMIndexer {
         }
     }
 
+    /// Number of the block the node reports under the `finalized` tag
+    pub async fn get_finalized_block(&self, chain: &str) -> Result<u64> {
+        let provider = self
+            .providers
+            .get(chain)
+            .ok_or_else(|| anyhow::anyhow!("Provider not connected for chain: {}", chain))?;
+        let block = provider
+            .get_block(BlockNumber::Finalized)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("No finalized block on {}", chain))?;
+
+        block
+            .number
+            .map(|n| n.as_u64())
+            .ok_or_else(|| anyhow::anyhow!("Finalized block on {} has no number", chain))
+    }
+
+    /// Hash and parent hash of the block at `number` on the node's canonical chain
+    pub async fn get_block_ref(&self, chain: &str, number: u64) -> Result<BlockRef> {
+        let provider = self
+            .providers
+            .get(chain)
+            .ok_or_else(|| anyhow::anyhow!("Provider not connected for chain: {}", chain))?;
+        let block = provider
+            .get_block(number)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Block {} not found on {}", number, chain))?;
+        let hash = block
+            .hash
+            .ok_or_else(|| anyhow::anyhow!("Block {} on {} is pending", number, chain))?;
+
+        Ok(BlockRef {
+            number,
+            hash: format!("{:?}", hash),
+            parent_hash: format!("{:?}", block.parent_hash),
+        })
+    }
+
     pub async fn get_balance(&self, chain: &str, address: &str) -> Result<U256> {
         let addr: Address = address.parse()?;
 
@@ -189,31 +245,209 @@ impl EVMIndexer {
         address: &str,
         from_block: u64,
         to_block: u64,
+    ) -> Result<Vec<CoreTransaction>> {
+        let discovered = self
+            .discover_transactions(chain, address, from_block, to_block)
+            .await?;
+        self.get_discovered_transactions(chain, address, &discovered)
+            .await
+    }
+
+    /// Transactions touching `address` in `from_block..=to_block`, without
+    /// fetching them. A sync discovers its whole range once and then fetches
+    /// the discoveries chunk by chunk.
+    pub async fn discover_transactions(
+        &self,
+        chain: &str,
+        address: &str,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<DiscoveredTransaction>> {
+        let addr: Address = address.parse()?;
+        self.get_discovery(chain)?
+            .discover(addr, from_block, to_block)
+            .await
+    }
+
+    /// Fetch the blocks containing `discovered` and convert the transactions
+    /// sent from or to `address`, plus a row per internal transfer in or out
+    /// of it
+    pub async fn get_discovered_transactions(
+        &self,
+        chain: &str,
+        address: &str,
+        discovered: &[DiscoveredTransaction],
     ) -> Result<Vec<CoreTransaction>> {
         let mut transactions = Vec::new();
         let addr: Address = address.parse()?;
 
         if let Some(provider) = self.providers.get(chain) {
-            // Get transactions for the address
-            // This is simplified - in production you'd need to:
-            // 1. Query logs for ERC20 transfers
-            // 2. Get internal transactions
-            // 3. Handle different transaction types
-
-            for block_num in from_block..=to_block {
-                if let Ok(Some(block)) = provider.get_block_with_txs(block_num).await {
+            let wanted: HashMap<H256, &DiscoveredTransaction> =
+                discovered.iter().map(|tx| (tx.hash, tx)).collect();
+            let blocks: BTreeSet<u64> = discovered.iter().map(|tx| tx.block_number).collect();
+
+            for block_num in blocks {
+                if let Some(block) = provider.get_block_with_txs(block_num).await? {
                     for tx in block.transactions {
-                        if tx.from == addr || tx.to == Some(addr) {
-                            transactions.push(self.convert_to_core_transaction(chain, tx)?);
+                        let Some(found) = wanted.get(&tx.hash) else {
+                            continue;
+                        };
+                        let internal: Vec<_> = found
+                            .internal_transfers
+                            .iter()
+                            .filter(|t| t.from == addr || t.to == addr)
+                            .collect();
+
+                        for (position, transfer) in internal.iter().enumerate() {
+                            transactions.push(self.convert_internal_transfer(
+                                chain,
+                                &tx,
+                                position,
+                                transfer,
+                                block.timestamp,
+                            )?);
                         }
+
+                        // Token-only activity is stored per log by get_token_transfers
+                        if tx.from != addr && tx.to != Some(addr) {
+                            continue;
+                        }
+
+                        // The receipt carries gas used, effective gas price and status
+                        let receipt = provider
+                            .get_transaction_receipt(tx.hash)
+                            .await?
+                            .ok_or_else(|| {
+                                anyhow::anyhow!("Missing receipt for transaction {:?}", tx.hash)
+                            })?;
+
+                        transactions.push(self.convert_to_core_transaction(
+                            chain,
+                            tx,
+                            &receipt,
+                            block.timestamp,
+                        )?);
+                    }
+                }
+            }
+        }
+
+        Ok(transactions)
+    }
+
+    /// ERC-20 transfers in or out of `address`, one transaction per log.
+    ///
+    /// Symbols and decimals come from the bundled token list; any other token
+    /// found in the wallet's `Transfer` logs is resolved on-chain.
+    pub async fn get_token_transfers(
+        &self,
+        chain: &str,
+        address: &str,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<CoreTransaction>> {
+        let addr: Address = address.parse()?;
+        let provider = self
+            .providers
+            .get(chain)
+            .ok_or_else(|| anyhow::anyhow!("Provider not connected for chain: {}", chain))?;
+        let scanner = self.get_erc20_scanner(chain)?;
+
+        let mut tokens: HashMap<Address, Token> = HashMap::new();
+        for token in token_list::bundled_tokens(chain)? {
+            if let Some(contract) = token
+                .contract_address
+                .as_deref()
+                .and_then(|a| a.parse().ok())
+            {
+                tokens.insert(contract, token);
+            }
+        }
+
+        let logs = self
+            .get_discovery(chain)?
+            .transfer_logs(addr, from_block, to_block)
+            .await?;
+
+        let mut unresolved: HashSet<Address> = HashSet::new();
+        let mut block_timestamps: HashMap<u64, U256> = HashMap::new();
+        let mut transactions = Vec::new();
+
+        for log in logs {
+            let Some(transfer) = TokenTransfer::from_log(log) else {
+                continue;
+            };
+
+            if unresolved.contains(&transfer.token_address) {
+                continue;
+            }
+            if let Entry::Vacant(entry) = tokens.entry(transfer.token_address) {
+                match scanner.get_token_info(transfer.token_address).await {
+                    Ok(info) => {
+                        entry.insert(Token {
+                            symbol: info.symbol,
+                            decimals: info.decimals,
+                            chain: chain.to_string(),
+                            contract_address: Some(format!("{:?}", info.address)),
+                        });
+                    }
+                    Err(e) => {
+                        // Log error but continue with other tokens
+                        eprintln!("Error resolving token {:?}: {}", transfer.token_address, e);
+                        unresolved.insert(transfer.token_address);
+                        continue;
                     }
                 }
             }
+
+            let block_timestamp = match block_timestamps.get(&transfer.block_number) {
+                Some(timestamp) => *timestamp,
+                None => {
+                    let block = provider
+                        .get_block(transfer.block_number)
+                        .await?
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("Block {} not found", transfer.block_number)
+                        })?;
+                    block_timestamps.insert(transfer.block_number, block.timestamp);
+                    block.timestamp
+                }
+            };
+
+            transactions.push(self.convert_token_transfer(
+                chain,
+                &transfer,
+                &tokens[&transfer.token_address],
+                block_timestamp,
+            )?);
         }
 
+        transactions.sort_by_key(|tx| (tx.block_number, tx.log_index));
         Ok(transactions)
     }
 
+    pub fn get_discovery(&self, chain: &str) -> Result<TransactionDiscovery> {
+        let config = self
+            .chain_configs
+            .get(chain)
+            .ok_or_else(|| anyhow::anyhow!("Unknown chain: {}", chain))?;
+
+        if let Some(provider) = self.providers.get(chain) {
+            Ok(
+                TransactionDiscovery::new(provider.clone(), config.explorer_api.clone())
+                    .with_config(DiscoveryConfig {
+                        block_scan: config.block_scan,
+                        ..DiscoveryConfig::default()
+                    }),
+            )
+        } else {
+            Err(anyhow::anyhow!(
+                "Provider not connected for chain: {}",
+                chain
+            ))
+        }
+    }
+
     pub fn get_erc20_scanner(&self, chain: &str) -> Result<ERC20Scanner> {
         if let Some(provider) = self.providers.get(chain) {
             Ok(ERC20Scanner::new(provider.clone()))
@@ -296,37 +530,293 @@ impl EVMIndexer {
         &self,
         chain: &str,
         tx: ethers::types::Transaction,
+        receipt: &TransactionReceipt,
+        block_timestamp: U256,
     ) -> Result<CoreTransaction> {
         let config = self
             .chain_configs
             .get(chain)
             .ok_or_else(|| anyhow::anyhow!("Unknown chain"))?;
 
+        let timestamp = block_time(block_timestamp)?;
+
+        // Pre-Byzantium receipts have no status field, treat them as successful
+        let succeeded = receipt.status.map(|s| s.as_u64() == 1).unwrap_or(true);
+
+        // EIP-1559 receipts report the price actually paid; legacy ones may not
+        let gas_price = receipt.effective_gas_price.or(tx.gas_price);
+        let fee = match (gas_price, receipt.gas_used) {
+            (Some(price), Some(gas_used)) => Some((price * gas_used).to_string()),
+            _ => None,
+        };
+
+        // A reverted transaction moves no value, only the fee is charged
+        let value = if succeeded { tx.value } else { U256::zero() };
+
+        let mut metadata = serde_json::json!({
+            "nonce": tx.nonce.as_u64(),
+            "gas_limit": tx.gas.as_u64(),
+            "gas_used": receipt.gas_used.map(|g| g.as_u64()),
+            "effective_gas_price": gas_price.map(|p| p.to_string()),
+            "input": format!("0x{}", hex::encode(&tx.input)),
+        });
+        if !succeeded {
+            metadata["attempted_value"] = serde_json::json!(tx.value.to_string());
+        }
+
         Ok(CoreTransaction {
             id: uuid::Uuid::new_v4(),
             profile_id: None,
             chain: chain.to_string(),
             hash: format!("0x{}", hex::encode(tx.hash)),
+            log_index: -1,
             from_address: format!("0x{}", hex::encode(tx.from)),
             to_address: tx.to.map(|a| format!("0x{}", hex::encode(a))),
-            value: tx.value.to_string(),
+            value: value.to_string(),
             token_symbol: config.native_token.symbol.clone(),
             token_decimals: config.native_token.decimals as i32,
-            timestamp: chrono::Utc::now(), // Would need to get from block
+            timestamp,
             block_number: tx.block_number.unwrap_or_default().as_u64() as i64,
             transaction_type: "transfer".to_string(),
+            status: if succeeded { "confirmed" } else { "failed" }.to_string(),
+            fee,
+            metadata,
+            amount_primary: None,
+            primary_currency: None,
+            exchange_rate: None,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        })
+    }
+
+    /// Internal transfers share the parent transaction's hash and take log
+    /// indices -2, -3, ... so they never collide with the parent (-1) or its logs
+    fn convert_internal_transfer(
+        &self,
+        chain: &str,
+        parent: &ethers::types::Transaction,
+        position: usize,
+        transfer: &InternalTransfer,
+        block_timestamp: U256,
+    ) -> Result<CoreTransaction> {
+        let config = self
+            .chain_configs
+            .get(chain)
+            .ok_or_else(|| anyhow::anyhow!("Unknown chain"))?;
+
+        Ok(CoreTransaction {
+            id: uuid::Uuid::new_v4(),
+            profile_id: None,
+            chain: chain.to_string(),
+            hash: format!("0x{}", hex::encode(parent.hash)),
+            log_index: -2 - position as i64,
+            from_address: format!("0x{}", hex::encode(transfer.from)),
+            to_address: Some(format!("0x{}", hex::encode(transfer.to))),
+            value: transfer.value.to_string(),
+            token_symbol: config.native_token.symbol.clone(),
+            token_decimals: config.native_token.decimals as i32,
+            timestamp: block_time(block_timestamp)?,
+            block_number: parent.block_number.unwrap_or_default().as_u64() as i64,
+            transaction_type: "internal_transfer".to_string(),
+            // Reverted calls are dropped during discovery
             status: "confirmed".to_string(),
-            fee: tx.gas_price.map(|gp| {
-                let gas_used = tx.gas;
-                (gp * gas_used).to_string()
+            // The gas fee is recorded on the parent transaction row
+            fee: None,
+            metadata: serde_json::json!({
+                "trace_id": transfer.trace_id,
+                "parent_hash": format!("0x{}", hex::encode(parent.hash)),
             }),
+            amount_primary: None,
+            primary_currency: None,
+            exchange_rate: None,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        })
+    }
+
+    fn convert_token_transfer(
+        &self,
+        chain: &str,
+        transfer: &TokenTransfer,
+        token: &Token,
+        block_timestamp: U256,
+    ) -> Result<CoreTransaction> {
+        Ok(CoreTransaction {
+            id: uuid::Uuid::new_v4(),
+            profile_id: None,
+            chain: chain.to_string(),
+            // Shares the parent transaction's hash, told apart by log index
+            hash: format!("0x{}", hex::encode(transfer.transaction_hash)),
+            log_index: transfer.log_index as i64,
+            from_address: format!("0x{}", hex::encode(transfer.from)),
+            to_address: Some(format!("0x{}", hex::encode(transfer.to))),
+            value: transfer.value.to_string(),
+            token_symbol: token.symbol.clone(),
+            token_decimals: token.decimals as i32,
+            timestamp: block_time(block_timestamp)?,
+            block_number: transfer.block_number as i64,
+            transaction_type: "transfer".to_string(),
+            // Transfer logs are only emitted by successful transactions
+            status: "confirmed".to_string(),
+            // The gas fee is recorded on the parent transaction row
+            fee: None,
             metadata: serde_json::json!({
-                "nonce": tx.nonce.as_u64(),
-                "gas_limit": tx.gas.as_u64(),
-                "input": format!("0x{}", hex::encode(&tx.input)),
+                "token_address": format!("0x{}", hex::encode(transfer.token_address)),
+                "parent_hash": format!("0x{}", hex::encode(transfer.transaction_hash)),
             }),
+            amount_primary: None,
+            primary_currency: None,
+            exchange_rate: None,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
         })
     }
 }
+
+fn block_time(block_timestamp: U256) -> Result<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::from_timestamp(block_timestamp.as_u64() as i64, 0)
+        .ok_or_else(|| anyhow::anyhow!("Invalid block timestamp: {}", block_timestamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_transaction() -> ethers::types::Transaction {
+        ethers::types::Transaction {
+            hash: H256::repeat_byte(0xab),
+            from: Address::repeat_byte(0x11),
+            to: Some(Address::repeat_byte(0x22)),
+            value: U256::exp10(18),
+            gas: U256::from(100_000),
+            gas_price: Some(U256::from(150_000_000_000u64)),
+            block_number: Some(U64::from(4_520_000)),
+            ..Default::default()
+        }
+    }
+
+    fn sample_receipt(status: u64) -> TransactionReceipt {
+        TransactionReceipt {
+            transaction_hash: H256::repeat_byte(0xab),
+            status: Some(U64::from(status)),
+            gas_used: Some(U256::from(21_000)),
+            effective_gas_price: Some(U256::from(125_000_000_000u64)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_convert_uses_receipt_and_block_timestamp() {
+        let indexer = EVMIndexer::new();
+        let tx = indexer
+            .convert_to_core_transaction(
+                "moonbeam",
+                sample_transaction(),
+                &sample_receipt(1),
+                U256::from(1_700_000_000u64),
+            )
+            .unwrap();
+
+        assert_eq!(tx.timestamp.timestamp(), 1_700_000_000);
+        assert_eq!(tx.status, "confirmed");
+        assert_eq!(tx.value, "1000000000000000000");
+        // 21_000 gas used * 125 gwei effective price, not the 100_000 gas limit
+        assert_eq!(tx.fee.as_deref(), Some("2625000000000000"));
+        assert_eq!(tx.metadata["gas_used"], 21_000);
+    }
+
+    #[test]
+    fn test_convert_failed_transaction_keeps_only_fee() {
+        let indexer = EVMIndexer::new();
+        let tx = indexer
+            .convert_to_core_transaction(
+                "moonbeam",
+                sample_transaction(),
+                &sample_receipt(0),
+                U256::from(1_700_000_000u64),
+            )
+            .unwrap();
+
+        assert_eq!(tx.status, "failed");
+        assert_eq!(tx.value, "0");
+        assert_eq!(tx.fee.as_deref(), Some("2625000000000000"));
+        assert_eq!(tx.metadata["attempted_value"], "1000000000000000000");
+    }
+
+    #[test]
+    fn test_convert_internal_transfer() {
+        let transfer = InternalTransfer {
+            trace_id: "0_1".to_string(),
+            from: Address::repeat_byte(0x22),
+            to: Address::repeat_byte(0x11),
+            value: U256::exp10(17),
+        };
+        let tx = EVMIndexer::new()
+            .convert_internal_transfer(
+                "moonbeam",
+                &sample_transaction(),
+                1,
+                &transfer,
+                U256::from(1_700_000_000u64),
+            )
+            .unwrap();
+
+        assert_eq!(tx.hash, format!("0x{}", "ab".repeat(32)));
+        assert_eq!(tx.log_index, -3);
+        assert_eq!(tx.transaction_type, "internal_transfer");
+        assert_eq!(tx.from_address, format!("0x{}", "22".repeat(20)));
+        assert_eq!(tx.value, "100000000000000000");
+        assert_eq!(tx.token_symbol, "GLMR");
+        assert!(tx.fee.is_none());
+        assert_eq!(tx.metadata["trace_id"], "0_1");
+    }
+
+    #[test]
+    fn test_token_transfer_from_log() {
+        let from = Address::repeat_byte(0x11);
+        let to = Address::repeat_byte(0x22);
+        let log = Log {
+            address: Address::repeat_byte(0x33),
+            topics: vec![
+                H256::from(ethers::utils::keccak256(
+                    "Transfer(address,address,uint256)",
+                )),
+                H256::from(from),
+                H256::from(to),
+            ],
+            data: ethers::abi::encode(&[ethers::abi::Token::Uint(U256::from(2_500_000))]).into(),
+            block_number: Some(U64::from(4_520_000)),
+            transaction_hash: Some(H256::repeat_byte(0xab)),
+            log_index: Some(U256::from(7)),
+            ..Default::default()
+        };
+
+        let transfer = TokenTransfer::from_log(log.clone()).unwrap();
+        assert_eq!(transfer.from, from);
+        assert_eq!(transfer.to, to);
+        assert_eq!(transfer.value, U256::from(2_500_000));
+        assert_eq!(transfer.log_index, 7);
+
+        let token = Token {
+            symbol: "USDC".to_string(),
+            decimals: 6,
+            chain: "moonbeam".to_string(),
+            contract_address: Some(format!("{:?}", transfer.token_address)),
+        };
+        let tx = EVMIndexer::new()
+            .convert_token_transfer("moonbeam", &transfer, &token, U256::from(1_700_000_000u64))
+            .unwrap();
+        assert_eq!(tx.hash, format!("0x{}", "ab".repeat(32)));
+        assert_eq!(tx.log_index, 7);
+        assert_eq!(tx.token_symbol, "USDC");
+        assert_eq!(tx.token_decimals, 6);
+        assert_eq!(tx.value, "2500000");
+        assert!(tx.fee.is_none());
+
+        // ERC-721 transfers index the token ID as a fourth topic
+        let mut nft_log = log;
+        nft_log.topics.push(H256::from_low_u64_be(1));
+        nft_log.data = Default::default();
+        assert!(TokenTransfer::from_log(nft_log).is_none());
+    }
+}
@@ -0,0 +1,71 @@
+#![allow(dead_code)]
+
+use crate::core::Token;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct TokenList {
+    name: String,
+    tokens: Vec<TokenListEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenListEntry {
+    address: String,
+    symbol: String,
+    name: String,
+    decimals: u8,
+    #[serde(rename = "logoURI")]
+    logo_uri: Option<String>,
+}
+
+/// Tokens from the bundled `resources/token-lists/<chain>-tokens.json`
+pub fn bundled_tokens(chain: &str) -> Result<Vec<Token>> {
+    let json = match chain {
+        "moonbeam" => include_str!("../../resources/token-lists/moonbeam-tokens.json"),
+        "moonriver" => include_str!("../../resources/token-lists/moonriver-tokens.json"),
+        "astar" => include_str!("../../resources/token-lists/astar-tokens.json"),
+        _ => return Ok(Vec::new()),
+    };
+
+    let list: TokenList = serde_json::from_str(json)
+        .with_context(|| format!("Invalid bundled token list for {}", chain))?;
+
+    Ok(list
+        .tokens
+        .into_iter()
+        .map(|entry| Token {
+            symbol: entry.symbol,
+            decimals: entry.decimals,
+            chain: chain.to_string(),
+            contract_address: Some(entry.address),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::Address;
+
+    #[test]
+    fn test_bundled_token_lists_parse() {
+        for chain in ["moonbeam", "moonriver", "astar"] {
+            let tokens = bundled_tokens(chain).unwrap();
+            assert!(!tokens.is_empty(), "{} token list is empty", chain);
+
+            for token in tokens {
+                let address = token.contract_address.unwrap();
+                assert!(
+                    address.parse::<Address>().is_ok(),
+                    "{} has invalid address {}",
+                    chain,
+                    address
+                );
+            }
+        }
+
+        assert!(bundled_tokens("acala-evm").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+use subxt::ext::scale_value::{Composite, Primitive, Value, ValueDef};
+
+/// Raw 32-byte Substrate account ID
+pub type AccountBytes = [u8; 32];
+
+/// Pallets whose presence in an extrinsic marks balance movements as XCM transfers
+pub const XCM_PALLETS: &[&str] = &[
+    "XcmPallet",
+    "PolkadotXcm",
+    "XTokens",
+    "MessageQueue",
+    "XcmpQueue",
+    "DmpQueue",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementKind {
+    Transfer,
+    StakingReward,
+    Stake,
+    Unstake,
+    Slash,
+    XcmTransfer,
+}
+
+impl MovementKind {
+    /// Value stored in `transactions.transaction_type`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MovementKind::Transfer => "transfer",
+            MovementKind::StakingReward => "staking_reward",
+            MovementKind::Stake => "stake",
+            MovementKind::Unstake => "unstake",
+            MovementKind::Slash => "slash",
+            MovementKind::XcmTransfer => "xcm_transfer",
+        }
+    }
+}
+
+/// A balance movement involving the indexed account, decoded from one event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movement {
+    pub kind: MovementKind,
+    pub from: Option<AccountBytes>,
+    pub to: Option<AccountBytes>,
+    pub amount: u128,
+    pub asset_id: Option<String>,
+}
+
+/// Map a decoded event to a movement of `account`'s funds.
+///
+/// `in_xcm_context` is true when the same extrinsic (or block phase) also
+/// emitted an event from one of the [`XCM_PALLETS`].
+pub fn classify_event<T>(
+    pallet: &str,
+    variant: &str,
+    fields: &Composite<T>,
+    account: &AccountBytes,
+    in_xcm_context: bool,
+) -> Option<Movement> {
+    let involves = |who: &Option<AccountBytes>| who.as_ref() == Some(account);
+
+    let movement = match (pallet, variant) {
+        ("Balances", "Transfer") => Movement {
+            kind: if in_xcm_context {
+                MovementKind::XcmTransfer
+            } else {
+                MovementKind::Transfer
+            },
+            from: account_field(fields, "from"),
+            to: account_field(fields, "to"),
+            amount: u128_field(fields, "amount")?,
+            asset_id: None,
+        },
+        ("Assets", "Transferred") => Movement {
+            kind: MovementKind::Transfer,
+            from: account_field(fields, "from"),
+            to: account_field(fields, "to"),
+            amount: u128_field(fields, "amount")?,
+            asset_id: Some(field(fields, "asset_id")?.to_string()),
+        },
+        ("Balances", "Minted") if in_xcm_context => Movement {
+            kind: MovementKind::XcmTransfer,
+            from: None,
+            to: account_field(fields, "who"),
+            amount: u128_field(fields, "amount")?,
+            asset_id: None,
+        },
+        ("Balances", "Burned") if in_xcm_context => Movement {
+            kind: MovementKind::XcmTransfer,
+            from: account_field(fields, "who"),
+            to: None,
+            amount: u128_field(fields, "amount")?,
+            asset_id: None,
+        },
+        ("Staking", "Rewarded") => Movement {
+            kind: MovementKind::StakingReward,
+            from: None,
+            to: account_field(fields, "stash"),
+            amount: u128_field(fields, "amount")?,
+            asset_id: None,
+        },
+        ("Staking", "Bonded") => Movement {
+            kind: MovementKind::Stake,
+            from: account_field(fields, "stash"),
+            to: None,
+            amount: u128_field(fields, "amount")?,
+            asset_id: None,
+        },
+        ("Staking", "Unbonded") => Movement {
+            kind: MovementKind::Unstake,
+            from: None,
+            to: account_field(fields, "stash"),
+            amount: u128_field(fields, "amount")?,
+            asset_id: None,
+        },
+        ("Staking", "Slashed") => Movement {
+            kind: MovementKind::Slash,
+            from: account_field(fields, "staker"),
+            to: None,
+            amount: u128_field(fields, "amount")?,
+            asset_id: None,
+        },
+        _ => return None,
+    };
+
+    if involves(&movement.from) || involves(&movement.to) {
+        Some(movement)
+    } else {
+        None
+    }
+}
+
+/// Fee charged to `account` by a `TransactionPayment.TransactionFeePaid` event.
+/// `actual_fee` already includes the tip.
+pub fn fee_paid<T>(
+    pallet: &str,
+    variant: &str,
+    fields: &Composite<T>,
+    account: &AccountBytes,
+) -> Option<u128> {
+    if (pallet, variant) != ("TransactionPayment", "TransactionFeePaid") {
+        return None;
+    }
+
+    if account_field(fields, "who").as_ref() != Some(account) {
+        return None;
+    }
+
+    u128_field(fields, "actual_fee")
+}
+
+/// Decode `Assets.Metadata` storage into `(symbol, decimals)`
+pub fn decode_asset_metadata<T>(value: &Value<T>) -> Option<(String, i32)> {
+    let ValueDef::Composite(fields) = &value.value else {
+        return None;
+    };
+
+    let mut symbol = Vec::new();
+    collect_bytes(field(fields, "symbol")?, &mut symbol)?;
+    let decimals = u128_field(fields, "decimals")?;
+
+    Some((
+        String::from_utf8(symbol).ok()?,
+        i32::try_from(decimals).ok()?,
+    ))
+}
+
+fn field<'a, T>(fields: &'a Composite<T>, name: &str) -> Option<&'a Value<T>> {
+    match fields {
+        Composite::Named(named) => named.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+        Composite::Unnamed(_) => None,
+    }
+}
+
+fn u128_field<T>(fields: &Composite<T>, name: &str) -> Option<u128> {
+    field(fields, name)?.as_u128()
+}
+
+fn account_field<T>(fields: &Composite<T>, name: &str) -> Option<AccountBytes> {
+    let mut bytes = Vec::with_capacity(32);
+    collect_bytes(field(fields, name)?, &mut bytes)?;
+    bytes.try_into().ok()
+}
+
+/// AccountId32 decodes as (possibly nested) composites of 32 `u8` primitives
+fn collect_bytes<T>(value: &Value<T>, out: &mut Vec<u8>) -> Option<()> {
+    match &value.value {
+        ValueDef::Composite(composite) => {
+            for inner in composite.values() {
+                collect_bytes(inner, out)?;
+            }
+            Some(())
+        }
+        ValueDef::Primitive(Primitive::U128(n)) => {
+            out.push(u8::try_from(*n).ok()?);
+            Some(())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: AccountBytes = [1u8; 32];
+    const BOB: AccountBytes = [2u8; 32];
+
+    fn account(bytes: AccountBytes) -> Value<()> {
+        Value::unnamed_composite(vec![Value::unnamed_composite(
+            bytes.iter().map(|b| Value::u128(*b as u128)),
+        )])
+    }
+
+    fn transfer(from: AccountBytes, to: AccountBytes, amount: u128) -> Composite<()> {
+        Composite::Named(vec![
+            ("from".to_string(), account(from)),
+            ("to".to_string(), account(to)),
+            ("amount".to_string(), Value::u128(amount)),
+        ])
+    }
+
+    #[test]
+    fn test_classify_balances_transfer() {
+        let fields = transfer(ALICE, BOB, 10_000_000_000);
+
+        let movement = classify_event("Balances", "Transfer", &fields, &ALICE, false).unwrap();
+        assert_eq!(movement.kind, MovementKind::Transfer);
+        assert_eq!(movement.from, Some(ALICE));
+        assert_eq!(movement.to, Some(BOB));
+        assert_eq!(movement.amount, 10_000_000_000);
+
+        // Same event seen from an unrelated account
+        assert!(classify_event("Balances", "Transfer", &fields, &[3u8; 32], false).is_none());
+
+        // Inside an XCM extrinsic the transfer goes to a sovereign account
+        let movement = classify_event("Balances", "Transfer", &fields, &BOB, true).unwrap();
+        assert_eq!(movement.kind, MovementKind::XcmTransfer);
+    }
+
+    #[test]
+    fn test_classify_staking_reward() {
+        let fields = Composite::Named(vec![
+            ("stash".to_string(), account(ALICE)),
+            ("dest".to_string(), Value::unnamed_variant("Staked", vec![])),
+            ("amount".to_string(), Value::u128(42)),
+        ]);
+
+        let movement = classify_event("Staking", "Rewarded", &fields, &ALICE, false).unwrap();
+        assert_eq!(movement.kind, MovementKind::StakingReward);
+        assert_eq!(movement.to, Some(ALICE));
+        assert_eq!(movement.amount, 42);
+    }
+
+    #[test]
+    fn test_minted_only_counts_inside_xcm() {
+        let fields = Composite::Named(vec![
+            ("who".to_string(), account(ALICE)),
+            ("amount".to_string(), Value::u128(5)),
+        ]);
+
+        assert!(classify_event("Balances", "Minted", &fields, &ALICE, false).is_none());
+        let movement = classify_event("Balances", "Minted", &fields, &ALICE, true).unwrap();
+        assert_eq!(movement.kind, MovementKind::XcmTransfer);
+    }
+
+    #[test]
+    fn test_decode_asset_metadata() {
+        let metadata = Value::named_composite(vec![
+            ("deposit", Value::u128(0)),
+            (
+                "name",
+                Value::unnamed_composite(b"Tether USD".iter().map(|b| Value::u128(*b as u128))),
+            ),
+            (
+                "symbol",
+                Value::unnamed_composite(b"USDT".iter().map(|b| Value::u128(*b as u128))),
+            ),
+            ("decimals", Value::u128(6)),
+            ("is_frozen", Value::bool(false)),
+        ]);
+
+        assert_eq!(
+            decode_asset_metadata(&metadata),
+            Some(("USDT".to_string(), 6))
+        );
+        assert_eq!(decode_asset_metadata(&Value::u128(1)), None);
+    }
+
+    #[test]
+    fn test_fee_paid() {
+        let fields = Composite::Named(vec![
+            ("who".to_string(), account(ALICE)),
+            ("actual_fee".to_string(), Value::u128(156_000_000)),
+            ("tip".to_string(), Value::u128(0)),
+        ]);
+
+        assert_eq!(
+            fee_paid("TransactionPayment", "TransactionFeePaid", &fields, &ALICE),
+            Some(156_000_000)
+        );
+        assert_eq!(
+            fee_paid("TransactionPayment", "TransactionFeePaid", &fields, &BOB),
+            None
+        );
+    }
+}
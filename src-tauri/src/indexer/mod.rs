@@ -1,15 +1,55 @@
 #![allow(dead_code)]
 
-use crate::core::{ChainConfig, Transaction};
-use anyhow::Result;
-use std::collections::HashMap;
-use subxt::{OnlineClient, PolkadotConfig};
+mod events;
+
+use crate::core::{BlockRef, ChainConfig, Transaction};
+use anyhow::{Context, Result};
+use events::{classify_event, decode_asset_metadata, fee_paid, AccountBytes, XCM_PALLETS};
+use sp_core::crypto::{AccountId32, Ss58AddressFormat, Ss58Codec};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use subxt::backend::legacy::LegacyRpcMethods;
+use subxt::backend::rpc::{rpc_params, RpcClient};
+use subxt::events::{Events, Phase};
+use subxt::ext::codec::Decode;
+use subxt::ext::scale_value::Composite;
+use subxt::utils::H256;
+use subxt::{Metadata, OnlineClient, PolkadotConfig};
 
 pub struct PolkadotIndexer {
     clients: HashMap<String, OnlineClient<PolkadotConfig>>,
+    rpcs: HashMap<String, LegacyRpcMethods<PolkadotConfig>>,
+    /// Raw RPC access for calls the legacy methods do not cover
+    rpc_clients: HashMap<String, RpcClient>,
     configs: HashMap<String, ChainConfig>,
 }
 
+/// An event decoded once per block so it can be matched against the account
+struct DecodedEvent {
+    index: u32,
+    pallet: String,
+    variant: String,
+    fields: Composite<u32>,
+}
+
+/// Events grouped by block phase: initialization, extrinsic index, finalization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum PhaseKey {
+    Initialization,
+    Extrinsic(u32),
+    Finalization,
+}
+
+impl From<Phase> for PhaseKey {
+    fn from(phase: Phase) -> Self {
+        match phase {
+            Phase::Initialization => PhaseKey::Initialization,
+            Phase::ApplyExtrinsic(index) => PhaseKey::Extrinsic(index),
+            Phase::Finalization => PhaseKey::Finalization,
+        }
+    }
+}
+
 impl PolkadotIndexer {
     pub fn new() -> Self {
         let mut configs = HashMap::new();
@@ -53,15 +93,27 @@ impl PolkadotIndexer {
 
         Self {
             clients: HashMap::new(),
+            rpcs: HashMap::new(),
+            rpc_clients: HashMap::new(),
             configs,
         }
     }
 
     pub async fn connect(&mut self, chain: &str) -> Result<()> {
+        if self.clients.contains_key(chain) {
+            return Ok(());
+        }
+
         if let Some(config) = self.configs.get(chain) {
             let url = config.ws_endpoint.as_ref().unwrap_or(&config.rpc_endpoint);
-            let client = OnlineClient::<PolkadotConfig>::from_url(url).await?;
+            // Share one connection between the client and the legacy RPC methods
+            let rpc_client = RpcClient::from_url(url).await?;
+            let client =
+                OnlineClient::<PolkadotConfig>::from_rpc_client(rpc_client.clone()).await?;
             self.clients.insert(chain.to_string(), client);
+            self.rpcs
+                .insert(chain.to_string(), LegacyRpcMethods::new(rpc_client.clone()));
+            self.rpc_clients.insert(chain.to_string(), rpc_client);
         }
         Ok(())
     }
@@ -75,25 +127,443 @@ impl PolkadotIndexer {
         }
     }
 
+    /// Number of the last GRANDPA-finalized block
+    pub async fn get_finalized_block(&self, chain: &str) -> Result<u32> {
+        let rpc = self
+            .rpcs
+            .get(chain)
+            .ok_or_else(|| anyhow::anyhow!("Chain not connected"))?;
+        let hash = rpc.chain_get_finalized_head().await?;
+        let header = rpc
+            .chain_get_header(Some(hash))
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Finalized header {:?} not found", hash))?;
+
+        Ok(header.number)
+    }
+
+    /// Hash and parent hash of the block at `number` on the current best chain
+    pub async fn get_block_ref(&self, chain: &str, number: u32) -> Result<BlockRef> {
+        let rpc = self
+            .rpcs
+            .get(chain)
+            .ok_or_else(|| anyhow::anyhow!("Chain not connected"))?;
+        let hash = rpc
+            .chain_get_block_hash(Some(number.into()))
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Block {} not found on {}", number, chain))?;
+        let header = rpc
+            .chain_get_header(Some(hash))
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Header {:?} not found on {}", hash, chain))?;
+
+        Ok(BlockRef {
+            number: number as u64,
+            hash: format!("{:?}", hash),
+            parent_hash: format!("{:?}", header.parent_hash),
+        })
+    }
+
+    /// Decode every block in `from_block..=to_block` and return the balances,
+    /// assets, staking and XCM movements involving `address`, ordered by block
+    /// and event index.
+    ///
+    /// Each block's events are decoded with the metadata of the runtime that
+    /// produced them. Runtimes older than metadata V14 cannot be decoded, so
+    /// the account's start block has to come after them.
     pub async fn fetch_account_transactions(
         &self,
-        _chain: &str,
-        _address: &str,
-        _from_block: Option<u32>,
-        _to_block: Option<u32>,
+        chain: &str,
+        address: &str,
+        from_block: u32,
+        to_block: Option<u32>,
     ) -> Result<Vec<Transaction>> {
-        // Implementation would query the chain for transactions
-        // This is a simplified version
-        let transactions = Vec::new();
+        let rpc = self
+            .rpcs
+            .get(chain)
+            .ok_or_else(|| anyhow::anyhow!("Chain not connected"))?;
+
+        let (account_id, format) = AccountId32::from_ss58check_with_version(address)
+            .map_err(|e| anyhow::anyhow!("Invalid SS58 address {}: {:?}", address, e))?;
+        let account: AccountBytes = account_id.into();
+
+        let to_block = match to_block {
+            Some(block) => block,
+            None => self.get_latest_block(chain).await?,
+        };
 
-        // TODO: Implement actual transaction fetching
-        // - Query extrinsics
-        // - Filter by account
-        // - Parse transaction data
-        // - Convert to our Transaction type
+        let mut transactions = Vec::new();
+        let mut asset_metadata = HashMap::new();
+        let mut runtimes = HashMap::new();
+
+        for number in from_block..=to_block {
+            let hash = rpc
+                .chain_get_block_hash(Some(number.into()))
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Block {} not found on {}", number, chain))?;
+            let metadata = self
+                .runtime_metadata(chain, hash, &mut runtimes)
+                .await
+                .with_context(|| format!("Cannot decode block {} on {}", number, chain))?;
+            let phases = self.block_events(rpc, hash, number, metadata).await?;
+
+            transactions.extend(
+                self.index_block(
+                    chain,
+                    hash,
+                    number,
+                    phases,
+                    &account,
+                    format,
+                    &mut asset_metadata,
+                )
+                .await?,
+            );
+        }
 
         Ok(transactions)
     }
+
+    /// Metadata of the runtime active at `hash`, fetched once per spec version
+    async fn runtime_metadata<'a>(
+        &self,
+        chain: &str,
+        hash: H256,
+        runtimes: &'a mut HashMap<u32, Metadata>,
+    ) -> Result<&'a Metadata> {
+        let (rpc, rpc_client) = match (self.rpcs.get(chain), self.rpc_clients.get(chain)) {
+            (Some(rpc), Some(rpc_client)) => (rpc, rpc_client),
+            _ => return Err(anyhow::anyhow!("Chain not connected")),
+        };
+        let spec_version = rpc
+            .state_get_runtime_version(Some(hash))
+            .await?
+            .spec_version;
+
+        match runtimes.entry(spec_version) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let encoded: String = rpc_client
+                    .request("state_getMetadata", rpc_params![hash])
+                    .await?;
+                let bytes = hex::decode(encoded.trim_start_matches("0x"))?;
+                let metadata = Metadata::decode(&mut &bytes[..]).with_context(|| {
+                    format!(
+                        "Metadata of spec version {} is older than V14; start the sync after this runtime",
+                        spec_version
+                    )
+                })?;
+                Ok(entry.insert(metadata))
+            }
+        }
+    }
+
+    /// `System.Events` of a block grouped by phase. An event that does not
+    /// decode is skipped; events are not length-prefixed, so one that cannot
+    /// be located ends the block's list.
+    async fn block_events(
+        &self,
+        rpc: &LegacyRpcMethods<PolkadotConfig>,
+        hash: H256,
+        number: u32,
+        metadata: &Metadata,
+    ) -> Result<BTreeMap<PhaseKey, Vec<DecodedEvent>>> {
+        let bytes = rpc
+            .state_get_storage(&storage_key("System", "Events"), Some(hash))
+            .await?
+            .unwrap_or_default();
+        let events = Events::<PolkadotConfig>::decode_from(bytes, metadata.clone());
+
+        let mut phases: BTreeMap<PhaseKey, Vec<DecodedEvent>> = BTreeMap::new();
+        for event in events.iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("Skipping undecodable events in block {}: {}", number, e);
+                    break;
+                }
+            };
+            let fields = match event.field_values() {
+                Ok(fields) => fields,
+                Err(e) => {
+                    eprintln!(
+                        "Skipping event {}.{} in block {}: {}",
+                        event.pallet_name(),
+                        event.variant_name(),
+                        number,
+                        e
+                    );
+                    continue;
+                }
+            };
+
+            phases
+                .entry(event.phase().into())
+                .or_default()
+                .push(DecodedEvent {
+                    index: event.index(),
+                    pallet: event.pallet_name().to_string(),
+                    variant: event.variant_name().to_string(),
+                    fields,
+                });
+        }
+
+        Ok(phases)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn index_block(
+        &self,
+        chain: &str,
+        hash: H256,
+        number: u32,
+        phases: BTreeMap<PhaseKey, Vec<DecodedEvent>>,
+        account: &AccountBytes,
+        format: Ss58AddressFormat,
+        asset_metadata: &mut HashMap<String, (String, i32)>,
+    ) -> Result<Vec<Transaction>> {
+        let (client, rpc) = match (self.clients.get(chain), self.rpcs.get(chain)) {
+            (Some(client), Some(rpc)) => (client, rpc),
+            _ => return Err(anyhow::anyhow!("Chain not connected")),
+        };
+        let config = self
+            .configs
+            .get(chain)
+            .ok_or_else(|| anyhow::anyhow!("Unknown chain: {}", chain))?;
+
+        // Only fetch extrinsics and the timestamp for blocks that touch the account
+        let mut matches = Vec::new();
+        for (phase, events) in &phases {
+            let in_xcm_context = events
+                .iter()
+                .any(|e| XCM_PALLETS.contains(&e.pallet.as_str()));
+            let failed = events
+                .iter()
+                .any(|e| e.pallet == "System" && e.variant == "ExtrinsicFailed");
+            let fee = events.iter().find_map(|e| {
+                fee_paid(&e.pallet, &e.variant, &e.fields, account).map(|f| (e.index, f))
+            });
+
+            let movements: Vec<_> = events
+                .iter()
+                .filter_map(|e| {
+                    classify_event(&e.pallet, &e.variant, &e.fields, account, in_xcm_context)
+                        .map(|m| (e.index, e, m))
+                })
+                .collect();
+
+            // The fee is paid in the native token, so only a native movement can carry it
+            let fee_carrier = movements.iter().position(|(_, _, m)| m.asset_id.is_none());
+            if fee_carrier.is_none() {
+                // A failed, fee-only or assets-only extrinsic still costs the signer its fee
+                if let Some((fee_index, fee)) = fee {
+                    matches.push((*phase, fee_index, None, Some(fee), failed));
+                }
+            }
+
+            for (position, (event_index, event, movement)) in movements.into_iter().enumerate() {
+                let fee = if Some(position) == fee_carrier {
+                    fee.map(|(_, f)| f)
+                } else {
+                    None
+                };
+                matches.push((*phase, event_index, Some((event, movement)), fee, false));
+            }
+        }
+
+        if matches.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let block_hash = format!("{:?}", hash);
+        let block_number = number as i64;
+        let timestamp = self.block_timestamp(rpc, hash).await?;
+        let extrinsic_hashes = self.extrinsic_hashes(rpc, hash).await?;
+        let to_ss58 =
+            |who: &AccountBytes| AccountId32::from(*who).to_ss58check_with_version(format);
+
+        let mut transactions = Vec::new();
+        for (phase, event_index, found, fee, failed) in matches {
+            let (extrinsic_index, tx_hash) = match phase {
+                PhaseKey::Extrinsic(index) => (
+                    Some(index),
+                    extrinsic_hashes
+                        .get(&index)
+                        .cloned()
+                        .unwrap_or_else(|| block_hash.clone()),
+                ),
+                _ => (None, block_hash.clone()),
+            };
+
+            let mut metadata = serde_json::json!({
+                "block_hash": block_hash,
+                "extrinsic_index": extrinsic_index,
+                "event_index": event_index,
+            });
+            if fee.is_some() {
+                // Movements such as staking payouts carry a fee the indexed account paid
+                metadata["fee_payer"] = serde_json::json!(to_ss58(account));
+            }
+
+            let transaction = match found {
+                Some((event, movement)) => {
+                    let (token_symbol, token_decimals) = match &movement.asset_id {
+                        Some(asset_id) => {
+                            metadata["asset_id"] = serde_json::json!(asset_id);
+                            self.asset_metadata(client, hash, asset_id, asset_metadata)
+                                .await
+                        }
+                        None => (config.symbol.clone(), config.decimals as i32),
+                    };
+                    metadata["pallet"] = serde_json::json!(event.pallet);
+                    metadata["event"] = serde_json::json!(event.variant);
+
+                    Transaction {
+                        id: uuid::Uuid::new_v4(),
+                        profile_id: None,
+                        chain: chain.to_string(),
+                        hash: tx_hash,
+                        log_index: event_index as i64,
+                        from_address: movement.from.as_ref().map(to_ss58).unwrap_or_default(),
+                        to_address: movement.to.as_ref().map(to_ss58),
+                        value: movement.amount.to_string(),
+                        token_symbol,
+                        token_decimals,
+                        timestamp,
+                        block_number,
+                        transaction_type: movement.kind.as_str().to_string(),
+                        status: "confirmed".to_string(),
+                        fee: fee.map(|f| f.to_string()),
+                        metadata,
+                        amount_primary: None,
+                        primary_currency: None,
+                        exchange_rate: None,
+                        created_at: chrono::Utc::now(),
+                        updated_at: chrono::Utc::now(),
+                    }
+                }
+                None => {
+                    metadata["pallet"] = serde_json::json!("TransactionPayment");
+                    metadata["event"] = serde_json::json!("TransactionFeePaid");
+
+                    Transaction {
+                        id: uuid::Uuid::new_v4(),
+                        profile_id: None,
+                        chain: chain.to_string(),
+                        hash: tx_hash,
+                        log_index: event_index as i64,
+                        from_address: to_ss58(account),
+                        to_address: None,
+                        value: "0".to_string(),
+                        token_symbol: config.symbol.clone(),
+                        token_decimals: config.decimals as i32,
+                        timestamp,
+                        block_number,
+                        transaction_type: "fee".to_string(),
+                        status: if failed { "failed" } else { "confirmed" }.to_string(),
+                        fee: fee.map(|f| f.to_string()),
+                        metadata,
+                        amount_primary: None,
+                        primary_currency: None,
+                        exchange_rate: None,
+                        created_at: chrono::Utc::now(),
+                        updated_at: chrono::Utc::now(),
+                    }
+                }
+            };
+
+            transactions.push(transaction);
+        }
+
+        // Deterministic order regardless of phase grouping
+        transactions.sort_by_key(|tx| tx.metadata["event_index"].as_u64());
+        Ok(transactions)
+    }
+
+    /// Block time from `Timestamp.Now`, stored in milliseconds
+    async fn block_timestamp(
+        &self,
+        rpc: &LegacyRpcMethods<PolkadotConfig>,
+        hash: H256,
+    ) -> Result<chrono::DateTime<chrono::Utc>> {
+        let bytes = rpc
+            .state_get_storage(&storage_key("Timestamp", "Now"), Some(hash))
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Timestamp.Now missing at block {:?}", hash))?;
+        let millis = u64::decode(&mut &bytes[..])?;
+
+        chrono::DateTime::from_timestamp_millis(millis as i64)
+            .ok_or_else(|| anyhow::anyhow!("Invalid block timestamp: {}", millis))
+    }
+
+    /// Extrinsic hashes keyed by index: blake2_256 of the length-prefixed
+    /// encoding, which is how the block body stores each extrinsic
+    async fn extrinsic_hashes(
+        &self,
+        rpc: &LegacyRpcMethods<PolkadotConfig>,
+        hash: H256,
+    ) -> Result<HashMap<u32, String>> {
+        let block = rpc
+            .chain_get_block(Some(hash))
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Block {:?} not found", hash))?;
+
+        Ok(block
+            .block
+            .extrinsics
+            .iter()
+            .enumerate()
+            .map(|(index, extrinsic)| {
+                (
+                    index as u32,
+                    format!(
+                        "0x{}",
+                        hex::encode(sp_core::hashing::blake2_256(&extrinsic.0))
+                    ),
+                )
+            })
+            .collect())
+    }
+
+    /// Symbol and decimals for an `Assets` pallet asset, cached per sync run.
+    /// Unknown assets fall back to their ID with raw (0-decimal) units.
+    async fn asset_metadata(
+        &self,
+        client: &OnlineClient<PolkadotConfig>,
+        hash: H256,
+        asset_id: &str,
+        cache: &mut HashMap<String, (String, i32)>,
+    ) -> (String, i32) {
+        if let Some(cached) = cache.get(asset_id) {
+            return cached.clone();
+        }
+
+        let mut metadata = None;
+        if let Ok(id) = asset_id.parse::<u128>() {
+            let query = subxt::dynamic::storage(
+                "Assets",
+                "Metadata",
+                vec![subxt::dynamic::Value::u128(id)],
+            );
+            if let Ok(Some(value)) = client.storage().at(hash).fetch(&query).await {
+                metadata = value
+                    .to_value()
+                    .ok()
+                    .and_then(|value| decode_asset_metadata(&value));
+            }
+        }
+
+        let metadata = metadata.unwrap_or_else(|| (format!("ASSET-{}", asset_id), 0));
+        cache.insert(asset_id.to_string(), metadata.clone());
+        metadata
+    }
+}
+
+/// Key of a plain storage value: twox128(pallet) ++ twox128(item)
+fn storage_key(pallet: &str, item: &str) -> Vec<u8> {
+    let mut key = sp_core::hashing::twox_128(pallet.as_bytes()).to_vec();
+    key.extend_from_slice(&sp_core::hashing::twox_128(item.as_bytes()));
+    key
 }
 
 #[cfg(test)]
@@ -106,4 +576,12 @@ mod tests {
         assert!(indexer.configs.contains_key("polkadot"));
         assert!(indexer.configs.contains_key("kusama"));
     }
+
+    #[test]
+    fn test_storage_key() {
+        assert_eq!(
+            hex::encode(storage_key("System", "Events")),
+            "26aa394eea5630e07c48ae0c9558cef780d41e5e16056765bc8461851072c9d7"
+        );
+    }
 }
@@ -0,0 +1,408 @@
+#![allow(dead_code)]
+
+mod rules;
+mod statements;
+
+pub use rules::{journal_entries, DEFAULT_ACCOUNTS};
+pub use statements::{AccountBalance, BalanceSheet, IncomeStatement};
+
+use crate::tax::LedgerInputs;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Pool, Sqlite};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Account classification, matching the `type` of the frontend chart of accounts
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum AccountType {
+    #[serde(rename = "asset")]
+    Asset,
+    #[serde(rename = "liability")]
+    Liability,
+    #[serde(rename = "equity")]
+    Equity,
+    #[serde(rename = "income", alias = "revenue")]
+    Income,
+    #[serde(rename = "expense")]
+    Expense,
+}
+
+impl AccountType {
+    /// Assets and expenses grow with debits, everything else with credits
+    pub fn is_debit_normal(&self) -> bool {
+        matches!(self, AccountType::Asset | AccountType::Expense)
+    }
+}
+
+impl std::fmt::Display for AccountType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccountType::Asset => write!(f, "asset"),
+            AccountType::Liability => write!(f, "liability"),
+            AccountType::Equity => write!(f, "equity"),
+            AccountType::Income => write!(f, "income"),
+            AccountType::Expense => write!(f, "expense"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct LedgerAccount {
+    pub code: String,
+    pub name: String,
+    pub account_type: AccountType,
+    pub is_active: bool,
+}
+
+/// One debit or credit line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Posting {
+    pub account_code: String,
+    pub debit: Decimal,
+    pub credit: Decimal,
+    pub asset: Option<String>,
+}
+
+impl Posting {
+    pub fn debit(account_code: &str, amount: Decimal, asset: Option<&str>) -> Self {
+        Self {
+            account_code: account_code.to_string(),
+            debit: amount,
+            credit: Decimal::ZERO,
+            asset: asset.map(str::to_string),
+        }
+    }
+
+    pub fn credit(account_code: &str, amount: Decimal, asset: Option<&str>) -> Self {
+        Self {
+            account_code: account_code.to_string(),
+            debit: Decimal::ZERO,
+            credit: amount,
+            asset: asset.map(str::to_string),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: Uuid,
+    /// Source transaction, `None` for manual entries
+    pub transaction_id: Option<Uuid>,
+    pub date: DateTime<Utc>,
+    pub description: String,
+    pub currency: String,
+    pub postings: Vec<Posting>,
+}
+
+impl JournalEntry {
+    /// Check that the entry has postings, none negative, and debits equal credits
+    pub fn validate(&self) -> Result<()> {
+        if self.postings.len() < 2 {
+            anyhow::bail!("Journal entry {} needs at least two postings", self.id);
+        }
+
+        for posting in &self.postings {
+            if posting.debit.is_sign_negative() || posting.credit.is_sign_negative() {
+                anyhow::bail!(
+                    "Journal entry {} has a negative amount on {}",
+                    self.id,
+                    posting.account_code
+                );
+            }
+            if !posting.debit.is_zero() && !posting.credit.is_zero() {
+                anyhow::bail!(
+                    "Journal entry {} debits and credits {} on one line",
+                    self.id,
+                    posting.account_code
+                );
+            }
+        }
+
+        let debits: Decimal = self.postings.iter().map(|p| p.debit).sum();
+        let credits: Decimal = self.postings.iter().map(|p| p.credit).sum();
+        if debits != credits {
+            anyhow::bail!(
+                "Journal entry {} is unbalanced: debits {} != credits {}",
+                self.id,
+                debits,
+                credits
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Double-entry general ledger of the profiles in the database
+pub struct Ledger {
+    pool: Pool<Sqlite>,
+}
+
+impl Ledger {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+
+    /// Create the default accounts a profile is missing
+    pub async fn ensure_default_accounts(&self, profile_id: &str) -> Result<()> {
+        for (code, name, account_type) in DEFAULT_ACCOUNTS {
+            sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO ledger_accounts (profile_id, code, name, account_type)
+                VALUES (?, ?, ?, ?)
+                "#,
+            )
+            .bind(profile_id)
+            .bind(code)
+            .bind(name)
+            .bind(account_type.to_string())
+            .execute(&self.pool)
+            .await
+            .context("Failed to create ledger account")?;
+        }
+
+        Ok(())
+    }
+
+    /// Add or rename an account in a profile's chart of accounts
+    pub async fn upsert_account(&self, profile_id: &str, account: &LedgerAccount) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO ledger_accounts (profile_id, code, name, account_type, is_active)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(profile_id, code) DO UPDATE SET
+                name = excluded.name,
+                account_type = excluded.account_type,
+                is_active = excluded.is_active,
+                updated_at = datetime('now')
+            "#,
+        )
+        .bind(profile_id)
+        .bind(&account.code)
+        .bind(&account.name)
+        .bind(account.account_type.to_string())
+        .bind(account.is_active)
+        .execute(&self.pool)
+        .await
+        .context("Failed to save ledger account")?;
+
+        Ok(())
+    }
+
+    pub async fn get_accounts(&self, profile_id: &str) -> Result<Vec<LedgerAccount>> {
+        let accounts = sqlx::query_as::<_, LedgerAccount>(
+            "SELECT code, name, account_type, is_active FROM ledger_accounts
+             WHERE profile_id = ? ORDER BY code",
+        )
+        .bind(profile_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to fetch ledger accounts")?;
+
+        Ok(accounts)
+    }
+
+    /// Validate and store a journal entry
+    pub async fn post_entry(&self, profile_id: &str, entry: &JournalEntry) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        insert_entry(&mut tx, profile_id, entry).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Regenerate the journal entries of a profile's transactions.
+    /// Manual entries are kept.
+    pub async fn generate_entries(&self, profile_id: &str) -> Result<usize> {
+        self.ensure_default_accounts(profile_id).await?;
+
+        let inputs = LedgerInputs::load(&self.pool, profile_id).await?;
+        let lots = inputs.build_ledger()?;
+        let entries = journal_entries(&inputs, &lots)?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "DELETE FROM journal_entries WHERE profile_id = ? AND transaction_id IS NOT NULL",
+        )
+        .bind(profile_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to clear generated journal entries")?;
+        for entry in &entries {
+            insert_entry(&mut tx, profile_id, entry).await?;
+        }
+        tx.commit().await?;
+
+        Ok(entries.len())
+    }
+
+    /// Debit and credit totals per account for entries within the period
+    pub async fn trial_balance(
+        &self,
+        profile_id: &str,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<AccountBalance>> {
+        let accounts = self.get_accounts(profile_id).await?;
+        let postings = self.postings(profile_id, from, to).await?;
+
+        statements::trial_balance(&accounts, &postings)
+    }
+
+    /// Assets, liabilities and equity as of `as_of`, with net income to date in equity
+    pub async fn balance_sheet(
+        &self,
+        profile_id: &str,
+        as_of: DateTime<Utc>,
+    ) -> Result<BalanceSheet> {
+        let balances = self.trial_balance(profile_id, None, Some(as_of)).await?;
+
+        Ok(statements::balance_sheet(as_of, balances))
+    }
+
+    /// Income and expenses of the period
+    pub async fn income_statement(
+        &self,
+        profile_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<IncomeStatement> {
+        let balances = self.trial_balance(profile_id, Some(from), Some(to)).await?;
+
+        Ok(statements::income_statement(from, to, balances))
+    }
+
+    async fn postings(
+        &self,
+        profile_id: &str,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Posting>> {
+        let rows = sqlx::query_as::<_, PostingRow>(
+            r#"
+            SELECT p.account_code, p.debit, p.credit, p.asset
+            FROM journal_postings p
+            JOIN journal_entries e ON e.id = p.entry_id
+            WHERE e.profile_id = ?
+              AND (? IS NULL OR e.entry_date >= ?)
+              AND (? IS NULL OR e.entry_date <= ?)
+            "#,
+        )
+        .bind(profile_id)
+        .bind(from)
+        .bind(from)
+        .bind(to)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to fetch journal postings")?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(Posting {
+                    debit: Decimal::from_str(&row.debit)?,
+                    credit: Decimal::from_str(&row.credit)?,
+                    account_code: row.account_code,
+                    asset: row.asset,
+                })
+            })
+            .collect()
+    }
+}
+
+async fn insert_entry(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    profile_id: &str,
+    entry: &JournalEntry,
+) -> Result<()> {
+    entry.validate()?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO journal_entries (id, profile_id, transaction_id, entry_date, description, currency)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(entry.id.to_string())
+    .bind(profile_id)
+    .bind(entry.transaction_id.map(|id| id.to_string()))
+    .bind(entry.date)
+    .bind(&entry.description)
+    .bind(&entry.currency)
+    .execute(&mut **tx)
+    .await
+    .context("Failed to save journal entry")?;
+
+    for (line, posting) in entry.postings.iter().enumerate() {
+        sqlx::query(
+            r#"
+            INSERT INTO journal_postings (entry_id, line, profile_id, account_code, debit, credit, asset)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(entry.id.to_string())
+        .bind(line as i64)
+        .bind(profile_id)
+        .bind(&posting.account_code)
+        .bind(posting.debit.to_string())
+        .bind(posting.credit.to_string())
+        .bind(&posting.asset)
+        .execute(&mut **tx)
+        .await
+        .with_context(|| format!("Failed to save posting to {}", posting.account_code))?;
+    }
+
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct PostingRow {
+    account_code: String,
+    debit: String,
+    credit: String,
+    asset: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(postings: Vec<Posting>) -> JournalEntry {
+        JournalEntry {
+            id: Uuid::new_v4(),
+            transaction_id: None,
+            date: Utc::now(),
+            description: "Test".to_string(),
+            currency: "USD".to_string(),
+            postings,
+        }
+    }
+
+    #[test]
+    fn test_validate_entry() {
+        let balanced = entry(vec![
+            Posting::debit("5906", Decimal::from(2), None),
+            Posting::credit("1500", Decimal::from(2), Some("GLMR")),
+        ]);
+        assert!(balanced.validate().is_ok());
+
+        let unbalanced = entry(vec![
+            Posting::debit("5906", Decimal::from(2), None),
+            Posting::credit("1500", Decimal::from(3), None),
+        ]);
+        assert!(unbalanced.validate().is_err());
+
+        let single = entry(vec![Posting::debit("5906", Decimal::ZERO, None)]);
+        assert!(single.validate().is_err());
+
+        let negative = entry(vec![
+            Posting::debit("5906", Decimal::from(-2), None),
+            Posting::credit("1500", Decimal::from(-2), None),
+        ]);
+        assert!(negative.validate().is_err());
+    }
+}
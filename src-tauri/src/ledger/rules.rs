@@ -0,0 +1,219 @@
+use super::{AccountType, JournalEntry, Posting};
+use crate::core::Transaction;
+use crate::tax::{
+    flow, income_category, normalize_address, own_moves, quantity, Flow, LedgerInputs, LotLedger,
+};
+use anyhow::Result;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+pub const DIGITAL_ASSETS: &str = "1500";
+pub const OWNER_EQUITY: &str = "3000";
+pub const STAKING_INCOME: &str = "4320";
+pub const YIELD_INCOME: &str = "4330";
+pub const AIRDROP_INCOME: &str = "4345";
+pub const REALIZED_GAINS: &str = "4360";
+pub const TRANSACTION_FEES: &str = "5906";
+pub const REALIZED_LOSSES: &str = "5925";
+
+/// Accounts the generated entries post to, codes taken from the US GAAP templates
+pub const DEFAULT_ACCOUNTS: &[(&str, &str, AccountType)] = &[
+    (DIGITAL_ASSETS, "Digital Assets", AccountType::Asset),
+    (OWNER_EQUITY, "Owner's Equity", AccountType::Equity),
+    (STAKING_INCOME, "Staking Rewards", AccountType::Income),
+    (YIELD_INCOME, "Yield Farming Income", AccountType::Income),
+    (AIRDROP_INCOME, "Airdrop Income", AccountType::Income),
+    (
+        REALIZED_GAINS,
+        "Realized Gains on Digital Assets",
+        AccountType::Income,
+    ),
+    (TRANSACTION_FEES, "Transaction Fees", AccountType::Expense),
+    (
+        REALIZED_LOSSES,
+        "Realized Losses on Digital Assets",
+        AccountType::Expense,
+    ),
+];
+
+/// Turn a profile's transactions into balanced journal entries.
+///
+/// Amounts are fiat values in the lot ledger's currency: acquisitions at their
+/// value on receipt, disposals at the cost basis of the lots they consumed.
+/// Zero lines are dropped, so transactions without a fiat value produce no entry.
+pub fn journal_entries(inputs: &LedgerInputs, lots: &LotLedger) -> Result<Vec<JournalEntry>> {
+    let owned: HashSet<String> = inputs.owned.iter().map(|a| normalize_address(a)).collect();
+    let moves = own_moves(&inputs.transactions, &owned)?;
+    let disposals: HashMap<Uuid, _> = lots
+        .disposals
+        .iter()
+        .map(|d| (d.transaction_id, d))
+        .collect();
+    let fee_disposals: HashMap<Uuid, _> = lots
+        .fee_disposals
+        .iter()
+        .map(|d| (d.transaction_id, d))
+        .collect();
+    let lot_costs: HashMap<Uuid, Decimal> = lots
+        .lots
+        .iter()
+        .map(|l| (l.transaction_id, l.cost_basis))
+        .collect();
+
+    let mut entries = Vec::new();
+    for tx in &inputs.transactions {
+        let asset = Some(tx.token_symbol.as_str());
+        let mut postings = Vec::new();
+
+        match flow(tx, &owned, &moves) {
+            Some(Flow::Acquisition) => {
+                let value = lot_costs.get(&tx.id).copied().unwrap_or_default();
+                let source = match income_category(tx, &inputs.tags) {
+                    Some("staking") => STAKING_INCOME,
+                    Some("airdrop") => AIRDROP_INCOME,
+                    Some(_) => YIELD_INCOME,
+                    // Funds brought in from outside the profile
+                    None => OWNER_EQUITY,
+                };
+                postings.push(Posting::debit(DIGITAL_ASSETS, value, asset));
+                postings.push(Posting::credit(source, value, None));
+            }
+            Some(Flow::Disposal) => {
+                if let Some(disposal) = disposals.get(&tx.id) {
+                    postings.push(Posting::debit(OWNER_EQUITY, disposal.proceeds, None));
+                    postings.push(Posting::credit(DIGITAL_ASSETS, disposal.cost_basis, asset));
+                    postings.push(realized(disposal.gain));
+                }
+            }
+            None => {}
+        }
+
+        // The fee is expensed at its value and leaves the assets at the cost
+        // of the lots it spent
+        if let Some(fee) = fee_disposals.get(&tx.id) {
+            postings.push(Posting::debit(TRANSACTION_FEES, fee.proceeds, None));
+            postings.push(Posting::credit(DIGITAL_ASSETS, fee.cost_basis, asset));
+            postings.push(realized(fee.gain));
+        }
+
+        postings.retain(|p| !p.debit.is_zero() || !p.credit.is_zero());
+        if postings.is_empty() {
+            continue;
+        }
+
+        let entry = JournalEntry {
+            id: Uuid::new_v4(),
+            transaction_id: Some(tx.id),
+            date: tx.timestamp,
+            description: describe(tx),
+            currency: lots.fiat_currency.clone(),
+            postings,
+        };
+        entry.validate()?;
+        entries.push(entry);
+    }
+
+    entries.sort_by_key(|e| e.date);
+    Ok(entries)
+}
+
+/// Realised gain, or loss when negative
+fn realized(gain: Decimal) -> Posting {
+    if gain.is_sign_positive() {
+        Posting::credit(REALIZED_GAINS, gain, None)
+    } else {
+        Posting::debit(REALIZED_LOSSES, -gain, None)
+    }
+}
+
+fn describe(tx: &Transaction) -> String {
+    let amount = quantity(tx)
+        .map(|q| q.to_string())
+        .unwrap_or_else(|_| tx.value.clone());
+
+    format!(
+        "{} {} {} on {} ({})",
+        tx.transaction_type, amount, tx.token_symbol, tx.chain, tx.hash
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::currency::CostBasisMethod;
+    use chrono::{TimeZone, Utc};
+
+    const ME: &str = "0x1111111111111111111111111111111111111111";
+    const EXCHANGE: &str = "0x2222222222222222222222222222222222222222";
+
+    fn tx(day: u32, kind: &str, from: &str, to: &str, glmr: i64, usd: &str) -> Transaction {
+        Transaction {
+            id: Uuid::new_v4(),
+            profile_id: Some("profile-1".to_string()),
+            chain: "moonbeam".to_string(),
+            hash: format!("0x{:064x}", day),
+            log_index: -1,
+            from_address: from.to_string(),
+            to_address: Some(to.to_string()),
+            value: (glmr as i128 * 10_i128.pow(18)).to_string(),
+            token_symbol: "GLMR".to_string(),
+            token_decimals: 18,
+            timestamp: Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(),
+            block_number: day as i64,
+            transaction_type: kind.to_string(),
+            status: "confirmed".to_string(),
+            fee: None,
+            metadata: serde_json::json!({}),
+            amount_primary: Some(usd.to_string()),
+            primary_currency: Some("USD".to_string()),
+            exchange_rate: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_entries_balance_and_classify() {
+        let mut sale = tx(3, "transfer", ME, EXCHANGE, 100, "30");
+        // 0.5 GLMR at the sale's 0.30 USD
+        sale.fee = Some((5 * 10_i128.pow(17)).to_string());
+
+        let inputs = LedgerInputs {
+            method: CostBasisMethod::Fifo,
+            fiat_currency: "USD".to_string(),
+            owned: vec![ME.to_string()],
+            tags: HashMap::new(),
+            transactions: vec![
+                tx(1, "transfer", EXCHANGE, ME, 200, "40"),
+                tx(2, "staking_reward", "", ME, 10, "3"),
+                sale,
+            ],
+        };
+        let lots = inputs.build_ledger().unwrap();
+        let entries = journal_entries(&inputs, &lots).unwrap();
+        assert_eq!(entries.len(), 3);
+
+        assert_eq!(
+            entries[1].postings,
+            vec![
+                Posting::debit(DIGITAL_ASSETS, Decimal::from(3), Some("GLMR")),
+                Posting::credit(STAKING_INCOME, Decimal::from(3), None),
+            ]
+        );
+
+        // Proceeds 30 against a cost of 20, plus the fee worth 0.15 that
+        // spent 0.10 of lot cost
+        assert_eq!(
+            entries[2].postings,
+            vec![
+                Posting::debit(OWNER_EQUITY, Decimal::from(30), None),
+                Posting::credit(DIGITAL_ASSETS, Decimal::from(20), Some("GLMR")),
+                Posting::credit(REALIZED_GAINS, Decimal::from(10), None),
+                Posting::debit(TRANSACTION_FEES, Decimal::new(15, 2), None),
+                Posting::credit(DIGITAL_ASSETS, Decimal::new(10, 2), Some("GLMR")),
+                Posting::credit(REALIZED_GAINS, Decimal::new(5, 2), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use super::{AccountType, LedgerAccount, Posting};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Totals of one account, `balance` signed by its normal side
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountBalance {
+    pub code: String,
+    pub name: String,
+    pub account_type: AccountType,
+    pub debit: Decimal,
+    pub credit: Decimal,
+    pub balance: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceSheet {
+    pub as_of: DateTime<Utc>,
+    pub assets: Vec<AccountBalance>,
+    pub liabilities: Vec<AccountBalance>,
+    pub equity: Vec<AccountBalance>,
+    /// Income less expenses not yet closed into an equity account
+    pub retained_earnings: Decimal,
+    pub total_assets: Decimal,
+    pub total_liabilities: Decimal,
+    pub total_equity: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IncomeStatement {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub income: Vec<AccountBalance>,
+    pub expenses: Vec<AccountBalance>,
+    pub total_income: Decimal,
+    pub total_expenses: Decimal,
+    pub net_income: Decimal,
+}
+
+/// Sum postings per account. Fails if total debits and credits differ.
+pub fn trial_balance(
+    accounts: &[LedgerAccount],
+    postings: &[Posting],
+) -> Result<Vec<AccountBalance>> {
+    let mut balances: BTreeMap<&str, AccountBalance> = accounts
+        .iter()
+        .map(|account| {
+            (
+                account.code.as_str(),
+                AccountBalance {
+                    code: account.code.clone(),
+                    name: account.name.clone(),
+                    account_type: account.account_type,
+                    debit: Decimal::ZERO,
+                    credit: Decimal::ZERO,
+                    balance: Decimal::ZERO,
+                },
+            )
+        })
+        .collect();
+
+    for posting in postings {
+        let Some(balance) = balances.get_mut(posting.account_code.as_str()) else {
+            anyhow::bail!("Posting to unknown account {}", posting.account_code);
+        };
+        balance.debit += posting.debit;
+        balance.credit += posting.credit;
+    }
+
+    let mut total_debit = Decimal::ZERO;
+    let mut total_credit = Decimal::ZERO;
+    for balance in balances.values_mut() {
+        total_debit += balance.debit;
+        total_credit += balance.credit;
+        balance.balance = if balance.account_type.is_debit_normal() {
+            balance.debit - balance.credit
+        } else {
+            balance.credit - balance.debit
+        };
+    }
+    if total_debit != total_credit {
+        anyhow::bail!(
+            "Ledger is out of balance: debits {} != credits {}",
+            total_debit,
+            total_credit
+        );
+    }
+
+    Ok(balances.into_values().collect())
+}
+
+pub fn balance_sheet(as_of: DateTime<Utc>, balances: Vec<AccountBalance>) -> BalanceSheet {
+    let retained_earnings = net_income(&balances);
+    let assets = of_type(&balances, AccountType::Asset);
+    let liabilities = of_type(&balances, AccountType::Liability);
+    let equity = of_type(&balances, AccountType::Equity);
+
+    BalanceSheet {
+        as_of,
+        total_assets: total(&assets),
+        total_liabilities: total(&liabilities),
+        total_equity: total(&equity) + retained_earnings,
+        retained_earnings,
+        assets,
+        liabilities,
+        equity,
+    }
+}
+
+pub fn income_statement(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    balances: Vec<AccountBalance>,
+) -> IncomeStatement {
+    let income = of_type(&balances, AccountType::Income);
+    let expenses = of_type(&balances, AccountType::Expense);
+
+    IncomeStatement {
+        from,
+        to,
+        total_income: total(&income),
+        total_expenses: total(&expenses),
+        net_income: net_income(&balances),
+        income,
+        expenses,
+    }
+}
+
+fn of_type(balances: &[AccountBalance], account_type: AccountType) -> Vec<AccountBalance> {
+    balances
+        .iter()
+        .filter(|b| b.account_type == account_type)
+        .cloned()
+        .collect()
+}
+
+fn total(balances: &[AccountBalance]) -> Decimal {
+    balances.iter().map(|b| b.balance).sum()
+}
+
+fn net_income(balances: &[AccountBalance]) -> Decimal {
+    total(&of_type(balances, AccountType::Income)) - total(&of_type(balances, AccountType::Expense))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::DEFAULT_ACCOUNTS;
+
+    #[test]
+    fn test_statements_balance() {
+        let accounts: Vec<LedgerAccount> = DEFAULT_ACCOUNTS
+            .iter()
+            .map(|(code, name, account_type)| LedgerAccount {
+                code: code.to_string(),
+                name: name.to_string(),
+                account_type: *account_type,
+                is_active: true,
+            })
+            .collect();
+
+        // Contribute 20, sell for 30 with a 10 gain, pay a 1 fee
+        let postings = vec![
+            Posting::debit("1500", Decimal::from(20), Some("GLMR")),
+            Posting::credit("3000", Decimal::from(20), None),
+            Posting::debit("3000", Decimal::from(30), None),
+            Posting::credit("1500", Decimal::from(20), Some("GLMR")),
+            Posting::credit("4360", Decimal::from(10), None),
+            Posting::debit("5906", Decimal::from(1), None),
+            Posting::credit("1500", Decimal::from(1), Some("GLMR")),
+            Posting::debit("1500", Decimal::from(5), Some("GLMR")),
+            Posting::credit("4320", Decimal::from(5), None),
+        ];
+
+        let balances = trial_balance(&accounts, &postings).unwrap();
+        let now = Utc::now();
+
+        let statement = income_statement(now, now, balances.clone());
+        assert_eq!(statement.total_income, Decimal::from(15));
+        assert_eq!(statement.total_expenses, Decimal::from(1));
+        assert_eq!(statement.net_income, Decimal::from(14));
+
+        let sheet = balance_sheet(now, balances);
+        assert_eq!(sheet.total_assets, Decimal::from(4));
+        assert_eq!(sheet.retained_earnings, Decimal::from(14));
+        assert_eq!(
+            sheet.total_assets,
+            sheet.total_liabilities + sheet.total_equity
+        );
+
+        let unbalanced = [Posting::debit("1500", Decimal::from(1), None)];
+        assert!(trial_balance(&accounts, &unbalanced).is_err());
+    }
+}
@@ -3,14 +3,20 @@ mod core;
 mod db;
 mod evm_indexer;
 mod indexer;
+mod ledger;
+mod startup;
 mod sync;
+mod tax;
 
+use crate::core::secrets::SecretStore;
 use evm_indexer::EVMIndexer;
-use tauri::State;
+use std::sync::Arc;
+use sync::SyncScheduler;
+use tauri::{Manager, State};
 use tokio::sync::Mutex;
 
-// Global EVM indexer state
-type EVMIndexerState = Mutex<EVMIndexer>;
+// Global EVM indexer state, shared with background sync jobs
+type EVMIndexerState = Arc<Mutex<EVMIndexer>>;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
@@ -133,45 +139,69 @@ async fn scan_defi_positions(
         .collect())
 }
 
-#[tauri::command]
-async fn sync_evm_transactions(
-    state: State<'_, EVMIndexerState>,
-    chain: String,
-    address: String,
-) -> Result<String, String> {
-    // Get latest block and sync from last 1000 blocks
-    let indexer = state.lock().await;
-    let latest_block = indexer
-        .get_block_number(&chain)
-        .await
-        .map_err(|e| e.to_string())?;
-    let from_block = latest_block.saturating_sub(1000);
-
-    let transactions = indexer
-        .get_transactions(&chain, &address, from_block, latest_block)
-        .await
-        .map_err(|e| e.to_string())?;
-
-    Ok(format!("Synced {} transactions", transactions.len()))
-}
-
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .manage(EVMIndexerState::new(EVMIndexer::new()))
+        .manage(EVMIndexerState::new(Mutex::new(EVMIndexer::new())))
+        .manage(SyncScheduler::new())
+        .manage(Arc::new(SecretStore::new()))
+        .setup(|app| {
+            let status = startup::bootstrap(app);
+            app.manage(status);
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             greet,
+            api::startup::get_startup_error,
             connect_evm_chain,
             get_evm_balance,
             get_evm_token_balances,
             get_evm_transactions,
             scan_defi_positions,
-            sync_evm_transactions,
             api::export::export_transactions_csv,
             api::export::export_tax_report,
             api::backup::create_backup,
-            api::backup::restore_backup
+            api::backup::restore_backup,
+            api::currency::get_account_settings,
+            api::currency::update_account_settings,
+            api::currency::convert_transactions,
+            api::currency::get_transaction_conversions,
+            api::profiles::list_profiles,
+            api::profiles::create_profile,
+            api::profiles::rename_profile,
+            api::profiles::set_profile_avatar,
+            api::profiles::set_profile_archived,
+            api::profiles::delete_profile,
+            api::profiles::list_accounts,
+            api::profiles::add_account,
+            api::profiles::rename_account,
+            api::profiles::remove_account,
+            api::ledger::get_ledger_accounts,
+            api::ledger::generate_journal_entries,
+            api::ledger::get_trial_balance,
+            api::ledger::get_balance_sheet,
+            api::ledger::get_income_statement,
+            api::rates::list_manual_rates,
+            api::rates::add_manual_rate,
+            api::rates::import_manual_rates,
+            api::rates::delete_manual_rate,
+            api::rates::get_price_history,
+            api::rates::backfill_price_history,
+            api::secrets::get_secrets_status,
+            api::secrets::unlock_secrets,
+            api::secrets::lock_secrets,
+            api::secrets::set_price_feed_api_key,
+            api::secrets::get_configured_api_keys,
+            api::secrets::rotate_secrets_key,
+            api::sync::start_sync,
+            api::sync::cancel_sync,
+            api::sync::get_running_syncs,
+            api::sync::get_sync_states,
+            api::sync::set_sync_start_block,
+            api::transactions::query_transactions,
+            api::transactions::get_transaction_tags,
+            api::transactions::set_transaction_tags
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,50 @@
+use crate::core::currency_service::CurrencyService;
+use crate::core::secrets::SecretStore;
+use crate::core::substrate_currency::SubstrateCurrencyHandler;
+use crate::db::Database;
+use crate::sync::SyncManager;
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use tauri::{App, Manager};
+use tokio::sync::Mutex;
+
+/// Outcome of `bootstrap`, managed whether or not it succeeded so the
+/// frontend can show why the app cannot start
+pub struct StartupStatus {
+    pub error: Option<String>,
+}
+
+/// Open the database and register it and the services built on it as
+/// managed state
+pub fn bootstrap(app: &mut App) -> StartupStatus {
+    match register_services(app) {
+        Ok(()) => StartupStatus { error: None },
+        Err(e) => {
+            eprintln!("Startup failed: {:#}", e);
+            StartupStatus {
+                error: Some(format!("{:#}", e)),
+            }
+        }
+    }
+}
+
+fn register_services(app: &mut App) -> Result<()> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .context("Failed to locate the app data directory")?;
+    let db = tauri::async_runtime::block_on(Database::open(&data_dir))?;
+
+    let secrets = app.state::<Arc<SecretStore>>().inner().clone();
+    app.manage(Arc::new(CurrencyService::with_secrets(
+        db.pool.clone(),
+        secrets,
+    )));
+    app.manage(Arc::new(SubstrateCurrencyHandler::new(db.pool.clone())));
+    app.manage(Arc::new(SyncManager::new(Arc::new(Mutex::new(Database {
+        pool: db.pool.clone(),
+    })))));
+    app.manage(db);
+
+    Ok(())
+}
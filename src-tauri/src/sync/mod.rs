@@ -1,12 +1,177 @@
 #![allow(dead_code)]
 
-use crate::core::{SyncStatus, Transaction};
+mod reorg;
+mod scheduler;
+
+pub use scheduler::{SyncJob, SyncScheduler, SYNC_EVENT};
+
+use crate::core::address::validate_address;
+use crate::core::{BlockRef, SyncStatus, Transaction};
 use crate::db::Database;
+use crate::evm_indexer::{DiscoveredTransaction, EVMIndexer};
 use crate::indexer::PolkadotIndexer;
-use anyhow::Result;
-use std::sync::Arc;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, Pool, Sqlite};
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
 use tokio::sync::Mutex;
 
+/// Blocks fetched per chunk, the checkpoint moves after each one
+pub const EVM_CHUNK_BLOCKS: u64 = 2_000;
+pub const SUBSTRATE_CHUNK_BLOCKS: u64 = 100;
+
+/// What the sync loop needs from a chain
+trait ChainSource {
+    async fn current_block(&self) -> Result<u64>;
+    /// Highest block that can no longer be reorganized
+    async fn finalized_block(&self) -> Result<u64>;
+    async fn block_ref(&self, number: u64) -> Result<BlockRef>;
+    /// Called once with the whole range before it is fetched in chunks
+    async fn prepare(&self, _from: u64, _to: u64) -> Result<()> {
+        Ok(())
+    }
+    async fn fetch(&self, from: u64, to: u64) -> Result<Vec<Transaction>>;
+}
+
+struct SubstrateSource<'a> {
+    indexer: &'a Mutex<PolkadotIndexer>,
+    chain: &'a str,
+    address: &'a str,
+}
+
+impl ChainSource for SubstrateSource<'_> {
+    async fn current_block(&self) -> Result<u64> {
+        Ok(self
+            .indexer
+            .lock()
+            .await
+            .get_latest_block(self.chain)
+            .await? as u64)
+    }
+
+    async fn finalized_block(&self) -> Result<u64> {
+        Ok(self
+            .indexer
+            .lock()
+            .await
+            .get_finalized_block(self.chain)
+            .await? as u64)
+    }
+
+    async fn block_ref(&self, number: u64) -> Result<BlockRef> {
+        let indexer = self.indexer.lock().await;
+        indexer
+            .get_block_ref(self.chain, u32::try_from(number)?)
+            .await
+    }
+
+    async fn fetch(&self, from: u64, to: u64) -> Result<Vec<Transaction>> {
+        let indexer = self.indexer.lock().await;
+        indexer
+            .fetch_account_transactions(
+                self.chain,
+                self.address,
+                u32::try_from(from)?,
+                Some(u32::try_from(to)?),
+            )
+            .await
+    }
+}
+
+/// The indexer is only locked for one call at a time, so other commands
+/// can use it while a sync runs. Transactions are discovered once for the
+/// whole range, each chunk then fetches its share of the discoveries.
+struct EvmSource<'a> {
+    indexer: &'a Mutex<EVMIndexer>,
+    chain: &'a str,
+    address: &'a str,
+    discovered: StdMutex<Vec<DiscoveredTransaction>>,
+}
+
+impl ChainSource for EvmSource<'_> {
+    async fn current_block(&self) -> Result<u64> {
+        self.indexer.lock().await.get_block_number(self.chain).await
+    }
+
+    async fn finalized_block(&self) -> Result<u64> {
+        self.indexer
+            .lock()
+            .await
+            .get_finalized_block(self.chain)
+            .await
+    }
+
+    async fn block_ref(&self, number: u64) -> Result<BlockRef> {
+        self.indexer
+            .lock()
+            .await
+            .get_block_ref(self.chain, number)
+            .await
+    }
+
+    async fn prepare(&self, from: u64, to: u64) -> Result<()> {
+        let discovered = self
+            .indexer
+            .lock()
+            .await
+            .discover_transactions(self.chain, self.address, from, to)
+            .await?;
+        *self.discovered.lock().unwrap() = discovered;
+        Ok(())
+    }
+
+    async fn fetch(&self, from: u64, to: u64) -> Result<Vec<Transaction>> {
+        let discovered: Vec<_> = self
+            .discovered
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|tx| (from..=to).contains(&tx.block_number))
+            .cloned()
+            .collect();
+
+        let indexer = self.indexer.lock().await;
+        let mut transactions = indexer
+            .get_discovered_transactions(self.chain, self.address, &discovered)
+            .await?;
+        transactions.extend(
+            indexer
+                .get_token_transfers(self.chain, self.address, from, to)
+                .await?,
+        );
+        Ok(transactions)
+    }
+}
+
+/// Sync checkpoint and last error of one row in `accounts`
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AccountSyncState {
+    pub account_id: String,
+    pub chain: String,
+    pub address: String,
+    pub start_block: i64,
+    /// `None` until the first chunk is saved
+    pub last_synced_block: Option<i64>,
+    pub finalized_block: i64,
+    pub last_sync_time: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+}
+
+impl AccountSyncState {
+    /// Next block to fetch
+    fn next_block(&self) -> u64 {
+        let start = self.start_block as u64;
+        match self.last_synced_block {
+            Some(block) => (block as u64 + 1).max(start),
+            None => start,
+        }
+    }
+}
+
 pub struct SyncManager {
     db: Arc<Mutex<Database>>,
     indexer: Arc<Mutex<PolkadotIndexer>>,
@@ -26,83 +191,338 @@ impl SyncManager {
         address: &str,
         profile_id: &str,
     ) -> Result<SyncStatus> {
-        let mut indexer = self.indexer.lock().await;
+        self.sync_account_chunked(chain, address, profile_id, &AtomicBool::new(false), |_| {})
+            .await
+    }
 
+    /// Sync a Substrate address from its checkpoint to the current head,
+    /// reporting progress after every chunk. Stops between chunks once
+    /// `cancel` is set.
+    pub async fn sync_account_chunked(
+        &self,
+        chain: &str,
+        address: &str,
+        profile_id: &str,
+        cancel: &AtomicBool,
+        on_progress: impl FnMut(&SyncStatus),
+    ) -> Result<SyncStatus> {
         // Connect to chain if not already connected
-        indexer.connect(chain).await?;
+        self.indexer.lock().await.connect(chain).await?;
+
+        let source = SubstrateSource {
+            indexer: &self.indexer,
+            chain,
+            address,
+        };
+        self.sync_chunks(
+            &source,
+            profile_id,
+            chain,
+            address,
+            SUBSTRATE_CHUNK_BLOCKS,
+            cancel,
+            on_progress,
+        )
+        .await
+    }
 
-        // Get current block height
-        let current_block = indexer.get_latest_block(chain).await?;
+    /// Sync native and ERC-20 activity of an EVM address into the database
+    pub async fn sync_evm_account(
+        &self,
+        evm_indexer: &Mutex<EVMIndexer>,
+        chain: &str,
+        address: &str,
+        profile_id: &str,
+    ) -> Result<SyncStatus> {
+        self.sync_evm_account_chunked(
+            evm_indexer,
+            chain,
+            address,
+            profile_id,
+            &AtomicBool::new(false),
+            |_| {},
+        )
+        .await
+    }
 
-        // Get last synced block from database
-        let db = self.db.lock().await;
-        let last_synced_block = self.get_last_synced_block(&db, profile_id, chain).await?;
+    /// Chunked EVM sync, see `sync_account_chunked`
+    pub async fn sync_evm_account_chunked(
+        &self,
+        evm_indexer: &Mutex<EVMIndexer>,
+        chain: &str,
+        address: &str,
+        profile_id: &str,
+        cancel: &AtomicBool,
+        on_progress: impl FnMut(&SyncStatus),
+    ) -> Result<SyncStatus> {
+        let source = EvmSource {
+            indexer: evm_indexer,
+            chain,
+            address,
+            discovered: StdMutex::new(Vec::new()),
+        };
+        self.sync_chunks(
+            &source,
+            profile_id,
+            chain,
+            address,
+            EVM_CHUNK_BLOCKS,
+            cancel,
+            on_progress,
+        )
+        .await
+    }
 
-        // Fetch transactions
-        let transactions = indexer
-            .fetch_account_transactions(
+    /// Fetch `chunk_size` blocks at a time from the account's checkpoint up
+    /// to the current head, saving each chunk together with its checkpoint.
+    /// A failure is recorded on the account before it is returned.
+    ///
+    /// Transactions on a fork the chain has since abandoned are rolled back
+    /// first, and the checkpoint moves back to where the forks meet.
+    #[allow(clippy::too_many_arguments)]
+    async fn sync_chunks(
+        &self,
+        source: &impl ChainSource,
+        profile_id: &str,
+        chain: &str,
+        address: &str,
+        chunk_size: u64,
+        cancel: &AtomicBool,
+        on_progress: impl FnMut(&SyncStatus),
+    ) -> Result<SyncStatus> {
+        let account_id = self.account_id(profile_id, chain, address).await?;
+        let result = self
+            .run_chunks(
+                source,
+                profile_id,
                 chain,
-                address,
-                Some(last_synced_block),
-                Some(current_block),
+                &account_id,
+                chunk_size,
+                cancel,
+                on_progress,
             )
-            .await?;
+            .await;
 
-        // Save transactions to database
-        self.save_transactions(&db, profile_id, &transactions)
-            .await?;
+        if let Err(e) = &result {
+            self.record_error(&account_id, e).await?;
+        }
+        result
+    }
 
-        // Update sync status
-        self.update_sync_status(&db, profile_id, chain, current_block)
-            .await?;
+    #[allow(clippy::too_many_arguments)]
+    async fn run_chunks(
+        &self,
+        source: &impl ChainSource,
+        profile_id: &str,
+        chain: &str,
+        account_id: &str,
+        chunk_size: u64,
+        cancel: &AtomicBool,
+        mut on_progress: impl FnMut(&SyncStatus),
+    ) -> Result<SyncStatus> {
+        let pool = self.pool().await;
+        let current_block = source.current_block().await?;
+        let finalized_block = source.finalized_block().await?.min(current_block);
 
-        Ok(SyncStatus {
+        if let Some(rollback) = reorg::check(&pool, chain, source, finalized_block).await? {
+            eprintln!(
+                "Reorg on {}: rolled back {} transactions above block {}",
+                chain, rollback.removed_transactions, rollback.fork_block
+            );
+        }
+
+        let state = self.load_state(account_id).await?;
+        let start_block = state.next_block();
+        let synced_block = state.last_synced_block.unwrap_or(0).max(0) as u64;
+        let mut status = SyncStatus {
             chain: chain.to_string(),
-            last_block: last_synced_block as i64,
+            last_block: synced_block as i64,
             current_block: current_block as i64,
-            is_syncing: false,
-            progress: 100.0,
-        })
+            is_syncing: true,
+            progress: progress(start_block, start_block, current_block),
+            finalized_block: synced_block.min(finalized_block) as i64,
+        };
+        on_progress(&status);
+
+        if start_block <= current_block {
+            source.prepare(start_block, current_block).await?;
+        }
+
+        let mut next = start_block;
+        while next <= current_block && !cancel.load(Ordering::Relaxed) {
+            let to = (next + chunk_size - 1).min(current_block);
+            let transactions = source.fetch(next, to).await?;
+
+            // Remember the checkpoint and every unfinalized block with
+            // activity, a later sync compares their hashes with the chain
+            let mut numbers: BTreeSet<u64> = transactions
+                .iter()
+                .map(|tx| tx.block_number as u64)
+                .filter(|number| *number > finalized_block)
+                .collect();
+            numbers.insert(to);
+            let mut blocks = Vec::with_capacity(numbers.len());
+            for number in numbers {
+                blocks.push(source.block_ref(number).await?);
+            }
+
+            self.save_chunk(
+                profile_id,
+                chain,
+                account_id,
+                &transactions,
+                &blocks,
+                to,
+                finalized_block,
+            )
+            .await?;
+
+            status.last_block = to as i64;
+            status.progress = progress(start_block, to, current_block);
+            status.finalized_block = to.min(finalized_block) as i64;
+            on_progress(&status);
+            next = to + 1;
+        }
+
+        reorg::finalize(&pool, chain, finalized_block).await?;
+
+        status.is_syncing = false;
+        Ok(status)
+    }
+
+    /// Sync state of every account in a profile
+    pub async fn sync_states(&self, profile_id: &str) -> Result<Vec<AccountSyncState>> {
+        let states = sqlx::query_as::<_, AccountSyncState>(&format!(
+            "{} WHERE a.profile_id = ? ORDER BY a.chain, a.address",
+            STATE_QUERY
+        ))
+        .bind(profile_id)
+        .fetch_all(&self.pool().await)
+        .await
+        .context("Failed to fetch sync states")?;
+
+        Ok(states)
+    }
+
+    pub async fn sync_state(
+        &self,
+        profile_id: &str,
+        chain: &str,
+        address: &str,
+    ) -> Result<AccountSyncState> {
+        let account_id = self.account_id(profile_id, chain, address).await?;
+        self.load_state(&account_id).await
     }
 
-    async fn get_last_synced_block(
+    /// Skip the blocks before an account's first activity. Only blocks after
+    /// the checkpoint are fetched, so this never drops synced data.
+    pub async fn set_start_block(
         &self,
-        db: &Database,
         profile_id: &str,
         chain: &str,
-    ) -> Result<u32> {
-        let result: Option<(i64,)> = sqlx::query_as(
-            "SELECT last_synced_block FROM sync_status WHERE profile_id = ? AND chain = ?",
+        address: &str,
+        start_block: u64,
+    ) -> Result<()> {
+        let account_id = self.account_id(profile_id, chain, address).await?;
+        sqlx::query(
+            r#"
+            INSERT INTO account_sync_status (account_id, start_block) VALUES (?, ?)
+            ON CONFLICT(account_id) DO UPDATE SET start_block = excluded.start_block
+            "#,
+        )
+        .bind(&account_id)
+        .bind(start_block as i64)
+        .execute(&self.pool().await)
+        .await
+        .context("Failed to set sync start block")?;
+
+        Ok(())
+    }
+
+    async fn pool(&self) -> Pool<Sqlite> {
+        self.db.lock().await.pool.clone()
+    }
+
+    /// The `accounts` row of an address. Accounts are added through the
+    /// profile, so syncing an address the profile does not have is an error.
+    async fn account_id(&self, profile_id: &str, chain: &str, address: &str) -> Result<String> {
+        let address = validate_address(chain, address)?;
+        let id: Option<String> = sqlx::query_scalar(
+            "SELECT id FROM accounts WHERE profile_id = ? AND chain = ? AND address = ?
+             ORDER BY created_at LIMIT 1",
         )
         .bind(profile_id)
         .bind(chain)
-        .fetch_optional(&db.pool)
-        .await?;
+        .bind(&address)
+        .fetch_optional(&self.pool().await)
+        .await
+        .context("Failed to fetch account")?;
+
+        id.with_context(|| format!("{} on {} is not an account of this profile", address, chain))
+    }
+
+    async fn load_state(&self, account_id: &str) -> Result<AccountSyncState> {
+        let state =
+            sqlx::query_as::<_, AccountSyncState>(&format!("{} WHERE a.id = ?", STATE_QUERY))
+                .bind(account_id)
+                .fetch_one(&self.pool().await)
+                .await
+                .context("Failed to fetch sync state")?;
+
+        Ok(state)
+    }
+
+    async fn record_error(&self, account_id: &str, error: &anyhow::Error) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_sync_status (account_id, last_error, last_error_at)
+            VALUES (?, ?, datetime('now'))
+            ON CONFLICT(account_id) DO UPDATE SET
+                last_error = excluded.last_error,
+                last_error_at = excluded.last_error_at
+            "#,
+        )
+        .bind(account_id)
+        .bind(format!("{:#}", error))
+        .execute(&self.pool().await)
+        .await
+        .context("Failed to record sync error")?;
 
-        Ok(result.map(|(block,)| block as u32).unwrap_or(0))
+        Ok(())
     }
 
-    async fn save_transactions(
+    /// Save a chunk's transactions and block hashes and move the checkpoint
+    /// in one transaction, so an interrupted sync never skips blocks
+    #[allow(clippy::too_many_arguments)]
+    async fn save_chunk(
         &self,
-        db: &Database,
         profile_id: &str,
+        chain: &str,
+        account_id: &str,
         transactions: &[Transaction],
+        blocks: &[BlockRef],
+        block: u64,
+        finalized_block: u64,
     ) -> Result<()> {
-        // Save transactions to database
+        let pool = self.pool().await;
+        let mut db_tx = pool.begin().await?;
+
         for tx in transactions {
             sqlx::query(
                 r#"
                 INSERT INTO transactions (
-                    profile_id, chain, hash, block_number, timestamp,
+                    id, profile_id, chain, hash, log_index, block_number, timestamp,
                     from_address, to_address, value, token_symbol, token_decimals,
                     transaction_type, status, fee, metadata
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-                ON CONFLICT(hash, chain) DO NOTHING
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(chain, hash, log_index) DO NOTHING
                 "#,
             )
+            .bind(tx.id.to_string())
             .bind(profile_id)
             .bind(&tx.chain)
             .bind(&tx.hash)
+            .bind(tx.log_index)
             .bind(tx.block_number)
             .bind(tx.timestamp)
             .bind(&tx.from_address)
@@ -114,35 +534,411 @@ impl SyncManager {
             .bind(&tx.status)
             .bind(&tx.fee)
             .bind(serde_json::to_string(&tx.metadata)?)
-            .execute(&db.pool)
+            .execute(&mut *db_tx)
             .await?;
         }
 
-        Ok(())
-    }
+        reorg::save_blocks(&mut db_tx, chain, blocks, finalized_block).await?;
 
-    async fn update_sync_status(
-        &self,
-        db: &Database,
-        profile_id: &str,
-        chain: &str,
-        block: u32,
-    ) -> Result<()> {
         sqlx::query(
             r#"
-            INSERT INTO sync_status (profile_id, chain, last_synced_block, last_sync_time)
+            INSERT INTO account_sync_status (account_id, last_synced_block, finalized_block, last_sync_time)
             VALUES (?, ?, ?, datetime('now'))
-            ON CONFLICT(profile_id, chain) DO UPDATE SET
+            ON CONFLICT(account_id) DO UPDATE SET
                 last_synced_block = excluded.last_synced_block,
-                last_sync_time = excluded.last_sync_time
+                finalized_block = excluded.finalized_block,
+                last_sync_time = excluded.last_sync_time,
+                last_error = NULL,
+                last_error_at = NULL
             "#,
         )
-        .bind(profile_id)
-        .bind(chain)
-        .bind(block)
-        .execute(&db.pool)
+        .bind(account_id)
+        .bind(block as i64)
+        .bind(block.min(finalized_block) as i64)
+        .execute(&mut *db_tx)
         .await?;
 
+        db_tx.commit().await?;
         Ok(())
     }
 }
+
+/// Accounts without a sync yet get the defaults of `account_sync_status`
+const STATE_QUERY: &str = r#"
+    SELECT a.id AS account_id, a.chain, a.address,
+           COALESCE(s.start_block, 0) AS start_block,
+           s.last_synced_block,
+           COALESCE(s.finalized_block, 0) AS finalized_block,
+           s.last_sync_time, s.last_error, s.last_error_at
+    FROM accounts a
+    LEFT JOIN account_sync_status s ON s.account_id = a.id
+"#;
+
+/// Share of the blocks between the job's start and the head that are done
+fn progress(start_block: u64, synced_block: u64, current_block: u64) -> f64 {
+    if current_block <= start_block {
+        return 100.0;
+    }
+    let done = synced_block.saturating_sub(start_block) as f64;
+    (done / (current_block - start_block) as f64 * 100.0).min(100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    const ALICE: &str = "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5";
+    const BOB: &str = "14E5nqKAp3oAJcmzgZhUD2RcptBeUBScxKHgJKU4HPNcKVf3";
+
+    /// Chain whose blocks above `fork` have been replaced since the last sync
+    struct FakeChain {
+        head: u64,
+        finalized: u64,
+        fork: StdMutex<Option<u64>>,
+        /// Fetching blocks from here on fails
+        broken_from: Option<u64>,
+        activity: Vec<u64>,
+        prepared: StdMutex<Vec<(u64, u64)>>,
+        fetched: StdMutex<Vec<(u64, u64)>>,
+    }
+
+    impl FakeChain {
+        fn new(head: u64, finalized: u64, activity: Vec<u64>) -> Self {
+            Self {
+                head,
+                finalized,
+                fork: StdMutex::new(None),
+                broken_from: None,
+                activity,
+                prepared: StdMutex::new(Vec::new()),
+                fetched: StdMutex::new(Vec::new()),
+            }
+        }
+
+        fn hash(&self, number: u64) -> String {
+            match *self.fork.lock().unwrap() {
+                Some(fork) if number > fork => format!("0xb{:x}", number),
+                _ => format!("0xa{:x}", number),
+            }
+        }
+    }
+
+    impl ChainSource for FakeChain {
+        async fn current_block(&self) -> Result<u64> {
+            Ok(self.head)
+        }
+
+        async fn finalized_block(&self) -> Result<u64> {
+            Ok(self.finalized)
+        }
+
+        async fn block_ref(&self, number: u64) -> Result<BlockRef> {
+            Ok(BlockRef {
+                number,
+                hash: self.hash(number),
+                parent_hash: self.hash(number.saturating_sub(1)),
+            })
+        }
+
+        async fn prepare(&self, from: u64, to: u64) -> Result<()> {
+            self.prepared.lock().unwrap().push((from, to));
+            Ok(())
+        }
+
+        async fn fetch(&self, from: u64, to: u64) -> Result<Vec<Transaction>> {
+            if self.broken_from.is_some_and(|block| to >= block) {
+                anyhow::bail!("RPC unavailable");
+            }
+            self.fetched.lock().unwrap().push((from, to));
+            Ok(self
+                .activity
+                .iter()
+                .filter(|block| (from..=to).contains(*block))
+                .map(|block| transfer(*block, &self.hash(*block)))
+                .collect())
+        }
+    }
+
+    fn transfer(block: u64, block_hash: &str) -> Transaction {
+        Transaction {
+            id: Uuid::new_v4(),
+            profile_id: None,
+            chain: "polkadot".to_string(),
+            hash: format!("{}-tx", block_hash),
+            log_index: -1,
+            from_address: BOB.to_string(),
+            to_address: Some(ALICE.to_string()),
+            value: "10000000000".to_string(),
+            token_symbol: "DOT".to_string(),
+            token_decimals: 10,
+            timestamp: Utc::now(),
+            block_number: block as i64,
+            transaction_type: "transfer".to_string(),
+            status: "success".to_string(),
+            fee: None,
+            metadata: serde_json::json!({}),
+            amount_primary: None,
+            primary_currency: None,
+            exchange_rate: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    async fn manager() -> SyncManager {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO profiles (id, name) VALUES ('p1', 'Test')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        for address in [ALICE, BOB] {
+            sqlx::query(
+                "INSERT INTO accounts (id, profile_id, address, chain) VALUES (?, 'p1', ?, 'polkadot')",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(address)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        SyncManager::new(Arc::new(Mutex::new(Database { pool })))
+    }
+
+    #[test]
+    fn test_progress() {
+        assert_eq!(progress(100, 100, 300), 0.0);
+        assert_eq!(progress(100, 200, 300), 50.0);
+        assert_eq!(progress(100, 300, 300), 100.0);
+        assert_eq!(progress(300, 300, 300), 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_chunks_checkpoint_and_resume() {
+        let manager = manager().await;
+        let chain = FakeChain::new(450, 400, Vec::new());
+
+        // Cancel once the second chunk is reported
+        let cancel = AtomicBool::new(false);
+        let mut reports = Vec::new();
+        let status = manager
+            .sync_chunks(&chain, "p1", "polkadot", ALICE, 100, &cancel, |s| {
+                reports.push(s.progress);
+                if s.last_block >= 199 {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            })
+            .await
+            .unwrap();
+        assert!(!status.is_syncing);
+        assert_eq!(status.last_block, 199);
+        assert_eq!(status.finalized_block, 199);
+        assert_eq!(reports.len(), 3);
+        assert_eq!(
+            manager
+                .sync_state("p1", "polkadot", ALICE)
+                .await
+                .unwrap()
+                .last_synced_block,
+            Some(199)
+        );
+
+        // A new run starts after the checkpoint
+        let cancel = AtomicBool::new(false);
+        let status = manager
+            .sync_chunks(&chain, "p1", "polkadot", ALICE, 100, &cancel, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(status.progress, 100.0);
+        assert_eq!(status.finalized_block, 400);
+        assert_eq!(
+            *chain.fetched.lock().unwrap(),
+            vec![(0, 99), (100, 199), (200, 299), (300, 399), (400, 450)]
+        );
+        // Discovery covers each run's whole range once
+        assert_eq!(*chain.prepared.lock().unwrap(), vec![(0, 450), (200, 450)]);
+    }
+
+    #[tokio::test]
+    async fn test_reorg_rolls_back_unfinalized_blocks() {
+        let manager = manager().await;
+        let pool = manager.pool().await;
+        let chain = FakeChain::new(450, 400, vec![350, 420, 440]);
+        let cancel = AtomicBool::new(false);
+        manager
+            .sync_chunks(&chain, "p1", "polkadot", ALICE, 100, &cancel, |_| {})
+            .await
+            .unwrap();
+
+        // Only the finalized head and the unfinalized blocks are kept
+        let kept: Vec<(i64, bool)> =
+            sqlx::query_as("SELECT number, is_final FROM indexed_blocks ORDER BY number")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            kept,
+            vec![(399, true), (420, false), (440, false), (450, false)]
+        );
+
+        // Nothing changed on chain, nothing is rolled back
+        assert!(reorg::check(&pool, "polkadot", &chain, 400)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Blocks after 430 are replaced, the transaction in 440 disappears
+        // from the chain along with its XCM transfer, and 440 is fetched
+        // again from the new fork
+        sqlx::query(
+            "INSERT INTO xcm_transfers
+                 (id, transaction_id, from_chain_id, from_address, to_chain_id, to_address,
+                  asset_id, amount, status, timestamp)
+             SELECT 'x1', id, 'polkadot', from_address, 'moonbeam', to_address,
+                    'DOT', '1', 'completed', timestamp
+             FROM transactions WHERE block_number = 440",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        *chain.fork.lock().unwrap() = Some(430);
+        let rollback = reorg::check(&pool, "polkadot", &chain, 400)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rollback.fork_block, 420);
+        assert_eq!(rollback.removed_transactions, 1);
+        let xcm: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM xcm_transfers")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(xcm, 0);
+        assert_eq!(rollback.profiles, vec!["p1".to_string()]);
+        assert_eq!(
+            manager
+                .sync_state("p1", "polkadot", ALICE)
+                .await
+                .unwrap()
+                .last_synced_block,
+            Some(420)
+        );
+
+        manager
+            .sync_chunks(&chain, "p1", "polkadot", ALICE, 100, &cancel, |_| {})
+            .await
+            .unwrap();
+        let hashes: Vec<String> =
+            sqlx::query_scalar("SELECT hash FROM transactions ORDER BY block_number")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(hashes, vec!["0xa15e-tx", "0xa1a4-tx", "0xb1b8-tx"]);
+    }
+
+    #[tokio::test]
+    async fn test_accounts_keep_their_own_checkpoints() {
+        let manager = manager().await;
+        let cancel = AtomicBool::new(false);
+        let chain = FakeChain::new(450, 400, Vec::new());
+        manager
+            .sync_chunks(&chain, "p1", "polkadot", ALICE, 100, &cancel, |_| {})
+            .await
+            .unwrap();
+
+        // A second wallet on the same chain starts from its own start block
+        // instead of the first wallet's checkpoint
+        manager
+            .set_start_block("p1", "polkadot", BOB, 300)
+            .await
+            .unwrap();
+        let mut chain = FakeChain::new(450, 400, Vec::new());
+        chain.broken_from = Some(400);
+        let error = manager
+            .sync_chunks(&chain, "p1", "polkadot", BOB, 100, &cancel, |_| {})
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "RPC unavailable");
+        assert_eq!(*chain.fetched.lock().unwrap(), vec![(300, 399)]);
+
+        let states = manager.sync_states("p1").await.unwrap();
+        assert_eq!(states.len(), 2);
+        let bob = states.iter().find(|s| s.address == BOB).unwrap();
+        assert_eq!(bob.start_block, 300);
+        assert_eq!(bob.last_synced_block, Some(399));
+        assert_eq!(bob.last_error.as_deref(), Some("RPC unavailable"));
+        assert!(bob.last_error_at.is_some());
+        let alice = states.iter().find(|s| s.address == ALICE).unwrap();
+        assert_eq!(alice.last_synced_block, Some(450));
+        assert!(alice.last_sync_time.is_some());
+
+        // The next successful run resumes after the checkpoint and clears the error
+        let chain = FakeChain::new(450, 400, Vec::new());
+        manager
+            .sync_chunks(&chain, "p1", "polkadot", BOB, 100, &cancel, |_| {})
+            .await
+            .unwrap();
+        assert_eq!(*chain.fetched.lock().unwrap(), vec![(400, 450)]);
+        let bob = manager.sync_state("p1", "polkadot", BOB).await.unwrap();
+        assert_eq!(bob.last_synced_block, Some(450));
+        assert_eq!(bob.last_error, None);
+    }
+
+    #[tokio::test]
+    async fn test_only_profile_accounts_are_synced() {
+        let manager = manager().await;
+        let pool = manager.pool().await;
+        sqlx::query(
+            "INSERT INTO accounts (id, profile_id, address, chain)
+             VALUES ('evm', 'p1', '0x00000000000000000000000000000000000000ab', 'moonbeam')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // Addresses are normalized the way accounts store them
+        manager
+            .set_start_block(
+                "p1",
+                "moonbeam",
+                " 0x00000000000000000000000000000000000000AB",
+                5,
+            )
+            .await
+            .unwrap();
+        let state = manager
+            .sync_state(
+                "p1",
+                "moonbeam",
+                "0x00000000000000000000000000000000000000ab",
+            )
+            .await
+            .unwrap();
+        assert_eq!(state.start_block, 5);
+
+        let unknown = manager
+            .set_start_block(
+                "p1",
+                "moonbeam",
+                "0x00000000000000000000000000000000000000cd",
+                5,
+            )
+            .await
+            .unwrap_err();
+        assert!(
+            unknown.to_string().contains("not an account"),
+            "{}",
+            unknown
+        );
+        assert!(manager
+            .sync_state("p1", "polkadot", "not an address")
+            .await
+            .is_err());
+        let accounts: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM accounts")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(accounts, 3);
+    }
+}
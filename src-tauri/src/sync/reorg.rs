@@ -0,0 +1,212 @@
+use super::ChainSource;
+use crate::core::BlockRef;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{FromRow, Pool, Sqlite};
+
+/// Transactions and derived data removed after a reorganization
+#[derive(Debug, Clone, Serialize)]
+pub struct Rollback {
+    pub chain: String,
+    /// Last block both forks share, everything above it was removed
+    pub fork_block: u64,
+    pub removed_transactions: u64,
+    /// Profiles whose lot ledger and generated journal entries were cleared
+    pub profiles: Vec<String>,
+}
+
+#[derive(FromRow)]
+struct StoredBlock {
+    number: i64,
+    hash: String,
+}
+
+/// Compare the stored unfinalized block hashes with the chain and roll back
+/// to the highest block that still matches
+pub(super) async fn check(
+    pool: &Pool<Sqlite>,
+    chain: &str,
+    source: &impl ChainSource,
+    finalized_block: u64,
+) -> Result<Option<Rollback>> {
+    let pending = sqlx::query_as::<_, StoredBlock>(
+        "SELECT number, hash FROM indexed_blocks
+         WHERE chain = ? AND is_final = 0 ORDER BY number DESC",
+    )
+    .bind(chain)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch indexed blocks")?;
+
+    let mut fork_block = None;
+    for (i, stored) in pending.iter().enumerate() {
+        let number = stored.number as u64;
+        if source.block_ref(number).await?.hash == stored.hash {
+            if i == 0 {
+                return Ok(None);
+            }
+            fork_block = Some(number);
+            break;
+        }
+    }
+
+    let fork_block = match fork_block {
+        Some(block) => block,
+        // No stored block survived. The forks meet at or above the finalized
+        // head, and below the lowest block that changed.
+        None => match pending.last() {
+            Some(lowest) => finalized_block.min((lowest.number as u64).saturating_sub(1)),
+            None => return Ok(None),
+        },
+    };
+
+    rollback(pool, chain, fork_block).await.map(Some)
+}
+
+/// Remove everything indexed on `chain` above `fork_block` and move the
+/// checkpoints back to it
+pub(super) async fn rollback(
+    pool: &Pool<Sqlite>,
+    chain: &str,
+    fork_block: u64,
+) -> Result<Rollback> {
+    let fork = fork_block as i64;
+    let mut tx = pool.begin().await?;
+
+    let profiles: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT profile_id FROM transactions
+         WHERE chain = ? AND block_number > ? AND profile_id IS NOT NULL",
+    )
+    .bind(chain)
+    .bind(fork)
+    .fetch_all(&mut *tx)
+    .await
+    .context("Failed to find profiles affected by reorg")?;
+
+    // Lots and generated entries depend on every transaction of the profile,
+    // so they are cleared and rebuilt on demand
+    for profile_id in &profiles {
+        for query in [
+            "DELETE FROM tax_disposals WHERE profile_id = ?",
+            "DELETE FROM tax_lots WHERE profile_id = ?",
+            "DELETE FROM journal_entries WHERE profile_id = ? AND transaction_id IS NOT NULL",
+        ] {
+            sqlx::query(query)
+                .bind(profile_id)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to clear data derived from rolled back transactions")?;
+        }
+    }
+
+    sqlx::query(
+        "DELETE FROM xcm_transfers WHERE transaction_id IN
+             (SELECT id FROM transactions WHERE chain = ? AND block_number > ?)",
+    )
+    .bind(chain)
+    .bind(fork)
+    .execute(&mut *tx)
+    .await
+    .context("Failed to roll back XCM transfers")?;
+
+    let removed = sqlx::query("DELETE FROM transactions WHERE chain = ? AND block_number > ?")
+        .bind(chain)
+        .bind(fork)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to roll back transactions")?
+        .rows_affected();
+
+    sqlx::query("DELETE FROM indexed_blocks WHERE chain = ? AND number > ?")
+        .bind(chain)
+        .bind(fork)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to roll back indexed blocks")?;
+
+    sqlx::query(
+        "UPDATE account_sync_status SET
+             last_synced_block = MIN(last_synced_block, ?1),
+             finalized_block = MIN(finalized_block, ?1)
+         WHERE account_id IN (SELECT id FROM accounts WHERE chain = ?2)",
+    )
+    .bind(fork)
+    .bind(chain)
+    .execute(&mut *tx)
+    .await
+    .context("Failed to move sync checkpoints back")?;
+
+    tx.commit().await?;
+
+    Ok(Rollback {
+        chain: chain.to_string(),
+        fork_block,
+        removed_transactions: removed,
+        profiles,
+    })
+}
+
+pub(super) async fn save_blocks(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    chain: &str,
+    blocks: &[BlockRef],
+    finalized_block: u64,
+) -> Result<()> {
+    for block in blocks {
+        sqlx::query(
+            r#"
+            INSERT INTO indexed_blocks (chain, number, hash, parent_hash, is_final)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(chain, number) DO UPDATE SET
+                hash = excluded.hash,
+                parent_hash = excluded.parent_hash,
+                is_final = excluded.is_final,
+                indexed_at = datetime('now')
+            "#,
+        )
+        .bind(chain)
+        .bind(block.number as i64)
+        .bind(&block.hash)
+        .bind(&block.parent_hash)
+        .bind(block.number <= finalized_block)
+        .execute(&mut **tx)
+        .await
+        .context("Failed to save indexed block")?;
+    }
+
+    Ok(())
+}
+
+/// Mark blocks up to `finalized_block` final and keep only the highest final one
+pub(super) async fn finalize(pool: &Pool<Sqlite>, chain: &str, finalized_block: u64) -> Result<()> {
+    let finalized = finalized_block as i64;
+
+    sqlx::query("UPDATE indexed_blocks SET is_final = 1 WHERE chain = ? AND number <= ?")
+        .bind(chain)
+        .bind(finalized)
+        .execute(pool)
+        .await
+        .context("Failed to mark blocks final")?;
+
+    sqlx::query(
+        "DELETE FROM indexed_blocks WHERE chain = ?1 AND is_final = 1 AND number <
+             (SELECT MAX(number) FROM indexed_blocks WHERE chain = ?1 AND is_final = 1)",
+    )
+    .bind(chain)
+    .execute(pool)
+    .await
+    .context("Failed to prune final blocks")?;
+
+    sqlx::query(
+        "UPDATE account_sync_status SET finalized_block = MIN(last_synced_block, ?1)
+         WHERE account_id IN (SELECT id FROM accounts WHERE chain = ?2)
+           AND finalized_block < MIN(last_synced_block, ?1)",
+    )
+    .bind(finalized)
+    .bind(chain)
+    .execute(pool)
+    .await
+    .context("Failed to update finalized checkpoints")?;
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use super::SyncManager;
+use crate::core::SyncStatus;
+use crate::evm_indexer::EVMIndexer;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::sync::Mutex;
+
+/// Event the scheduler emits with every progress update
+pub const SYNC_EVENT: &str = "sync://progress";
+
+/// One address on one chain, synced into a profile
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SyncJob {
+    pub profile_id: String,
+    pub chain: String,
+    pub address: String,
+}
+
+impl SyncJob {
+    /// H160 addresses go through the EVM indexer, SS58 ones through Substrate
+    fn is_evm(&self) -> bool {
+        self.address.starts_with("0x")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncEvent {
+    pub job: SyncJob,
+    pub state: JobState,
+    /// Latest checkpoint, `None` if the job failed before its first chunk
+    pub status: Option<SyncStatus>,
+    pub error: Option<String>,
+}
+
+/// Runs sync jobs in the background, one at a time per job
+#[derive(Default)]
+pub struct SyncScheduler {
+    /// Cancel flags of the running jobs
+    jobs: Arc<StdMutex<HashMap<SyncJob, Arc<AtomicBool>>>>,
+}
+
+impl SyncScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a job unless it is already running. Returns whether it was started.
+    ///
+    /// The job resumes from the stored checkpoint and calls `emit` after every
+    /// chunk and once when it ends.
+    pub fn start(
+        &self,
+        manager: Arc<SyncManager>,
+        evm_indexer: Arc<Mutex<EVMIndexer>>,
+        job: SyncJob,
+        emit: impl Fn(SyncEvent) + Send + Sync + 'static,
+    ) -> bool {
+        let cancel = Arc::new(AtomicBool::new(false));
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            if jobs.contains_key(&job) {
+                return false;
+            }
+            jobs.insert(job.clone(), cancel.clone());
+        }
+
+        let jobs = self.jobs.clone();
+        tokio::spawn(async move {
+            let mut last_status = None;
+            let on_progress = |status: &SyncStatus| {
+                last_status = Some(status.clone());
+                emit(SyncEvent {
+                    job: job.clone(),
+                    state: JobState::Running,
+                    status: Some(status.clone()),
+                    error: None,
+                });
+            };
+
+            let result = if job.is_evm() {
+                manager
+                    .sync_evm_account_chunked(
+                        &evm_indexer,
+                        &job.chain,
+                        &job.address,
+                        &job.profile_id,
+                        &cancel,
+                        on_progress,
+                    )
+                    .await
+            } else {
+                manager
+                    .sync_account_chunked(
+                        &job.chain,
+                        &job.address,
+                        &job.profile_id,
+                        &cancel,
+                        on_progress,
+                    )
+                    .await
+            };
+
+            jobs.lock().unwrap().remove(&job);
+            emit(match result {
+                Ok(status) => SyncEvent {
+                    state: if cancel.load(Ordering::Relaxed) {
+                        JobState::Cancelled
+                    } else {
+                        JobState::Completed
+                    },
+                    status: Some(status),
+                    error: None,
+                    job,
+                },
+                Err(e) => SyncEvent {
+                    state: JobState::Failed,
+                    status: last_status,
+                    error: Some(e.to_string()),
+                    job,
+                },
+            });
+        });
+
+        true
+    }
+
+    /// Ask a running job to stop after its current chunk
+    pub fn cancel(&self, job: &SyncJob) -> bool {
+        match self.jobs.lock().unwrap().get(job) {
+            Some(cancel) => {
+                cancel.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn running(&self) -> Vec<SyncJob> {
+        self.jobs.lock().unwrap().keys().cloned().collect()
+    }
+}
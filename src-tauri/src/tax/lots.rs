@@ -0,0 +1,580 @@
+use crate::core::amount::TokenAmount;
+use crate::core::currency::CostBasisMethod;
+use crate::core::Transaction;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sp_core::crypto::{AccountId32, Ss58Codec};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// An acquisition of an asset and its cost in the reporting currency
+#[derive(Debug, Clone, Serialize)]
+pub struct Lot {
+    pub transaction_id: Uuid,
+    pub asset: String,
+    pub chain: String,
+    pub acquired_at: DateTime<Utc>,
+    pub quantity: Decimal,
+    pub remaining_quantity: Decimal,
+    /// Cost of the full `quantity`
+    pub cost_basis: Decimal,
+    /// False when the transaction had no fiat value and the cost is zero
+    pub priced: bool,
+}
+
+impl Lot {
+    fn unit_cost(&self) -> Decimal {
+        if self.quantity.is_zero() {
+            Decimal::ZERO
+        } else {
+            self.cost_basis / self.quantity
+        }
+    }
+}
+
+/// The part of a lot consumed by a disposal
+#[derive(Debug, Clone, Serialize)]
+pub struct LotMatch {
+    pub lot_id: Uuid,
+    pub acquired_at: DateTime<Utc>,
+    pub quantity: Decimal,
+    pub cost_basis: Decimal,
+}
+
+/// A disposal of an asset and its realised gain or loss
+#[derive(Debug, Clone, Serialize)]
+pub struct Disposal {
+    pub transaction_id: Uuid,
+    pub asset: String,
+    pub chain: String,
+    pub disposed_at: DateTime<Utc>,
+    pub quantity: Decimal,
+    pub proceeds: Decimal,
+    pub cost_basis: Decimal,
+    pub gain: Decimal,
+    /// Quantity not covered by any known lot, carried at zero cost
+    pub unmatched_quantity: Decimal,
+    /// False when the proceeds or any matched lot had no fiat value
+    pub priced: bool,
+    pub matches: Vec<LotMatch>,
+}
+
+/// Lots and disposals of one profile under one cost basis method
+#[derive(Debug, Clone, Serialize)]
+pub struct LotLedger {
+    pub method: CostBasisMethod,
+    pub fiat_currency: String,
+    pub lots: Vec<Lot>,
+    pub disposals: Vec<Disposal>,
+    /// Lots spent on network fees, the proceeds being the fee's fiat value.
+    /// Rebuilt with the ledger rather than stored.
+    pub fee_disposals: Vec<Disposal>,
+}
+
+/// Longest time between the two legs of an XCM transfer
+const XCM_LEG_WINDOW_MINUTES: i64 = 60;
+
+/// Whether a transaction adds to or takes from the profile's holdings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Acquisition,
+    Disposal,
+}
+
+/// Replay `transactions` in chain order and match every disposal against
+/// the open lots of the same asset.
+///
+/// `owned` are the profile's addresses; transfers between them, including
+/// XCM transfers between their chains, move no lots. Network fees they paid
+/// dispose of the fee token like any other spend.
+pub fn build_ledger(
+    method: CostBasisMethod,
+    fiat_currency: &str,
+    owned: &[String],
+    transactions: &[Transaction],
+) -> Result<LotLedger> {
+    let owned: HashSet<String> = owned.iter().map(|a| normalize_address(a)).collect();
+    let moves = own_moves(transactions, &owned)?;
+
+    let mut ordered: Vec<&Transaction> = transactions.iter().collect();
+    ordered.sort_by_key(|tx| (tx.timestamp, tx.block_number, tx.log_index));
+
+    let mut ledger = LotLedger {
+        method,
+        fiat_currency: fiat_currency.to_string(),
+        lots: Vec::new(),
+        disposals: Vec::new(),
+        fee_disposals: Vec::new(),
+    };
+    // Indices into `ledger.lots` of lots with quantity left, per asset
+    let mut open: HashMap<String, Vec<usize>> = HashMap::new();
+
+    for tx in ordered {
+        if let Some(flow) = flow(tx, &owned, &moves) {
+            let quantity = quantity(tx)?;
+            let value = fiat_value(tx, quantity, fiat_currency);
+
+            match flow {
+                _ if quantity.is_zero() => {}
+                Flow::Acquisition => {
+                    open.entry(tx.token_symbol.clone())
+                        .or_default()
+                        .push(ledger.lots.len());
+                    ledger.lots.push(Lot {
+                        transaction_id: tx.id,
+                        asset: tx.token_symbol.clone(),
+                        chain: tx.chain.clone(),
+                        acquired_at: tx.timestamp,
+                        quantity,
+                        remaining_quantity: quantity,
+                        cost_basis: value.unwrap_or_default(),
+                        priced: value.is_some(),
+                    });
+                }
+                Flow::Disposal => {
+                    let open_lots = open.entry(tx.token_symbol.clone()).or_default();
+                    ledger.disposals.push(dispose(
+                        method,
+                        &mut ledger.lots,
+                        open_lots,
+                        tx,
+                        quantity,
+                        value,
+                    ));
+                }
+            }
+        }
+
+        // Failed transactions still pay their fee
+        if let Some((fee, value)) = paid_fee(tx, &owned, fiat_currency)? {
+            let open_lots = open.entry(tx.token_symbol.clone()).or_default();
+            ledger
+                .fee_disposals
+                .push(dispose(method, &mut ledger.lots, open_lots, tx, fee, value));
+        }
+    }
+
+    Ok(ledger)
+}
+
+/// Match `quantity` of `tx`'s asset against the open lots
+fn dispose(
+    method: CostBasisMethod,
+    lots: &mut [Lot],
+    open: &mut Vec<usize>,
+    tx: &Transaction,
+    quantity: Decimal,
+    value: Option<Decimal>,
+) -> Disposal {
+    let (matches, lots_priced) = match_lots(method, lots, open, quantity);
+
+    let matched: Decimal = matches.iter().map(|m| m.quantity).sum();
+    let cost_basis: Decimal = matches.iter().map(|m| m.cost_basis).sum();
+    let proceeds = value.unwrap_or_default();
+
+    Disposal {
+        transaction_id: tx.id,
+        asset: tx.token_symbol.clone(),
+        chain: tx.chain.clone(),
+        disposed_at: tx.timestamp,
+        quantity,
+        proceeds,
+        cost_basis,
+        gain: proceeds - cost_basis,
+        unmatched_quantity: quantity - matched,
+        priced: value.is_some() && lots_priced,
+        matches,
+    }
+}
+
+/// Consume `quantity` from the open lots, dropping lots that run empty.
+/// Also returns whether every consumed lot had a fiat cost.
+fn match_lots(
+    method: CostBasisMethod,
+    lots: &mut [Lot],
+    open: &mut Vec<usize>,
+    quantity: Decimal,
+) -> (Vec<LotMatch>, bool) {
+    let mut matches = Vec::new();
+    let mut priced = true;
+
+    if method == CostBasisMethod::Average {
+        // Take the same fraction of every open lot so the pooled unit cost is unchanged
+        let available: Decimal = open.iter().map(|&i| lots[i].remaining_quantity).sum();
+        if available.is_zero() {
+            return (matches, priced);
+        }
+        let take = quantity.min(available);
+        let fraction = take / available;
+
+        let mut taken = Decimal::ZERO;
+        for (n, &i) in open.iter().enumerate() {
+            let lot = &mut lots[i];
+            let amount = if n + 1 == open.len() {
+                (take - taken).min(lot.remaining_quantity)
+            } else {
+                lot.remaining_quantity * fraction
+            };
+            taken += amount;
+            priced &= lot.priced;
+            matches.push(consume(lot, amount));
+        }
+    } else {
+        let mut order = open.clone();
+        match method {
+            CostBasisMethod::Lifo => order.reverse(),
+            CostBasisMethod::Hifo => {
+                order.sort_by(|&a, &b| lots[b].unit_cost().cmp(&lots[a].unit_cost()))
+            }
+            _ => {}
+        }
+
+        let mut left = quantity;
+        for i in order {
+            if left.is_zero() {
+                break;
+            }
+            let lot = &mut lots[i];
+            let amount = left.min(lot.remaining_quantity);
+            left -= amount;
+            priced &= lot.priced;
+            matches.push(consume(lot, amount));
+        }
+    }
+
+    open.retain(|&i| !lots[i].remaining_quantity.is_zero());
+    matches.retain(|m| !m.quantity.is_zero());
+    (matches, priced)
+}
+
+fn consume(lot: &mut Lot, amount: Decimal) -> LotMatch {
+    lot.remaining_quantity -= amount;
+    LotMatch {
+        lot_id: lot.transaction_id,
+        acquired_at: lot.acquired_at,
+        quantity: amount,
+        cost_basis: lot.unit_cost() * amount,
+    }
+}
+
+/// How a transaction moves the holdings of `owned` (normalized) addresses.
+/// `moves` are the legs of transfers between them, see [`own_moves`].
+pub fn flow(tx: &Transaction, owned: &HashSet<String>, moves: &HashSet<Uuid>) -> Option<Flow> {
+    if tx.status == "failed" || moves.contains(&tx.id) {
+        return None;
+    }
+
+    match tx.transaction_type.as_str() {
+        // Bonded funds stay with the owner
+        "stake" | "unstake" | "fee" => return None,
+        "staking_reward" => return Some(Flow::Acquisition),
+        "slash" => return Some(Flow::Disposal),
+        _ => {}
+    }
+
+    let from_owned = owned.contains(&normalize_address(&tx.from_address));
+    let to_owned = tx
+        .to_address
+        .as_deref()
+        .is_some_and(|to| owned.contains(&normalize_address(to)));
+
+    match (from_owned, to_owned) {
+        (false, true) => Some(Flow::Acquisition),
+        // XCM legs without a counterparty move funds between the owner's chains
+        (true, false) if tx.to_address.is_some() => Some(Flow::Disposal),
+        _ => None,
+    }
+}
+
+/// XCM transfers between the profile's own chains, as the ids of both legs.
+///
+/// Each chain records its own leg: the sending chain pays a sovereign account
+/// or burns, the receiving chain mints or pays out of one. An outgoing leg from
+/// an owned address is paired with the first unpaired incoming leg of the same
+/// token to an owned address on another chain within the following hour that
+/// is not larger. The destination fee taken from the incoming leg is not
+/// matched against lots.
+pub fn own_moves(transactions: &[Transaction], owned: &HashSet<String>) -> Result<HashSet<Uuid>> {
+    let is_owned =
+        |address: Option<&str>| address.is_some_and(|a| owned.contains(&normalize_address(a)));
+
+    let mut legs: Vec<&Transaction> = transactions
+        .iter()
+        .filter(|tx| tx.transaction_type == "xcm_transfer" && tx.status != "failed")
+        .collect();
+    legs.sort_by_key(|tx| (tx.timestamp, tx.block_number, tx.log_index));
+
+    let mut incoming = Vec::new();
+    for tx in &legs {
+        if is_owned(tx.to_address.as_deref()) && !is_owned(Some(&tx.from_address)) {
+            incoming.push((*tx, quantity(tx)?));
+        }
+    }
+
+    let mut moves = HashSet::new();
+    for tx in legs {
+        if !is_owned(Some(&tx.from_address)) || is_owned(tx.to_address.as_deref()) {
+            continue;
+        }
+        let sent = quantity(tx)?;
+        let window = tx.timestamp..=tx.timestamp + Duration::minutes(XCM_LEG_WINDOW_MINUTES);
+
+        let received = incoming.iter().position(|(leg, received)| {
+            leg.token_symbol == tx.token_symbol
+                && leg.chain != tx.chain
+                && window.contains(&leg.timestamp)
+                && *received <= sent
+        });
+        if let Some(position) = received {
+            let (leg, _) = incoming.remove(position);
+            moves.insert(tx.id);
+            moves.insert(leg.id);
+        }
+    }
+
+    Ok(moves)
+}
+
+/// Addresses in a comparable form. EVM addresses are stored lowercase but may
+/// be entered checksummed; SS58 addresses become their account ID, so the same
+/// account matches on every network.
+pub fn normalize_address(address: &str) -> String {
+    if address.starts_with("0x") {
+        address.to_lowercase()
+    } else {
+        match AccountId32::from_ss58check_with_version(address) {
+            Ok((account, _)) => format!("0x{}", hex::encode(account)),
+            Err(_) => address.to_string(),
+        }
+    }
+}
+
+/// Transaction value in whole tokens
+pub fn quantity(tx: &Transaction) -> Result<Decimal> {
+    tx.amount()
+        .and_then(|amount| amount.to_decimal())
+        .map(|quantity| quantity.normalize())
+        .with_context(|| format!("Invalid value {} in transaction {}", tx.value, tx.id))
+}
+
+/// Fiat value from the stored conversion, if it is in the reporting currency
+pub(super) fn fiat_value(
+    tx: &Transaction,
+    quantity: Decimal,
+    fiat_currency: &str,
+) -> Option<Decimal> {
+    if tx
+        .primary_currency
+        .as_deref()
+        .is_some_and(|c| c != fiat_currency)
+    {
+        return None;
+    }
+
+    if let Some(amount) = tx.amount_primary.as_deref().filter(|a| !a.is_empty()) {
+        return Decimal::from_str(amount).ok();
+    }
+
+    quantity.checked_mul(Decimal::from_str(tx.exchange_rate.as_deref()?).ok()?)
+}
+
+/// Fiat price of one whole token at the time of the transaction
+fn unit_price(tx: &Transaction, fiat_currency: &str) -> Option<Decimal> {
+    if tx
+        .primary_currency
+        .as_deref()
+        .is_some_and(|c| c != fiat_currency)
+    {
+        return None;
+    }
+
+    if let Some(rate) = tx.exchange_rate.as_deref().filter(|r| !r.is_empty()) {
+        return Decimal::from_str(rate).ok();
+    }
+
+    let quantity = quantity(tx).ok().filter(|q| !q.is_zero())?;
+    Decimal::from_str(tx.amount_primary.as_deref()?)
+        .ok()?
+        .checked_div(quantity)
+}
+
+/// Fee paid by one of the `owned` addresses, in whole tokens and fiat if priced
+pub fn paid_fee(
+    tx: &Transaction,
+    owned: &HashSet<String>,
+    fiat_currency: &str,
+) -> Result<Option<(Decimal, Option<Decimal>)>> {
+    let Some(fee) = tx.fee.as_deref() else {
+        return Ok(None);
+    };
+
+    // Substrate movements record who paid; EVM fees are paid by the sender
+    let payer = tx
+        .metadata
+        .get("fee_payer")
+        .and_then(|p| p.as_str())
+        .unwrap_or(&tx.from_address);
+    if !owned.contains(&normalize_address(payer)) {
+        return Ok(None);
+    }
+
+    let amount = TokenAmount::from_units(fee, tx.token_decimals)
+        .with_context(|| format!("Invalid fee {} in transaction {}", fee, tx.id))?;
+    if amount.is_zero() {
+        return Ok(None);
+    }
+    let value = unit_price(tx, fiat_currency).and_then(|price| amount.mul_rate(price).ok());
+
+    Ok(Some((amount.to_decimal()?.normalize(), value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const ME: &str = "0x1111111111111111111111111111111111111111";
+    const EXCHANGE: &str = "0x2222222222222222222222222222222222222222";
+    const COLD_WALLET: &str = "0xabcdefabcdefabcdefabcdefabcdefabcdefabcd";
+
+    fn tx(day: u32, from: &str, to: &str, dot: i64, usd: &str) -> Transaction {
+        Transaction {
+            id: Uuid::new_v4(),
+            profile_id: Some("profile-1".to_string()),
+            chain: "moonbeam".to_string(),
+            hash: format!("0x{:064x}", day),
+            log_index: -1,
+            from_address: from.to_string(),
+            to_address: Some(to.to_string()),
+            value: (dot as i128 * 10_i128.pow(10)).to_string(),
+            token_symbol: "DOT".to_string(),
+            token_decimals: 10,
+            timestamp: Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(),
+            block_number: day as i64,
+            transaction_type: "transfer".to_string(),
+            status: "confirmed".to_string(),
+            fee: None,
+            metadata: serde_json::json!({}),
+            amount_primary: Some(usd.to_string()),
+            primary_currency: Some("USD".to_string()),
+            exchange_rate: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// Buy 10 @ 5, buy 10 @ 8, buy 10 @ 6, then sell 15 for 150
+    fn history() -> Vec<Transaction> {
+        vec![
+            tx(1, EXCHANGE, ME, 10, "50"),
+            tx(2, EXCHANGE, ME, 10, "80"),
+            tx(3, EXCHANGE, ME, 10, "60"),
+            tx(4, ME, EXCHANGE, 15, "150"),
+        ]
+    }
+
+    fn disposal(method: CostBasisMethod) -> Disposal {
+        let ledger = build_ledger(method, "USD", &[ME.to_string()], &history()).unwrap();
+        assert_eq!(ledger.lots.len(), 3);
+        assert_eq!(ledger.disposals.len(), 1);
+        ledger.disposals[0].clone()
+    }
+
+    #[test]
+    fn test_fifo_lifo_hifo() {
+        let fifo = disposal(CostBasisMethod::Fifo);
+        assert_eq!(fifo.cost_basis, Decimal::from(90));
+        assert_eq!(fifo.gain, Decimal::from(60));
+        assert_eq!(fifo.matches.len(), 2);
+
+        let lifo = disposal(CostBasisMethod::Lifo);
+        assert_eq!(lifo.cost_basis, Decimal::from(100));
+        assert_eq!(lifo.gain, Decimal::from(50));
+
+        let hifo = disposal(CostBasisMethod::Hifo);
+        assert_eq!(hifo.cost_basis, Decimal::from(110));
+        assert_eq!(hifo.gain, Decimal::from(40));
+        assert!(hifo.priced);
+        assert!(hifo.unmatched_quantity.is_zero());
+    }
+
+    #[test]
+    fn test_average_cost() {
+        let ledger = build_ledger(
+            CostBasisMethod::Average,
+            "USD",
+            &[ME.to_string()],
+            &history(),
+        )
+        .unwrap();
+
+        let disposal = &ledger.disposals[0];
+        assert_eq!(disposal.cost_basis.round_dp(8), Decimal::from(95));
+        assert_eq!(disposal.matches.len(), 3);
+
+        let remaining: Decimal = ledger.lots.iter().map(|l| l.remaining_quantity).sum();
+        assert_eq!(remaining, Decimal::from(15));
+    }
+
+    #[test]
+    fn test_self_transfers_and_oversold() {
+        let mut transactions = vec![
+            tx(1, EXCHANGE, ME, 5, "25"),
+            tx(2, ME, COLD_WALLET, 5, "30"),
+            tx(3, ME, EXCHANGE, 8, "48"),
+        ];
+        // Checksummed input still matches the stored lowercase address
+        let owned = vec![ME.to_string(), COLD_WALLET.replace("abcdef", "ABCDEF")];
+        transactions[0].amount_primary = None;
+        transactions[0].exchange_rate = Some("5".to_string());
+
+        let ledger = build_ledger(CostBasisMethod::Fifo, "USD", &owned, &transactions).unwrap();
+
+        assert_eq!(ledger.lots[0].cost_basis, Decimal::from(25));
+        assert_eq!(ledger.disposals.len(), 1);
+        let disposal = &ledger.disposals[0];
+        assert_eq!(disposal.unmatched_quantity, Decimal::from(3));
+        assert_eq!(disposal.cost_basis, Decimal::from(25));
+        assert_eq!(disposal.gain, Decimal::from(23));
+    }
+
+    #[test]
+    fn test_xcm_between_own_chains_keeps_lots() {
+        const SOVEREIGN: &str = "0x7369626c00000000000000000000000000000000";
+        let mut sent = tx(2, ME, SOVEREIGN, 10, "80");
+        sent.chain = "polkadot".to_string();
+        sent.transaction_type = "xcm_transfer".to_string();
+        // Minted on arrival, less the destination fee
+        let mut received = tx(2, "", COLD_WALLET, 9, "72");
+        received.transaction_type = "xcm_transfer".to_string();
+        received.value = (95 * 10_i128.pow(9)).to_string();
+        received.timestamp += Duration::minutes(1);
+        // An XCM transfer to someone else is still a disposal
+        let mut gift = tx(3, ME, SOVEREIGN, 5, "30");
+        gift.chain = "polkadot".to_string();
+        gift.transaction_type = "xcm_transfer".to_string();
+
+        let transactions = vec![tx(1, EXCHANGE, ME, 20, "100"), sent, received, gift];
+        let owned = vec![ME.to_string(), COLD_WALLET.to_string()];
+        let ledger = build_ledger(CostBasisMethod::Fifo, "USD", &owned, &transactions).unwrap();
+
+        assert_eq!(ledger.lots.len(), 1);
+        assert_eq!(ledger.disposals.len(), 1);
+        assert_eq!(ledger.disposals[0].transaction_id, transactions[3].id);
+        assert_eq!(ledger.disposals[0].cost_basis, Decimal::from(25));
+        assert_eq!(ledger.lots[0].remaining_quantity, Decimal::from(15));
+    }
+
+    #[test]
+    fn test_ss58_addresses_match_across_networks() {
+        // The same account on Polkadot and Kusama
+        let polkadot = normalize_address("15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5");
+        let kusama = normalize_address("HNZata7iMYWmk5RvZRTiAsSDhV8366zq2YGb3tLH5Upf74F");
+        assert_eq!(polkadot, kusama);
+        assert!(polkadot.starts_with("0x"));
+        assert_eq!(normalize_address("not an address"), "not an address");
+    }
+}
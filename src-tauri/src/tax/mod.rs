@@ -0,0 +1,394 @@
+#![allow(dead_code)]
+
+mod lots;
+mod report;
+
+pub use lots::{
+    build_ledger, flow, normalize_address, own_moves, quantity, Disposal, Flow, Lot, LotLedger,
+    LotMatch,
+};
+pub use report::{build_report, income_category, TaxReport};
+
+use crate::core::currency::CostBasisMethod;
+use crate::core::currency_service::CurrencyService;
+use crate::core::Transaction;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use sqlx::{FromRow, Pool, Sqlite};
+use std::collections::HashMap;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Rebuild the lot ledger of a profile from its stored transactions and persist it
+pub async fn rebuild_lot_ledger(pool: &Pool<Sqlite>, profile_id: &str) -> Result<LotLedger> {
+    let inputs = LedgerInputs::load(pool, profile_id).await?;
+    let ledger = inputs.build_ledger()?;
+    save_lot_ledger(pool, profile_id, &ledger).await?;
+
+    Ok(ledger)
+}
+
+/// Rebuild the lot ledger and report gains, income and fees for `year` in
+/// `currency`, the primary currency by default. Only the primary currency
+/// ledger is persisted.
+pub async fn generate_tax_report(
+    pool: &Pool<Sqlite>,
+    profile_id: &str,
+    year: i32,
+    currency: Option<&str>,
+) -> Result<TaxReport> {
+    let inputs = LedgerInputs::load(pool, profile_id).await?;
+    let (inputs, primary) = match currency {
+        Some(currency) if currency != inputs.fiat_currency => {
+            (inputs.in_currency(pool, profile_id, currency).await?, false)
+        }
+        _ => (inputs, true),
+    };
+    let ledger = inputs.build_ledger()?;
+    if primary {
+        save_lot_ledger(pool, profile_id, &ledger).await?;
+    }
+
+    build_report(
+        year,
+        &ledger,
+        &inputs.owned,
+        &inputs.transactions,
+        &inputs.tags,
+    )
+}
+
+/// Everything the lot ledger of a profile is derived from
+pub struct LedgerInputs {
+    pub method: CostBasisMethod,
+    pub fiat_currency: String,
+    pub owned: Vec<String>,
+    pub transactions: Vec<Transaction>,
+    /// The user's tags per transaction, which classify airdrops and yield
+    pub tags: HashMap<Uuid, Vec<String>>,
+}
+
+impl LedgerInputs {
+    pub async fn load(pool: &Pool<Sqlite>, profile_id: &str) -> Result<Self> {
+        let (method, fiat_currency) = match CurrencyService::new(pool.clone())
+            .get_account_settings(profile_id)
+            .await?
+        {
+            Some(settings) => (settings.cost_basis_method, settings.primary_currency),
+            None => (CostBasisMethod::Fifo, "USD".to_string()),
+        };
+
+        let owned: Vec<String> =
+            sqlx::query_scalar("SELECT address FROM accounts WHERE profile_id = ?")
+                .bind(profile_id)
+                .fetch_all(pool)
+                .await
+                .context("Failed to fetch profile accounts")?;
+        if owned.is_empty() {
+            anyhow::bail!("Profile {} has no accounts", profile_id);
+        }
+
+        let transactions: Vec<Transaction> =
+            sqlx::query_as("SELECT * FROM transactions WHERE profile_id = ?")
+                .bind(profile_id)
+                .fetch_all(pool)
+                .await
+                .context("Failed to fetch transactions")?;
+
+        let tag_rows: Vec<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT g.transaction_id, g.tag FROM transaction_tags g
+            JOIN transactions t ON t.id = g.transaction_id
+            WHERE t.profile_id = ?
+            "#,
+        )
+        .bind(profile_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch transaction tags")?;
+        let mut tags: HashMap<Uuid, Vec<String>> = HashMap::new();
+        for (transaction_id, tag) in tag_rows {
+            tags.entry(Uuid::parse_str(&transaction_id)?)
+                .or_default()
+                .push(tag);
+        }
+
+        Ok(Self {
+            method,
+            fiat_currency,
+            owned,
+            transactions,
+            tags,
+        })
+    }
+
+    /// Same inputs valued with the transactions' conversions to `currency`
+    pub async fn in_currency(
+        mut self,
+        pool: &Pool<Sqlite>,
+        profile_id: &str,
+        currency: &str,
+    ) -> Result<Self> {
+        let conversions = CurrencyService::new(pool.clone())
+            .get_conversions_to(profile_id, currency)
+            .await?;
+        for tx in &mut self.transactions {
+            let conversion = conversions.get(&tx.id.to_string());
+            tx.amount_primary = conversion.map(|c| c.amount.clone());
+            tx.exchange_rate = conversion.map(|c| c.exchange_rate.clone());
+            tx.primary_currency = Some(currency.to_string());
+        }
+        self.fiat_currency = currency.to_string();
+
+        Ok(self)
+    }
+
+    pub fn build_ledger(&self) -> Result<LotLedger> {
+        build_ledger(
+            self.method,
+            &self.fiat_currency,
+            &self.owned,
+            &self.transactions,
+        )
+    }
+}
+
+/// Replace the stored lots and disposals of a profile
+pub async fn save_lot_ledger(
+    pool: &Pool<Sqlite>,
+    profile_id: &str,
+    ledger: &LotLedger,
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "DELETE FROM tax_lot_matches WHERE disposal_id IN
+            (SELECT transaction_id FROM tax_disposals WHERE profile_id = ?)",
+    )
+    .bind(profile_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM tax_disposals WHERE profile_id = ?")
+        .bind(profile_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM tax_lots WHERE profile_id = ?")
+        .bind(profile_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let method = ledger.method.to_string();
+
+    for lot in &ledger.lots {
+        sqlx::query(
+            r#"
+            INSERT INTO tax_lots (
+                transaction_id, profile_id, asset, chain, acquired_at, quantity,
+                remaining_quantity, cost_basis, fiat_currency, priced, method
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(lot.transaction_id.to_string())
+        .bind(profile_id)
+        .bind(&lot.asset)
+        .bind(&lot.chain)
+        .bind(lot.acquired_at)
+        .bind(lot.quantity.to_string())
+        .bind(lot.remaining_quantity.to_string())
+        .bind(lot.cost_basis.to_string())
+        .bind(&ledger.fiat_currency)
+        .bind(lot.priced)
+        .bind(&method)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to save tax lot")?;
+    }
+
+    for disposal in &ledger.disposals {
+        sqlx::query(
+            r#"
+            INSERT INTO tax_disposals (
+                transaction_id, profile_id, asset, chain, disposed_at, quantity, proceeds,
+                cost_basis, gain, unmatched_quantity, fiat_currency, priced, method
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(disposal.transaction_id.to_string())
+        .bind(profile_id)
+        .bind(&disposal.asset)
+        .bind(&disposal.chain)
+        .bind(disposal.disposed_at)
+        .bind(disposal.quantity.to_string())
+        .bind(disposal.proceeds.to_string())
+        .bind(disposal.cost_basis.to_string())
+        .bind(disposal.gain.to_string())
+        .bind(disposal.unmatched_quantity.to_string())
+        .bind(&ledger.fiat_currency)
+        .bind(disposal.priced)
+        .bind(&method)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to save tax disposal")?;
+
+        for lot_match in &disposal.matches {
+            sqlx::query(
+                r#"
+                INSERT INTO tax_lot_matches (disposal_id, lot_id, quantity, cost_basis, acquired_at)
+                VALUES (?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(disposal.transaction_id.to_string())
+            .bind(lot_match.lot_id.to_string())
+            .bind(lot_match.quantity.to_string())
+            .bind(lot_match.cost_basis.to_string())
+            .bind(lot_match.acquired_at)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to save tax lot match")?;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Load the ledger last persisted for a profile, if any
+pub async fn load_lot_ledger(pool: &Pool<Sqlite>, profile_id: &str) -> Result<Option<LotLedger>> {
+    let lot_rows = sqlx::query_as::<_, LotRow>(
+        "SELECT * FROM tax_lots WHERE profile_id = ? ORDER BY acquired_at, rowid",
+    )
+    .bind(profile_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch tax lots")?;
+
+    let disposal_rows = sqlx::query_as::<_, DisposalRow>(
+        "SELECT * FROM tax_disposals WHERE profile_id = ? ORDER BY disposed_at, rowid",
+    )
+    .bind(profile_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch tax disposals")?;
+
+    let match_rows = sqlx::query_as::<_, LotMatchRow>(
+        r#"
+        SELECT m.* FROM tax_lot_matches m
+        JOIN tax_disposals d ON d.transaction_id = m.disposal_id
+        WHERE d.profile_id = ?
+        ORDER BY m.rowid
+        "#,
+    )
+    .bind(profile_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch tax lot matches")?;
+
+    let Some((method, fiat_currency)) = lot_rows
+        .first()
+        .map(|row| (row.method, row.fiat_currency.clone()))
+        .or_else(|| {
+            disposal_rows
+                .first()
+                .map(|row| (row.method, row.fiat_currency.clone()))
+        })
+    else {
+        return Ok(None);
+    };
+
+    let mut matches: HashMap<String, Vec<LotMatch>> = HashMap::new();
+    for row in match_rows {
+        matches.entry(row.disposal_id).or_default().push(LotMatch {
+            lot_id: Uuid::parse_str(&row.lot_id)?,
+            acquired_at: row.acquired_at,
+            quantity: decimal(&row.quantity)?,
+            cost_basis: decimal(&row.cost_basis)?,
+        });
+    }
+
+    let lots = lot_rows
+        .into_iter()
+        .map(|row| {
+            Ok(Lot {
+                transaction_id: Uuid::parse_str(&row.transaction_id)?,
+                asset: row.asset,
+                chain: row.chain,
+                acquired_at: row.acquired_at,
+                quantity: decimal(&row.quantity)?,
+                remaining_quantity: decimal(&row.remaining_quantity)?,
+                cost_basis: decimal(&row.cost_basis)?,
+                priced: row.priced,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let disposals = disposal_rows
+        .into_iter()
+        .map(|row| {
+            Ok(Disposal {
+                transaction_id: Uuid::parse_str(&row.transaction_id)?,
+                matches: matches.remove(&row.transaction_id).unwrap_or_default(),
+                asset: row.asset,
+                chain: row.chain,
+                disposed_at: row.disposed_at,
+                quantity: decimal(&row.quantity)?,
+                proceeds: decimal(&row.proceeds)?,
+                cost_basis: decimal(&row.cost_basis)?,
+                gain: decimal(&row.gain)?,
+                unmatched_quantity: decimal(&row.unmatched_quantity)?,
+                priced: row.priced,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Some(LotLedger {
+        method,
+        fiat_currency,
+        lots,
+        disposals,
+        fee_disposals: Vec::new(),
+    }))
+}
+
+fn decimal(value: &str) -> Result<Decimal> {
+    Decimal::from_str(value).with_context(|| format!("Invalid stored amount {}", value))
+}
+
+#[derive(FromRow)]
+struct LotRow {
+    transaction_id: String,
+    asset: String,
+    chain: String,
+    acquired_at: DateTime<Utc>,
+    quantity: String,
+    remaining_quantity: String,
+    cost_basis: String,
+    fiat_currency: String,
+    priced: bool,
+    method: CostBasisMethod,
+}
+
+#[derive(FromRow)]
+struct DisposalRow {
+    transaction_id: String,
+    asset: String,
+    chain: String,
+    disposed_at: DateTime<Utc>,
+    quantity: String,
+    proceeds: String,
+    cost_basis: String,
+    gain: String,
+    unmatched_quantity: String,
+    fiat_currency: String,
+    priced: bool,
+    method: CostBasisMethod,
+}
+
+#[derive(FromRow)]
+struct LotMatchRow {
+    disposal_id: String,
+    lot_id: String,
+    quantity: String,
+    cost_basis: String,
+    acquired_at: DateTime<Utc>,
+}
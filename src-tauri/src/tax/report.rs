@@ -0,0 +1,387 @@
+use super::lots::{
+    fiat_value, flow, normalize_address, own_moves, paid_fee, quantity, Flow, LotLedger,
+};
+use crate::core::currency::CostBasisMethod;
+use crate::core::Transaction;
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Months, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use uuid::Uuid;
+
+/// Yearly tax report in the profile's primary currency
+#[derive(Debug, Clone, Serialize)]
+pub struct TaxReport {
+    pub year: i32,
+    pub currency: String,
+    pub cost_basis_method: CostBasisMethod,
+    pub capital_gains: CapitalGains,
+    pub income: Income,
+    pub fees: Fees,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CapitalGains {
+    /// Held for one year or less
+    pub short_term: Vec<GainLine>,
+    pub long_term: Vec<GainLine>,
+    pub total_short_term: Decimal,
+    pub total_long_term: Decimal,
+}
+
+/// Realised gains of one asset and holding period
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GainLine {
+    pub asset: String,
+    pub quantity: Decimal,
+    pub proceeds: Decimal,
+    pub cost_basis: Decimal,
+    pub gain: Decimal,
+    /// False when any disposal or lot behind the line had no fiat value
+    pub priced: bool,
+    /// Disposal and acquisition transactions behind the line
+    pub transaction_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Income {
+    pub lines: Vec<IncomeLine>,
+    pub total: Decimal,
+}
+
+/// Income of one category and asset, valued at receipt
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IncomeLine {
+    /// `staking`, `airdrop` or `defi_yield`
+    pub category: String,
+    pub asset: String,
+    pub quantity: Decimal,
+    pub value: Decimal,
+    pub priced: bool,
+    pub transaction_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Fees {
+    pub lines: Vec<FeeLine>,
+    pub total: Decimal,
+}
+
+/// Network fees paid on one chain
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FeeLine {
+    pub chain: String,
+    pub asset: String,
+    pub quantity: Decimal,
+    pub value: Decimal,
+    pub priced: bool,
+    pub transaction_ids: Vec<Uuid>,
+}
+
+/// Build the report for `year` from a ledger and the transactions it was
+/// built from. `tags` are the user's tags per transaction.
+pub fn build_report(
+    year: i32,
+    ledger: &LotLedger,
+    owned: &[String],
+    transactions: &[Transaction],
+    tags: &HashMap<Uuid, Vec<String>>,
+) -> Result<TaxReport> {
+    let owned: HashSet<String> = owned.iter().map(|a| normalize_address(a)).collect();
+    let moves = own_moves(transactions, &owned)?;
+    let currency = ledger.fiat_currency.as_str();
+
+    Ok(TaxReport {
+        year,
+        currency: currency.to_string(),
+        cost_basis_method: ledger.method,
+        capital_gains: capital_gains(year, ledger),
+        income: income(year, currency, transactions, tags, |tx| {
+            flow(tx, &owned, &moves) == Some(Flow::Acquisition)
+        })?,
+        fees: fees(year, currency, &owned, transactions)?,
+    })
+}
+
+fn capital_gains(year: i32, ledger: &LotLedger) -> CapitalGains {
+    let mut short_term: BTreeMap<String, GainLine> = BTreeMap::new();
+    let mut long_term: BTreeMap<String, GainLine> = BTreeMap::new();
+
+    // Tokens spent on fees are disposed of like any other
+    for disposal in ledger
+        .disposals
+        .iter()
+        .chain(&ledger.fee_disposals)
+        .filter(|d| d.disposed_at.year() == year)
+    {
+        // Proceeds are split across the matched lots by quantity
+        let proceeds_for = |quantity: Decimal| disposal.proceeds * quantity / disposal.quantity;
+
+        for lot_match in &disposal.matches {
+            let lines = if is_long_term(lot_match.acquired_at, disposal.disposed_at) {
+                &mut long_term
+            } else {
+                &mut short_term
+            };
+            add_gain(
+                lines,
+                &disposal.asset,
+                lot_match.quantity,
+                proceeds_for(lot_match.quantity),
+                lot_match.cost_basis,
+                disposal.priced,
+                &[disposal.transaction_id, lot_match.lot_id],
+            );
+        }
+
+        // Without a known acquisition the holding period cannot be shown to be long
+        if !disposal.unmatched_quantity.is_zero() {
+            add_gain(
+                &mut short_term,
+                &disposal.asset,
+                disposal.unmatched_quantity,
+                proceeds_for(disposal.unmatched_quantity),
+                Decimal::ZERO,
+                false,
+                &[disposal.transaction_id],
+            );
+        }
+    }
+
+    let short_term: Vec<GainLine> = short_term.into_values().collect();
+    let long_term: Vec<GainLine> = long_term.into_values().collect();
+
+    CapitalGains {
+        total_short_term: short_term.iter().map(|l| l.gain).sum(),
+        total_long_term: long_term.iter().map(|l| l.gain).sum(),
+        short_term,
+        long_term,
+    }
+}
+
+fn add_gain(
+    lines: &mut BTreeMap<String, GainLine>,
+    asset: &str,
+    quantity: Decimal,
+    proceeds: Decimal,
+    cost_basis: Decimal,
+    priced: bool,
+    transaction_ids: &[Uuid],
+) {
+    let line = lines.entry(asset.to_string()).or_insert_with(|| GainLine {
+        asset: asset.to_string(),
+        priced: true,
+        ..Default::default()
+    });
+    // Splitting proceeds leaves trailing zeros once the parts add back up
+    line.quantity += quantity;
+    line.proceeds = (line.proceeds + proceeds).normalize();
+    line.cost_basis = (line.cost_basis + cost_basis).normalize();
+    line.gain = line.proceeds - line.cost_basis;
+    line.priced &= priced;
+    for id in transaction_ids {
+        if !line.transaction_ids.contains(id) {
+            line.transaction_ids.push(*id);
+        }
+    }
+}
+
+/// Held for more than a year counts as long-term
+fn is_long_term(acquired_at: DateTime<Utc>, disposed_at: DateTime<Utc>) -> bool {
+    acquired_at
+        .checked_add_months(Months::new(12))
+        .is_some_and(|anniversary| disposed_at > anniversary)
+}
+
+/// Income category of a transaction, if it is income at all. Staking rewards
+/// are told apart on chain; airdrops and DeFi yield look like any incoming
+/// transfer, so they carry the user's `airdrop`, `defi_yield` or `yield` tag.
+pub fn income_category(
+    tx: &Transaction,
+    tags: &HashMap<Uuid, Vec<String>>,
+) -> Option<&'static str> {
+    if tx.transaction_type == "staking_reward" {
+        return Some("staking");
+    }
+
+    tags.get(&tx.id)?
+        .iter()
+        .find_map(|tag| match tag.to_lowercase().as_str() {
+            "airdrop" => Some("airdrop"),
+            "defi_yield" | "yield" => Some("defi_yield"),
+            _ => None,
+        })
+}
+
+/// Income received in `year`; `received` tells acquisitions apart, so a
+/// tagged outgoing transfer is not income
+fn income(
+    year: i32,
+    currency: &str,
+    transactions: &[Transaction],
+    tags: &HashMap<Uuid, Vec<String>>,
+    received: impl Fn(&Transaction) -> bool,
+) -> Result<Income> {
+    let mut lines: BTreeMap<(&str, &str), IncomeLine> = BTreeMap::new();
+
+    for tx in transactions
+        .iter()
+        .filter(|tx| tx.timestamp.year() == year && received(tx))
+    {
+        let Some(category) = income_category(tx, tags) else {
+            continue;
+        };
+
+        let quantity = quantity(tx)?;
+        let value = fiat_value(tx, quantity, currency);
+
+        let line = lines
+            .entry((category, tx.token_symbol.as_str()))
+            .or_insert_with(|| IncomeLine {
+                category: category.to_string(),
+                asset: tx.token_symbol.clone(),
+                priced: true,
+                ..Default::default()
+            });
+        line.quantity += quantity;
+        line.value += value.unwrap_or_default();
+        line.priced &= value.is_some();
+        line.transaction_ids.push(tx.id);
+    }
+
+    let lines: Vec<IncomeLine> = lines.into_values().collect();
+    Ok(Income {
+        total: lines.iter().map(|l| l.value).sum(),
+        lines,
+    })
+}
+
+fn fees(
+    year: i32,
+    currency: &str,
+    owned: &HashSet<String>,
+    transactions: &[Transaction],
+) -> Result<Fees> {
+    let mut lines: BTreeMap<(&str, &str), FeeLine> = BTreeMap::new();
+
+    // Failed transactions still cost their fee
+    for tx in transactions.iter().filter(|tx| tx.timestamp.year() == year) {
+        let Some((quantity, value)) = paid_fee(tx, owned, currency)? else {
+            continue;
+        };
+
+        let line = lines
+            .entry((tx.chain.as_str(), tx.token_symbol.as_str()))
+            .or_insert_with(|| FeeLine {
+                chain: tx.chain.clone(),
+                asset: tx.token_symbol.clone(),
+                priced: true,
+                ..Default::default()
+            });
+        line.quantity += quantity;
+        line.value += value.unwrap_or_default();
+        line.priced &= value.is_some();
+        line.transaction_ids.push(tx.id);
+    }
+
+    let lines: Vec<FeeLine> = lines.into_values().collect();
+    Ok(Fees {
+        total: lines.iter().map(|l| l.value).sum(),
+        lines,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tax::build_ledger;
+    use chrono::TimeZone;
+
+    const ME: &str = "0x1111111111111111111111111111111111111111";
+    const EXCHANGE: &str = "0x2222222222222222222222222222222222222222";
+
+    fn tx(at: DateTime<Utc>, kind: &str, from: &str, to: &str, dot: i64, usd: &str) -> Transaction {
+        Transaction {
+            id: Uuid::new_v4(),
+            profile_id: Some("profile-1".to_string()),
+            chain: "moonbeam".to_string(),
+            hash: format!("0x{:064x}", at.timestamp()),
+            log_index: -1,
+            from_address: from.to_string(),
+            to_address: Some(to.to_string()),
+            value: (dot as i128 * 10_i128.pow(10)).to_string(),
+            token_symbol: "DOT".to_string(),
+            token_decimals: 10,
+            timestamp: at,
+            block_number: at.timestamp(),
+            transaction_type: kind.to_string(),
+            status: "confirmed".to_string(),
+            fee: None,
+            metadata: serde_json::json!({}),
+            amount_primary: Some(usd.to_string()),
+            primary_currency: Some("USD".to_string()),
+            exchange_rate: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_report_sections() {
+        let old_buy = tx(date(2023, 1, 1), "transfer", EXCHANGE, ME, 10, "50");
+        let reward = tx(date(2024, 3, 1), "staking_reward", "", ME, 2, "14");
+        let mut sale = tx(date(2024, 6, 1), "transfer", ME, EXCHANGE, 12, "96");
+        // 0.01 DOT fee at the sale's 8 USD/DOT
+        sale.fee = Some("100000000".to_string());
+        // Incoming transfer whose fee the sender paid
+        let mut gift = tx(date(2024, 7, 1), "transfer", EXCHANGE, ME, 1, "8");
+        gift.fee = Some("100000000".to_string());
+        // Only the user's tag tells an airdrop from a transfer
+        let airdrop = tx(date(2024, 8, 1), "transfer", EXCHANGE, ME, 3, "6");
+        let tags = HashMap::from([(airdrop.id, vec!["Airdrop".to_string()])]);
+
+        let transactions = vec![
+            old_buy.clone(),
+            reward.clone(),
+            sale.clone(),
+            gift,
+            airdrop.clone(),
+        ];
+        let owned = vec![ME.to_string()];
+        let ledger = build_ledger(CostBasisMethod::Fifo, "USD", &owned, &transactions).unwrap();
+        let report = build_report(2024, &ledger, &owned, &transactions, &tags).unwrap();
+
+        // 10 DOT held since 2023 sold for 80, 2 DOT from the reward sold for 16
+        let long = &report.capital_gains.long_term[0];
+        assert_eq!(long.quantity, Decimal::from(10));
+        assert_eq!(long.gain, Decimal::from(30));
+        assert_eq!(long.transaction_ids, vec![sale.id, old_buy.id]);
+
+        // plus the 0.01 DOT fee worth 0.08, spent after the sale emptied every lot
+        let short = &report.capital_gains.short_term[0];
+        assert_eq!(short.quantity, Decimal::new(201, 2));
+        assert_eq!(short.gain, Decimal::new(208, 2));
+        assert!(!short.priced);
+        assert_eq!(short.transaction_ids, vec![sale.id, reward.id]);
+
+        assert_eq!(report.income.lines.len(), 2);
+        assert_eq!(report.income.lines[0].category, "airdrop");
+        assert_eq!(report.income.lines[0].transaction_ids, vec![airdrop.id]);
+        assert_eq!(report.income.lines[1].category, "staking");
+        assert_eq!(report.income.lines[1].transaction_ids, vec![reward.id]);
+        assert_eq!(report.income.total, Decimal::from(20));
+
+        assert_eq!(report.fees.lines.len(), 1);
+        assert_eq!(report.fees.total, Decimal::new(8, 2));
+        assert_eq!(report.fees.lines[0].transaction_ids, vec![sale.id]);
+
+        let empty = build_report(2025, &ledger, &owned, &transactions, &tags).unwrap();
+        assert!(empty.capital_gains.long_term.is_empty());
+        assert!(empty.income.lines.is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+import React, { useEffect, useState } from 'react'
+import { invoke } from '@tauri-apps/api/core'
+import {
+  getSecretsStatus,
+  unlockSecrets,
+  type SecretStoreStatus,
+} from '../../services/secretsService'
+
+interface StartupGateProps {
+  children: React.ReactNode
+}
+
+/**
+ * Renders the app only once the backend started, otherwise explains why it
+ * could not, e.g. a failed database migration. After a normal start it asks
+ * for the passphrase of the secret store holding the price feed API keys.
+ */
+const StartupGate: React.FC<StartupGateProps> = ({ children }) => {
+  const [checked, setChecked] = useState(false)
+  const [error, setError] = useState<string | null>(null)
+  const [secrets, setSecrets] = useState<SecretStoreStatus | null>(null)
+
+  useEffect(() => {
+    invoke<string | null>('get_startup_error')
+      .then(async startupError => {
+        setError(startupError)
+        if (!startupError) {
+          setSecrets(await getSecretsStatus())
+        }
+      })
+      // Outside Tauri (plain browser dev server) there is no backend to check
+      .catch(() => setError(null))
+      .finally(() => setChecked(true))
+  }, [])
+
+  if (!checked) {
+    return null
+  }
+
+  if (error) {
+    return (
+      <div className="min-h-screen bg-gray-50 dark:bg-black flex items-center justify-center p-8">
+        <div className="max-w-xl w-full bg-white dark:bg-gray-900 border border-red-200 dark:border-red-900 rounded-lg p-6">
+          <h1 className="text-xl font-semibold text-red-700 dark:text-red-400">
+            Pacioli could not start
+          </h1>
+          <p className="mt-2 text-gray-700 dark:text-gray-300">
+            The database could not be opened or updated. Restore a backup or
+            contact support with the details below.
+          </p>
+          <pre className="mt-4 p-3 bg-gray-100 dark:bg-gray-800 rounded text-sm text-gray-800 dark:text-gray-200 whitespace-pre-wrap break-words">
+            {error}
+          </pre>
+        </div>
+      </div>
+    )
+  }
+
+  if (secrets && !secrets.unlocked) {
+    return (
+      <SecretsUnlock
+        initialized={secrets.initialized}
+        onDone={() => setSecrets(null)}
+      />
+    )
+  }
+
+  return <>{children}</>
+}
+
+interface SecretsUnlockProps {
+  initialized: boolean
+  /** Called once unlocked, or when the user continues without API keys */
+  onDone: () => void
+}
+
+const SecretsUnlock: React.FC<SecretsUnlockProps> = ({
+  initialized,
+  onDone,
+}) => {
+  const [passphrase, setPassphrase] = useState('')
+  const [unlockError, setUnlockError] = useState<string | null>(null)
+  const [busy, setBusy] = useState(false)
+
+  const handleUnlock = async (e: React.FormEvent) => {
+    e.preventDefault()
+    setBusy(true)
+    setUnlockError(null)
+    try {
+      await unlockSecrets(passphrase)
+      onDone()
+    } catch (err) {
+      setUnlockError(String(err))
+    } finally {
+      setBusy(false)
+    }
+  }
+
+  return (
+    <div className="min-h-screen bg-gray-50 dark:bg-black flex items-center justify-center p-8">
+      <form
+        onSubmit={handleUnlock}
+        className="max-w-md w-full bg-white dark:bg-gray-900 border border-gray-200 dark:border-gray-800 rounded-lg p-6"
+      >
+        <h1 className="text-xl font-semibold text-gray-900 dark:text-white">
+          {initialized ? 'Unlock API keys' : 'Protect your API keys'}
+        </h1>
+        <p className="mt-2 text-gray-700 dark:text-gray-300">
+          {initialized
+            ? 'Enter your passphrase to use your CoinGecko and Fixer API keys.'
+            : 'Choose a passphrase to encrypt your CoinGecko and Fixer API keys.'}{' '}
+          Without it prices are fetched from the free APIs.
+        </p>
+        <label
+          htmlFor="secretsPassphrase"
+          className="block mt-4 text-sm font-medium text-gray-700 dark:text-gray-300 mb-2"
+        >
+          Passphrase
+        </label>
+        <input
+          id="secretsPassphrase"
+          type="password"
+          autoFocus
+          value={passphrase}
+          onChange={e => setPassphrase(e.target.value)}
+          className="w-full px-3 py-2 border border-gray-300 dark:border-gray-600 rounded-lg bg-white dark:bg-gray-800 text-gray-900 dark:text-white focus:outline-none focus:ring-2 focus:ring-blue-500"
+        />
+        {unlockError && (
+          <p className="mt-2 text-sm text-red-700 dark:text-red-400">
+            {unlockError}
+          </p>
+        )}
+        <div className="mt-6 flex justify-end space-x-3">
+          <button
+            type="button"
+            onClick={onDone}
+            className="px-4 py-2 text-sm font-medium text-gray-700 dark:text-gray-300 bg-white dark:bg-gray-800 border border-gray-300 dark:border-gray-600 rounded-lg hover:bg-gray-50 dark:hover:bg-gray-700"
+          >
+            Continue without keys
+          </button>
+          <button
+            type="submit"
+            disabled={busy || passphrase.length === 0}
+            className="px-4 py-2 text-sm font-medium text-white bg-blue-600 rounded-lg hover:bg-blue-700 disabled:opacity-50"
+          >
+            {initialized ? 'Unlock' : 'Set passphrase'}
+          </button>
+        </div>
+      </form>
+    </div>
+  )
+}
+
+export default StartupGate
@@ -128,7 +128,7 @@ export const useEVMService = () => {
   )
 
   const syncTransactions = useCallback(
-    async (chain?: string, address?: string) => {
+    async (profileId: string, chain?: string, address?: string) => {
       if (!chain || !address) {
         if (!currentAccount) return null
         chain = currentAccount.chain
@@ -136,9 +136,9 @@ export const useEVMService = () => {
       }
 
       try {
-        const result = await EVMService.syncEVMTransactions(chain, address)
-        toast.success(result)
-        return result
+        const started = await EVMService.startSync(profileId, chain, address)
+        toast.success(started ? 'Sync started' : 'Sync already running')
+        return started
       } catch (error: unknown) {
         toast.error(getErrorMessage(error) || 'Failed to sync transactions')
         throw error
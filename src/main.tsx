@@ -5,15 +5,18 @@ import './index.css'
 import { ThemeProvider } from './contexts/ThemeContext'
 import { CurrencyProvider } from './contexts/CurrencyContext'
 import { OrganizationProvider } from './contexts/OrganizationContext'
+import StartupGate from './components/common/StartupGate'
 
 ReactDOM.createRoot(document.getElementById('root') as HTMLElement).render(
   <React.StrictMode>
     <ThemeProvider>
-      <CurrencyProvider>
-        <OrganizationProvider>
-          <App />
-        </OrganizationProvider>
-      </CurrencyProvider>
+      <StartupGate>
+        <CurrencyProvider>
+          <OrganizationProvider>
+            <App />
+          </OrganizationProvider>
+        </CurrencyProvider>
+      </StartupGate>
     </ThemeProvider>
   </React.StrictMode>
 )
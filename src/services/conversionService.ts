@@ -0,0 +1,96 @@
+/**
+ * Conversion of stored transactions to the primary and reporting currencies
+ * Settings changes that affect conversions reconvert in the background
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
+import type { ConversionMethod, ExchangeRateSource } from '../types/currency'
+
+/** Event with the report of a background conversion run */
+export const CONVERSION_EVENT = 'currency://conversions'
+
+/** Currency settings as stored by the backend */
+export interface AccountSettingsRecord {
+  id: string
+  profile_id: string
+  primary_currency: string
+  /** Comma-separated */
+  reporting_currencies: string | null
+  conversion_method: ConversionMethod
+  cost_basis_method: 'fifo' | 'lifo' | 'hifo' | 'average'
+  decimal_places: number
+  use_thousands_separator: boolean
+  currency_display_format: 'symbol' | 'code' | 'name'
+  auto_convert: boolean
+  cache_exchange_rates: boolean
+  /**
+   * Comma-separated providers to ask for missing rates, first to last, e.g.
+   * 'manual,coingecko'. Null keeps the default 'coingecko,fixer,manual'.
+   */
+  price_provider_priority: string | null
+  created_at: string
+  updated_at: string
+}
+
+export interface UnpricedTransaction {
+  transaction_id: string
+  currency: string
+  token_symbol: string
+  timestamp: string
+  reason: string
+}
+
+export interface ConversionReport {
+  profile_id: string
+  primary_currency: string
+  /** Primary currency first */
+  currencies: string[]
+  method: ConversionMethod
+  /** One per transaction and currency */
+  converted: number
+  unpriced: UnpricedTransaction[]
+}
+
+/** Value of a transaction in one reporting currency */
+export interface TransactionConversion {
+  transaction_id: string
+  currency: string
+  amount: string
+  /** Per whole token */
+  exchange_rate: string
+  /** Absent when the transaction is in this currency */
+  exchange_rate_source: ExchangeRateSource | null
+  exchange_rate_timestamp: string | null
+  conversion_method: ConversionMethod
+  created_at: string
+  updated_at: string
+}
+
+export async function getAccountSettings(
+  profileId: string
+): Promise<AccountSettingsRecord | null> {
+  return invoke<AccountSettingsRecord | null>('get_account_settings', { profileId })
+}
+
+/** New primary or reporting currencies or a new method trigger a `CONVERSION_EVENT` when done */
+export async function updateAccountSettings(settings: AccountSettingsRecord): Promise<void> {
+  return invoke<void>('update_account_settings', { settings })
+}
+
+/** Convert transactions lacking a conversion to one of the reporting currencies */
+export async function convertTransactions(profileId: string): Promise<ConversionReport> {
+  return invoke<ConversionReport>('convert_transactions', { profileId })
+}
+
+export async function getTransactionConversions(
+  transactionId: string
+): Promise<TransactionConversion[]> {
+  return invoke<TransactionConversion[]>('get_transaction_conversions', { transactionId })
+}
+
+export async function onConversionReport(
+  handler: (report: ConversionReport) => void
+): Promise<UnlistenFn> {
+  return listen<ConversionReport>(CONVERSION_EVENT, (event) => handler(event.payload))
+}
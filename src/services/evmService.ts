@@ -13,6 +13,19 @@ export interface EVMChain {
   explorer: string
 }
 
+/** Sync checkpoint of one account, as returned by `get_sync_states` */
+export interface AccountSyncState {
+  account_id: string
+  chain: string
+  address: string
+  start_block: number
+  last_synced_block: number | null
+  finalized_block: number
+  last_sync_time: string | null
+  last_error: string | null
+  last_error_at: string | null
+}
+
 export const EVM_CHAINS: Record<string, EVMChain> = {
   moonbeam: {
     name: 'Moonbeam',
@@ -106,8 +119,38 @@ export class EVMService {
     }
   }
 
-  static async syncEVMTransactions(chain: string, address: string): Promise<string> {
-    return invoke<string>('sync_evm_transactions', { chain, address })
+  /**
+   * Start a background sync; progress arrives as `sync://progress` events.
+   * Resolves to false if the same sync is already running.
+   */
+  static async startSync(
+    profileId: string,
+    chain: string,
+    address: string
+  ): Promise<boolean> {
+    return invoke<boolean>('start_sync', { profileId, chain, address })
+  }
+
+  static async cancelSync(
+    profileId: string,
+    chain: string,
+    address: string
+  ): Promise<boolean> {
+    return invoke<boolean>('cancel_sync', { profileId, chain, address })
+  }
+
+  static async getSyncStates(profileId: string): Promise<AccountSyncState[]> {
+    return invoke<AccountSyncState[]>('get_sync_states', { profileId })
+  }
+
+  /** Skip the blocks before an account's first activity */
+  static async setSyncStartBlock(
+    profileId: string,
+    chain: string,
+    address: string,
+    startBlock: number
+  ): Promise<void> {
+    return invoke<void>('set_sync_start_block', { profileId, chain, address, startBlock })
   }
 
   static async getTokenBalances(chain: string, address: string): Promise<[string, string][]> {
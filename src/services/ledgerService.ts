@@ -0,0 +1,93 @@
+/**
+ * General ledger
+ * Double-entry journal generated from the profile's transactions, and the
+ * trial balance and statements built from it
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+
+export type LedgerAccountType =
+  | 'asset'
+  | 'liability'
+  | 'equity'
+  | 'income'
+  | 'expense'
+
+export interface LedgerAccount {
+  code: string
+  name: string
+  account_type: LedgerAccountType
+  is_active: boolean
+}
+
+/** Totals of one account, balance signed by its normal side. Decimal strings. */
+export interface AccountBalance {
+  code: string
+  name: string
+  account_type: LedgerAccountType
+  debit: string
+  credit: string
+  balance: string
+}
+
+export interface BalanceSheet {
+  as_of: string
+  assets: AccountBalance[]
+  liabilities: AccountBalance[]
+  equity: AccountBalance[]
+  /** Income less expenses not yet closed into an equity account */
+  retained_earnings: string
+  total_assets: string
+  total_liabilities: string
+  total_equity: string
+}
+
+export interface IncomeStatement {
+  from: string
+  to: string
+  income: AccountBalance[]
+  expenses: AccountBalance[]
+  total_income: string
+  total_expenses: string
+  net_income: string
+}
+
+/** Chart of accounts, with the default accounts created on first use */
+export async function getLedgerAccounts(
+  profileId: string
+): Promise<LedgerAccount[]> {
+  return invoke<LedgerAccount[]>('get_ledger_accounts', { profileId })
+}
+
+/** Rebuild the entries of the profile's transactions, returning how many */
+export async function generateJournalEntries(profileId: string): Promise<number> {
+  return invoke<number>('generate_journal_entries', { profileId })
+}
+
+/** Dates are RFC 3339 timestamps; without a period all entries count */
+export async function getTrialBalance(
+  profileId: string,
+  from?: string,
+  to?: string
+): Promise<AccountBalance[]> {
+  return invoke<AccountBalance[]>('get_trial_balance', {
+    profileId,
+    from: from ?? null,
+    to: to ?? null,
+  })
+}
+
+export async function getBalanceSheet(
+  profileId: string,
+  asOf: string
+): Promise<BalanceSheet> {
+  return invoke<BalanceSheet>('get_balance_sheet', { profileId, asOf })
+}
+
+export async function getIncomeStatement(
+  profileId: string,
+  from: string,
+  to: string
+): Promise<IncomeStatement> {
+  return invoke<IncomeStatement>('get_income_statement', { profileId, from, to })
+}
@@ -0,0 +1,53 @@
+/**
+ * Manual rate book
+ * Board-approved rates with validity periods, used by the fixed conversion method
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+
+export interface ManualRate {
+  id: string
+  profile_id: string
+  from_currency: string
+  to_currency: string
+  rate: string
+  /** YYYY-MM-DD, inclusive */
+  valid_from: string
+  /** YYYY-MM-DD, inclusive, null while open-ended */
+  valid_to: string | null
+  note: string | null
+  created_at: string
+  updated_at: string
+}
+
+export interface NewManualRate {
+  from_currency: string
+  to_currency: string
+  /** Decimal string */
+  rate: string
+  valid_from: string
+  valid_to?: string | null
+  note?: string | null
+}
+
+export async function listManualRates(profileId: string): Promise<ManualRate[]> {
+  return invoke<ManualRate[]>('list_manual_rates', { profileId })
+}
+
+/** Rejects rates whose period overlaps another rate for the same pair */
+export async function addManualRate(profileId: string, rate: NewManualRate): Promise<ManualRate> {
+  return invoke<ManualRate>('add_manual_rate', { profileId, rate })
+}
+
+/**
+ * Import CSV with the header
+ * `from_currency,to_currency,rate,valid_from,valid_to,note`.
+ * Nothing is imported if any row is invalid.
+ */
+export async function importManualRates(profileId: string, csv: string): Promise<number> {
+  return invoke<number>('import_manual_rates', { profileId, csv })
+}
+
+export async function deleteManualRate(profileId: string, rateId: string): Promise<void> {
+  return invoke<void>('delete_manual_rate', { profileId, rateId })
+}
@@ -0,0 +1,42 @@
+/**
+ * Daily price history
+ * Permanent daily prices per asset and fiat currency, filled in bulk from CoinGecko
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+import type { ExchangeRateSource } from '../types/currency'
+
+export interface DailyPrice {
+  asset: string
+  fiat: string
+  /** YYYY-MM-DD */
+  date: string
+  /** First price of the day, null when the feed has one price per day */
+  open: string | null
+  /** Last price of the day */
+  close: string
+  source: ExchangeRateSource
+  created_at: string
+  updated_at: string
+}
+
+/** Stored prices between two YYYY-MM-DD dates, inclusive */
+export async function getPriceHistory(
+  asset: string,
+  fiat: string,
+  from: string,
+  to: string
+): Promise<DailyPrice[]> {
+  return invoke<DailyPrice[]>('get_price_history', { asset, fiat, from, to })
+}
+
+/** Fetch the days missing in a range, returning how many were added */
+export async function backfillPriceHistory(
+  profileId: string,
+  asset: string,
+  fiat: string,
+  from: string,
+  to: string
+): Promise<number> {
+  return invoke<number>('backfill_price_history', { profileId, asset, fiat, from, to })
+}
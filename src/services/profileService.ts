@@ -0,0 +1,83 @@
+/**
+ * Profile and account management
+ * Profiles group the addresses tracked together, accounts are validated per chain
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+
+export interface Profile {
+  id: string
+  name: string
+  avatar_url: string | null
+  /** Set while the profile is archived */
+  archived_at: string | null
+  created_at: string
+  updated_at: string
+}
+
+export interface Account {
+  id: string
+  profile_id: string
+  /** EVM addresses are stored lowercase */
+  address: string
+  chain: string
+  nickname: string | null
+  created_at: string
+  updated_at: string
+}
+
+export async function listProfiles(includeArchived = false): Promise<Profile[]> {
+  return invoke<Profile[]>('list_profiles', { includeArchived })
+}
+
+export async function createProfile(name: string, avatarUrl?: string): Promise<Profile> {
+  return invoke<Profile>('create_profile', { name, avatarUrl })
+}
+
+export async function renameProfile(profileId: string, name: string): Promise<Profile> {
+  return invoke<Profile>('rename_profile', { profileId, name })
+}
+
+export async function setProfileAvatar(
+  profileId: string,
+  avatarUrl: string | null
+): Promise<Profile> {
+  return invoke<Profile>('set_profile_avatar', { profileId, avatarUrl })
+}
+
+export async function setProfileArchived(
+  profileId: string,
+  archived: boolean
+): Promise<Profile> {
+  return invoke<Profile>('set_profile_archived', { profileId, archived })
+}
+
+/** Removes the profile and everything recorded for it */
+export async function deleteProfile(profileId: string): Promise<void> {
+  return invoke<void>('delete_profile', { profileId })
+}
+
+export async function listAccounts(profileId: string): Promise<Account[]> {
+  return invoke<Account[]>('list_accounts', { profileId })
+}
+
+/** Rejects addresses that are not valid on the chain */
+export async function addAccount(
+  profileId: string,
+  chain: string,
+  address: string,
+  nickname?: string
+): Promise<Account> {
+  return invoke<Account>('add_account', { profileId, chain, address, nickname })
+}
+
+export async function renameAccount(
+  accountId: string,
+  nickname: string | null
+): Promise<Account> {
+  return invoke<Account>('rename_account', { accountId, nickname })
+}
+
+export async function removeAccount(accountId: string): Promise<void> {
+  return invoke<void>('remove_account', { accountId })
+}
@@ -0,0 +1,50 @@
+/**
+ * Secret store
+ * Price feed API keys encrypted under a passphrase. The store starts locked on
+ * every launch; until it is unlocked the price feeds run without keys.
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+
+export type PriceFeed = 'coingecko' | 'fixer'
+
+export interface SecretStoreStatus {
+  /** A passphrase has been set */
+  initialized: boolean
+  unlocked: boolean
+}
+
+export async function getSecretsStatus(): Promise<SecretStoreStatus> {
+  return invoke<SecretStoreStatus>('get_secrets_status')
+}
+
+/** Sets the passphrase on first use, rejects a wrong one afterwards */
+export async function unlockSecrets(passphrase: string): Promise<void> {
+  return invoke('unlock_secrets', { passphrase })
+}
+
+export async function lockSecrets(): Promise<void> {
+  return invoke('lock_secrets')
+}
+
+/** Store an API key, or remove it with null. Keys are never read back. */
+export async function setPriceFeedApiKey(
+  profileId: string,
+  feed: PriceFeed,
+  apiKey: string | null
+): Promise<void> {
+  return invoke('set_price_feed_api_key', { profileId, feed, apiKey })
+}
+
+/** Price feeds the profile has an API key for */
+export async function getConfiguredApiKeys(profileId: string): Promise<PriceFeed[]> {
+  return invoke<PriceFeed[]>('get_configured_api_keys', { profileId })
+}
+
+/** Re-encrypt all secrets under a new passphrase, returning how many */
+export async function rotateSecretsKey(
+  currentPassphrase: string,
+  newPassphrase: string
+): Promise<number> {
+  return invoke<number>('rotate_secrets_key', { currentPassphrase, newPassphrase })
+}
@@ -0,0 +1,83 @@
+/**
+ * Transaction history queries
+ * Pages through a profile's stored transactions with typed filters
+ */
+
+import { invoke } from '@tauri-apps/api/core'
+
+/** Empty or missing fields match everything */
+export interface TransactionFilter {
+  profile_id: string
+  chains?: string[]
+  account_id?: string
+  token_symbols?: string[]
+  transaction_types?: string[]
+  statuses?: string[]
+  /** Amount bounds in whole tokens, inclusive */
+  min_amount?: string
+  max_amount?: string
+  counterparty?: string
+  /** Only transactions carrying all of these tags */
+  tags?: string[]
+  /** RFC 3339, inclusive */
+  from?: string
+  /** RFC 3339, exclusive */
+  to?: string
+}
+
+export type SortField = 'timestamp' | 'block_number' | 'amount'
+
+export interface PageRequest {
+  sort?: SortField
+  direction?: 'asc' | 'desc'
+  /** `next_cursor` of the previous page */
+  cursor?: string | null
+  limit?: number
+}
+
+export interface StoredTransaction {
+  id: string
+  profile_id: string | null
+  chain: string
+  hash: string
+  log_index: number
+  from_address: string
+  to_address: string | null
+  value: string
+  token_symbol: string
+  token_decimals: number
+  timestamp: string
+  block_number: number
+  transaction_type: string
+  status: string
+  fee: string | null
+  metadata: unknown
+  amount_primary: string | null
+  primary_currency: string | null
+  exchange_rate: string | null
+  created_at: string
+  updated_at: string
+}
+
+export interface TransactionPage {
+  transactions: StoredTransaction[]
+  next_cursor: string | null
+}
+
+export async function queryTransactions(
+  filter: TransactionFilter,
+  page?: PageRequest
+): Promise<TransactionPage> {
+  return invoke<TransactionPage>('query_transactions', { filter, page })
+}
+
+export async function getTransactionTags(transactionId: string): Promise<string[]> {
+  return invoke<string[]>('get_transaction_tags', { transactionId })
+}
+
+export async function setTransactionTags(
+  transactionId: string,
+  tags: string[]
+): Promise<void> {
+  return invoke<void>('set_transaction_tags', { transactionId, tags })
+}
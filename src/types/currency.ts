@@ -22,6 +22,8 @@ export interface Currency {
   fixerId?: string // For fiat exchange rates from Fixer
   symbol?: string // Display symbol (e.g., $, €, ₿)
   iconUrl?: string // URL to currency icon
+  isSubstrate: boolean // Native token of a Substrate chain
+  chainId?: string // e.g. 'polkadot', 'kusama'
   createdAt: string
   updatedAt: string
 }
@@ -43,6 +45,7 @@ export interface ExchangeRate {
   source: ExchangeRateSource
   ttlSeconds: number // Time to live: 300s (5 min) for crypto, 86400s (24h) for fiat
   metadata?: string // JSON string for additional data
+  isHistorical: boolean // Rate for a past date, kept regardless of TTL
   createdAt: string
   updatedAt: string
 }
@@ -129,6 +132,8 @@ export interface CurrencyConversion {
   convertedAmount: string
   exchangeRate: string
   timestamp: string
+  source?: ExchangeRateSource // Absent when no conversion was needed
+  rateTimestamp?: string // When the applied rate was in effect
 }
 
 /**